@@ -4,7 +4,7 @@ use std::sync::Arc;
 use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
 
 use crimeline::arena::{Cold, Hot};
-use crimeline::Window;
+use crimeline::{Uid, Window};
 
 const SIZES: &[usize] = &[100, 1_000, 10_000, 100_000];
 const BLOB_SIZE: usize = 256;
@@ -16,7 +16,7 @@ fn make_blob(i: usize) -> Vec<u8> {
 fn populated_hot(n: usize) -> Hot {
     let mut hot = Hot::new(Window::new(0, (n as u32 + 1) * 10)).unwrap();
     for i in 0..n {
-        hot.add(i as u32, i as u64, (i as u64) * 10, &make_blob(i))
+        hot.add(i as Uid, i as u64, (i as u64) * 10, &make_blob(i))
             .unwrap();
     }
     hot
@@ -33,6 +33,39 @@ fn exported_bytes(n: usize) -> bytes::Bytes {
     bytes::Bytes::from(buf)
 }
 
+/// Hot::add throughput with and without cid dedup
+fn bench_add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("arena/add");
+    let size = 10_000;
+    let blobs: Vec<Vec<u8>> = (0..size).map(make_blob).collect();
+
+    for dedup in [true, false] {
+        let label = if dedup { "dedup" } else { "no_dedup" };
+
+        group.bench_with_input(BenchmarkId::new(label, size), &size, |b, &size| {
+            b.iter_batched(
+                || {
+                    let span = Window::new(0, (size as u32 + 1) * 10);
+                    if dedup {
+                        Hot::new(span).unwrap()
+                    } else {
+                        Hot::without_dedup(span).unwrap()
+                    }
+                },
+                |mut hot| {
+                    for (i, blob) in blobs.iter().enumerate() {
+                        hot.add(i as Uid, i as u64, (i as u64) * 10, blob).unwrap();
+                    }
+                    black_box(hot)
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+
+    group.finish();
+}
+
 /// Hot → Cold conversion (sort + blob rewrite)
 fn bench_hot_to_cold(c: &mut Criterion) {
     let mut group = c.benchmark_group("arena/hot_to_cold");
@@ -45,6 +78,18 @@ fn bench_hot_to_cold(c: &mut Criterion) {
                 BatchSize::SmallInput,
             );
         });
+
+        group.bench_with_input(
+            BenchmarkId::new("assume_sorted", size),
+            &size,
+            |b, &size| {
+                b.iter_batched(
+                    || populated_hot(size),
+                    |hot| black_box(hot.freeze_assume_sorted().unwrap()),
+                    BatchSize::SmallInput,
+                );
+            },
+        );
     }
 
     group.finish();
@@ -66,6 +111,6 @@ fn bench_import(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_hot_to_cold, bench_import);
+criterion_group!(benches, bench_add, bench_hot_to_cold, bench_import);
 
 criterion_main!(benches);
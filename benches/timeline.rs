@@ -4,7 +4,7 @@ use std::sync::Arc;
 use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 
 use crimeline::arena::{Cold, Hot};
-use crimeline::{Order, Timeline, Window};
+use crimeline::{Order, Timeline, Uid, Window};
 
 const ARENA_COUNTS: &[usize] = &[1, 5, 10, 50];
 const BLOB_SIZE: usize = 256;
@@ -19,7 +19,7 @@ fn make_cold(epoch: u64, n: usize) -> Arc<Cold> {
     let mut hot = Hot::new(Window::new(epoch, duration)).unwrap();
     for i in 0..n {
         hot.add(
-            i as u32,
+            i as Uid,
             epoch + i as u64,
             epoch + (i as u64) * 10,
             &make_blob(i),
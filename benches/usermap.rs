@@ -2,7 +2,7 @@ use std::hint::black_box;
 
 use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
 
-use crimeline::{Sharding, UserMap};
+use crimeline::{Relationships, Sharding, Uid, UserMap};
 
 const SIZES: &[u32] = &[10, 100, 1000, 10_000, 100_000];
 
@@ -10,7 +10,7 @@ fn populated_map(n_targets: u32) -> UserMap {
     let map = UserMap::new(Sharding::S128);
 
     for t in 0..n_targets {
-        map.add(0, t * 3);
+        map.add(0, (t * 3) as Uid);
     }
 
     map
@@ -23,7 +23,7 @@ fn bench_add(c: &mut Criterion) {
         group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
             b.iter_batched_ref(
                 || populated_map(size),
-                |map| map.add(0, black_box(size * 3 + 1)),
+                |map| map.add(0, black_box((size * 3 + 1) as Uid)),
                 BatchSize::SmallInput,
             );
         });
@@ -36,7 +36,7 @@ fn bench_add_bulk(c: &mut Criterion) {
     let mut group = c.benchmark_group("add_bulk");
 
     for &size in SIZES {
-        let incoming: Vec<u32> = (0..size).map(|t| t * 3 + 1).collect();
+        let incoming: Vec<Uid> = (0..size).map(|t| (t * 3 + 1) as Uid).collect();
 
         group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
             b.iter_batched_ref(
@@ -55,7 +55,7 @@ fn bench_contains_hit(c: &mut Criterion) {
 
     for &size in SIZES {
         let map = populated_map(size);
-        let target = (size / 2) * 3;
+        let target = ((size / 2) * 3) as Uid;
         group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
             b.iter(|| black_box(map.contains(0, black_box(target))));
         });
@@ -70,7 +70,7 @@ fn bench_contains_miss(c: &mut Criterion) {
     for &size in SIZES {
         let map = populated_map(size);
         group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
-            b.iter(|| black_box(map.contains(0, black_box(u32::MAX))));
+            b.iter(|| black_box(map.contains(0, black_box(Uid::MAX))));
         });
     }
 
@@ -81,7 +81,7 @@ fn bench_remove(c: &mut Criterion) {
     let mut group = c.benchmark_group("remove");
 
     for &size in SIZES {
-        let target = (size / 2) * 3;
+        let target = ((size / 2) * 3) as Uid;
         group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
             b.iter_batched_ref(
                 || populated_map(size),
@@ -94,13 +94,86 @@ fn bench_remove(c: &mut Criterion) {
     group.finish();
 }
 
+/// Contains across shard counts, including the large variants.
+fn bench_contains_sharding(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contains_sharding");
+
+    for sharding in [Sharding::S64, Sharding::S4096, Sharding::S65536] {
+        let map = UserMap::new(sharding);
+        for p in 0..10_000 as Uid {
+            map.add_bulk(p, (0..10).map(|t| t * 3));
+        }
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{sharding:?}")),
+            &map,
+            |b, map| b.iter(|| black_box(map.contains(black_box(5_000), black_box(15)))),
+        );
+    }
+
+    group.finish();
+}
+
+/// Repeated visibility checks for one (viewer, author) pair, with and without
+/// the decision cache.
+fn bench_visibility(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visibility");
+
+    for (name, rels) in [
+        ("uncached", Relationships::new(Sharding::S128)),
+        (
+            "cached",
+            Relationships::new(Sharding::S128).with_decision_cache(1024),
+        ),
+    ] {
+        rels.follows.add_bulk(0, (0..1000).map(|t| t * 3));
+        rels.blocks.add_bulk(0, (0..100).map(|t| t * 7));
+
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(rels.visibility(0, black_box(1500))));
+        });
+    }
+
+    group.finish();
+}
+
+/// Full scan over 2048 shards; with the `rayon` feature, once per pool size
+/// to record how it scales.
+fn bench_find_all_with_target(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_all_with_target");
+
+    let map = UserMap::new(Sharding::S2048);
+    for subject in 0..200_000 as Uid {
+        map.add_bulk(subject, (0..8).map(|t| (subject + t * 101) % 50_000));
+    }
+
+    #[cfg(feature = "rayon")]
+    let threads = [1, 2, 4, 8];
+    #[cfg(not(feature = "rayon"))]
+    let threads = [1];
+
+    for n in threads {
+        #[cfg(feature = "rayon")]
+        crimeline::set_parallelism(n).unwrap();
+
+        group.bench_with_input(BenchmarkId::new("threads", n), &n, |b, _| {
+            b.iter(|| black_box(map.find_all_with_target(black_box(4242))));
+        });
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
+    bench_find_all_with_target,
     bench_add,
     bench_add_bulk,
     bench_contains_hit,
     bench_contains_miss,
-    bench_remove
+    bench_contains_sharding,
+    bench_remove,
+    bench_visibility
 );
 
 criterion_main!(benches);
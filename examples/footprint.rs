@@ -3,9 +3,10 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use crimeline::{
-    Order, ReportUsage, Timeline, Uid, Usage, Window,
+    Order, ReportUsage, ReportUsageTree, Sharding, Timeline, Uid, Usage, Window,
     arena::{Cold, Hot},
 };
+use humansize::{BINARY, SizeFormatter};
 
 // ── Bluesky content statistics ───────────────────────────────────────────────
 //
@@ -26,6 +27,9 @@ use crimeline::{
 //     ~17K   new users/day (late 2025, down from 466K/day peak Nov 2024)
 //     Milestones: 1M (Sep 2023) → 10M (Sep 2024) → 42.5M (Feb 2026)
 
+/// Concurrent graph writers assumed when picking a sharding.
+const WRITER_THREADS: usize = 16;
+
 struct ContentScenario {
     avg_blob_bytes: usize,
     entries_per_window: u64,
@@ -114,6 +118,9 @@ fn print_user_scenarios() {
 
         println!("── {} ({:.1}% dense) ──", s.name, density);
 
+        let sharding = Sharding::for_expected_users(s.users, WRITER_THREADS);
+        println!("  sharding: {sharding:?} for {WRITER_THREADS} writer threads");
+
         let follows = estimate_users(
             vec_uid,
             uid,
@@ -211,17 +218,26 @@ fn print_content_scenarios() -> Result<()> {
         let mut total = Usage::new("timeline", 0);
         total.add_heap_usage(per_arena.heap * s.windows as usize);
         total.add_disk_usage(per_arena.disk * s.windows);
+        total.add_mapped_usage(per_arena.mapped * s.windows);
 
         println!("── {} ──", s.name);
         println!(
             "  window:    {}s, {} entries/window",
             s.window_secs, s.entries_per_window,
         );
-        println!("  per arena: {per_arena}");
+        println!("  per arena:");
+        for line in sample.usage_tree().to_string().lines() {
+            println!("    {line}");
+        }
         println!("  {} arenas: {total}", s.windows);
+        println!(
+            "  resident:  {} heap + {} mapped",
+            SizeFormatter::new(total.heap, BINARY),
+            SizeFormatter::new(total.mapped, BINARY),
+        );
 
         let timeline = Timeline::new(vec![sample]);
-        let mut iter = timeline.iter(0, Order::Asc);
+        let mut iter = timeline.iter_all(Order::Asc);
         let mut count = 0;
         while iter.next().is_some() {
             count += 1;
@@ -246,5 +262,5 @@ fn freeze(epoch: u64, duration: u32, n: u64, blob_size: usize) -> Result<Arc<Col
             &blob,
         )?;
     }
-    Ok(hot.try_into()?)
+    hot.try_into()
 }
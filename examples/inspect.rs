@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+use anyhow::{Context, Result};
+use bytes::Bytes;
+use crimeline::inspect::{self, Violations};
+use humansize::{BINARY, SizeFormatter};
+
+fn main() -> Result<ExitCode> {
+    let path = env::args()
+        .nth(1)
+        .context("usage: cargo run --example inspect -- <export.parquet>")?;
+    let data = Bytes::from(fs::read(&path).with_context(|| format!("read {path}"))?);
+
+    println!("{path} ({})", SizeFormatter::new(data.len() as u64, BINARY));
+
+    let footer = inspect::describe(&data)?;
+    let end = footer.window.end_exclusive();
+    let or_unknown = |v: Option<u64>| v.map_or("unknown".to_string(), |v| v.to_string());
+
+    println!();
+    println!("  window         [{}, {end})", footer.window.epoch);
+    println!("  rows           {}", footer.rows);
+    println!("  row groups     {}", footer.row_groups);
+    println!(
+        "  timestamps     {} ..= {}",
+        or_unknown(footer.min_timestamp),
+        or_unknown(footer.max_timestamp)
+    );
+    println!(
+        "  compression    {}",
+        footer
+            .compression
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    println!(
+        "  format         parquet v{}, {}",
+        footer.version,
+        footer.created_by.as_deref().unwrap_or("unknown writer")
+    );
+    match footer.checksum {
+        Some(c) => println!("  fingerprint    {c:016x}"),
+        None => println!("  fingerprint    absent"),
+    }
+
+    let report = inspect::validate(data)?;
+
+    println!();
+    print_violations("unsorted", &report.unsorted);
+    print_violations("out of window", &report.out_of_window);
+    print_violations("duplicate cids", &report.duplicate_cids);
+    match (report.fingerprint, report.fingerprint_matches) {
+        (None, _) => println!("  import         skipped, rows invalid"),
+        (Some(f), Some(false)) => println!("  import         fingerprint {f:016x} MISMATCH"),
+        (Some(f), _) => println!("  import         ok, fingerprint {f:016x}"),
+    }
+
+    println!();
+    if report.is_valid() {
+        println!("valid");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("INVALID");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn print_violations(name: &str, v: &Violations) {
+    if v.is_empty() {
+        println!("  {name:<14} none");
+    } else {
+        println!("  {name:<14} {} rows, e.g. {:?}", v.count, v.samples);
+    }
+}
@@ -14,18 +14,18 @@ fn freeze(epoch: u64, duration: u32, entries: &[(Uid, u64, u64, &str)]) -> Resul
     for &(uid, cid, ts, text) in entries {
         hot.add(uid, cid, ts, text.as_bytes())?;
     }
-    Ok(hot.try_into()?)
+    hot.try_into()
 }
 
 fn main() -> Result<()> {
-    const SEBASTIAN: u32 = 1;
+    const SEBASTIAN: Uid = 1;
 
-    const ALICE: u32 = 10;
-    const BOB: u32 = 20;
-    const CAROL: u32 = 30;
-    const TROLL: u32 = 99;
+    const ALICE: Uid = 10;
+    const BOB: Uid = 20;
+    const CAROL: Uid = 30;
+    const TROLL: Uid = 99;
 
-    let rels = Arc::new(Relationships::new(Sharding::S2));
+    let rels = Arc::new(Relationships::new(Sharding::for_expected_users(5, 2)));
 
     let viewer: Uid = SEBASTIAN;
 
@@ -67,18 +67,10 @@ fn main() -> Result<()> {
         handles.push(thread::spawn(move || {
             sleep(Duration::from_millis(reader_id * 30));
 
-            let mut iter = tl.iter(0, Order::Asc);
+            let mut iter = tl.iter_all(Order::Asc).visible_to(r, viewer);
             let mut count = 0;
 
             while let Some(e) = iter.next() {
-                if !r.follows.contains(viewer, e.uid) {
-                    continue;
-                }
-
-                if r.blocks.contains(viewer, e.uid) {
-                    continue;
-                }
-
                 count += 1;
 
                 let (_cid, blob) = e.resolve();
@@ -133,17 +125,11 @@ fn main() -> Result<()> {
 
     println!("final timeline ({} arenas)", timeline.len());
 
-    let mut iter = timeline.iter(0, Order::Desc);
+    let mut iter = timeline
+        .iter_all(Order::Desc)
+        .visible_to(Arc::clone(&rels), viewer);
 
     while let Some(e) = iter.next() {
-        if !rels.follows.contains(viewer, e.uid) {
-            continue;
-        }
-
-        if rels.blocks.contains(viewer, e.uid) {
-            continue;
-        }
-
         let (_cid, blob) = e.resolve();
         let text = from_utf8(blob).unwrap_or("<binary>");
 
@@ -1,11 +1,19 @@
-use std::collections::HashSet;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::mem::size_of;
+use std::ops::{ControlFlow, Range};
+use std::path::Path;
 use std::sync::{Arc, LazyLock};
 
-use anyhow::{Context, Result};
-use arrow::array::{BinaryArray, RecordBatch, UInt32Array, UInt64Array};
-use arrow::datatypes::{DataType, Field, Schema};
+use anyhow::{Context, Result, bail, ensure};
+use arrow::array::{
+    Array, ArrayRef, BinaryArray, PrimitiveArray, RecordBatch, RecordBatchOptions, UInt32Array,
+    UInt64Array,
+};
+use arrow::datatypes::{ArrowPrimitiveType, DataType, Field, Schema};
+use arrow::ipc::writer::FileWriter;
 use bytes::Bytes;
 use itertools::Itertools;
 use parquet::{
@@ -13,113 +21,668 @@ use parquet::{
     basic::{Compression, ZstdLevel},
     file::{metadata::KeyValue, properties::WriterProperties},
 };
-use tracing::trace;
+use tracing::{debug_span, field, trace, warn};
 
-use crate::usage::{ReportUsage, Usage};
-use crate::users::Uid;
+use crate::par;
+use crate::usage::{ReportUsage, ReportUsageTree, Usage, UsageTree};
+use crate::users::{Uid, UidType};
 
-use super::blobs::{BlobStore, BlobStoreBuilder};
+#[cfg(not(target_arch = "wasm32"))]
+use super::backing::BlobBacking;
+use super::blobs::{BlobStore, BlobStoreBuilder, PresortedBuilder};
+use super::counters::{self, count, timed};
+use super::pool::{BlobPool, Blobs, Pooled};
 use super::{Cid, Order, Timestamp, Window};
 
+/// Passes writes through to `inner`, counting the bytes accepted.
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Indices of every column in the export schema.
+pub(super) const ALL_COLUMNS: [usize; 4] = [0, 1, 2, 3];
+
 /// Max blobs held in memory before flushing to the blob store.
 const BLOB_BATCH: usize = 256;
 
+/// Metadata namespace reserved for crimeline's own parquet keys.
+const METADATA_NAMESPACE: &str = "crimeline.";
+
+/// Metadata key the parquet arrow writer uses to embed the arrow schema.
+const ARROW_SCHEMA_KEY: &str = "ARROW:schema";
+
+/// Metadata key carrying [`Cold::fingerprint`] of a full export, as 16 hex
+/// digits.
+pub(crate) const FINGERPRINT_KEY: &str = "crimeline.fingerprint";
+
+/// 64-bit FNV-1a, used where hashes must be stable across processes and
+/// Rust versions.
+pub(super) struct Fnv64(pub(super) u64);
+
+impl Fnv64 {
+    pub(super) fn new() -> Self {
+        Fnv64(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(super) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes());
+    }
+
+    /// Starts a [`Cold::fingerprint`] over `len` rows in `span`.
+    #[allow(clippy::unnecessary_cast)] // Window duration may be u64
+    fn fingerprint(span: &Window, len: usize) -> Self {
+        let mut h = Fnv64::new();
+
+        h.write_u64(span.epoch);
+        h.write_u64(span.duration as u64);
+        h.write_u64(len as u64);
+
+        h
+    }
+
+    /// Adds one row to a [`Cold::fingerprint`].
+    #[allow(clippy::unnecessary_cast)] // Uid may be u64
+    fn write_row(&mut self, uid: Uid, cid: Cid, timestamp: Timestamp, blob: &[u8]) {
+        self.write_u64(uid as u64);
+        self.write_u64(cid);
+        self.write_u64(timestamp);
+        self.write_u64(blob.len() as u64);
+        self.write(blob);
+    }
+}
+
 static PARQUET_SCHEMA: LazyLock<Arc<Schema>> = LazyLock::new(|| {
     Arc::new(Schema::new(vec![
-        Field::new("uid", DataType::UInt32, false),
+        Field::new("uid", UidType::DATA_TYPE, false),
         Field::new("cid", DataType::UInt64, false),
         Field::new("timestamp", DataType::UInt64, false),
         Field::new("blob", DataType::Binary, false),
     ]))
 });
 
-fn schema() -> Arc<Schema> {
+pub(super) fn schema() -> Arc<Schema> {
     PARQUET_SCHEMA.clone()
 }
 
+/// Layout of [`Cold::export_self_contained`]: the blob store's columns first,
+/// then uids and timestamps relative to the window epoch.
+static SELF_CONTAINED_SCHEMA: LazyLock<Schema> = LazyLock::new(|| {
+    Schema::new(vec![
+        Field::new("cid", DataType::UInt64, false),
+        Field::new("blob", DataType::Binary, false),
+        Field::new("uid", UidType::DATA_TYPE, false),
+        Field::new("timestamp", DataType::UInt32, false),
+    ])
+});
+
+/// Value of `crimeline.format` in self-contained files.
+const SELF_CONTAINED_FORMAT: &str = "self-contained-v1";
+
 pub struct Cold {
-    blobs: BlobStore,
+    /// Shared by arenas that differ only in uids, see [`Cold::remap_uids`],
+    /// and possibly rows of a [`BlobPool`].
+    blobs: Arc<Blobs>,
+    metadata: HashMap<String, String>,
     pub span: Window,
     pub(super) timestamps: Box<[u32]>,
     uids: Box<[Uid]>,
 }
 
+#[derive(Debug)]
+pub struct ExportOptions {
+    /// Rows per batch handed to the parquet writer, 256 by default.
+    pub batch_rows: usize,
+    /// Additional key/values written alongside the reserved `crimeline.` keys.
+    pub extra_metadata: Vec<(String, String)>,
+    /// Zstd compression level, 1 to 22; 3 by default.
+    pub zstd_level: i32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            batch_rows: BLOB_BATCH,
+            extra_metadata: Vec::new(),
+            zstd_level: 3,
+        }
+    }
+}
+
+impl ExportOptions {
+    /// Checks the options without writing anything. Every export calls this
+    /// before its first byte, so misconfiguration fails fast.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.batch_rows > 0, "batch_rows must be positive");
+        ZstdLevel::try_new(self.zstd_level)
+            .with_context(|| format!("invalid zstd level {}", self.zstd_level))?;
+
+        let mut seen = HashSet::new();
+        for (key, _) in &self.extra_metadata {
+            ensure!(seen.insert(key), "metadata key {key} given more than once");
+            ensure!(
+                !key.starts_with(METADATA_NAMESPACE),
+                "metadata key {key} collides with reserved {METADATA_NAMESPACE} namespace",
+            );
+            ensure!(
+                key != ARROW_SCHEMA_KEY,
+                "metadata key {key} is reserved by the parquet writer",
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct ImportOptions {
+    pub on_null_blob: NullBlob,
+    /// Blob columns estimated above this many decoded bytes are spooled to
+    /// a tempfile batch by batch instead of collected on the heap; 256 MiB
+    /// by default.
+    pub spill_blob_bytes: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            on_null_blob: NullBlob::default(),
+            spill_blob_bytes: 256 << 20,
+        }
+    }
+}
+
+/// Where [`Cold::import_with`] collects blobs, see
+/// [`ImportOptions::spill_blob_bytes`].
+enum ImportBlobs {
+    Memory(PresortedBuilder),
+    Spill(BlobStoreBuilder),
+}
+
+impl ImportBlobs {
+    fn push(&mut self, cids: &[Cid], blobs: &[&[u8]]) -> Result<()> {
+        match self {
+            Self::Memory(builder) => {
+                for (&cid, blob) in cids.iter().zip(blobs) {
+                    builder.push(cid, blob);
+                }
+                Ok(())
+            }
+            Self::Spill(builder) => builder.append(cids, blobs),
+        }
+    }
+
+    fn build(self) -> Result<BlobStore> {
+        match self {
+            Self::Memory(builder) => builder.build(),
+            Self::Spill(builder) => builder.build_presorted(),
+        }
+    }
+}
+
+/// What [`Cold::import_with`] does with a null blob, which parquet written by
+/// other tools may contain.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NullBlob {
+    /// Keep the row with an empty blob.
+    #[default]
+    Empty,
+    /// Fail the import.
+    Error,
+    /// Drop the row.
+    Skip,
+}
+
+/// Returned, wrapped in an [`anyhow::Error`], by
+/// [`Cold::import_with_progress`] when the callback breaks.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    /// Rows read before the import stopped.
+    pub rows: usize,
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "import cancelled after {} rows", self.rows)
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// What [`Hot`] does with a timestamp outside its window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutOfWindow {
+    /// Fail with [`OutsideWindow`].
+    #[default]
+    Reject,
+    /// Drop the entry, counting it in [`Hot::rejected`].
+    Skip,
+}
+
+/// Returned, wrapped in an [`anyhow::Error`], by [`Hot::add`] and
+/// [`Hot::add_bulk`] for a timestamp outside the arena's window.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OutsideWindow {
+    pub end_exclusive: Timestamp,
+    pub epoch: Timestamp,
+    pub ts: Timestamp,
+}
+
+impl fmt::Display for OutsideWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "timestamp {} outside window [{}, {})",
+            self.ts, self.epoch, self.end_exclusive
+        )
+    }
+}
+
+impl std::error::Error for OutsideWindow {}
+
 pub struct Entry<'a> {
     cold: &'a Cold,
     idx: usize,
     pub uid: Uid,
 }
 
+/// An [`Entry`] detached from its arena, see [`Slice::drain_owned`].
+///
+/// [`Slice::drain_owned`]: super::Slice::drain_owned
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OwnedEntry {
+    /// Shares the arena's blob buffer, see [`Entry::resolve_bytes`].
+    pub blob: Bytes,
+    pub cid: Cid,
+    pub timestamp: Timestamp,
+    pub uid: Uid,
+}
+
 pub struct Hot {
     blobs: BlobStoreBuilder,
-    cid_set: HashSet<Cid>,
+    /// `None` when dedup is disabled, see [`Hot::without_dedup`].
+    cid_set: Option<HashSet<Cid>>,
     cids: Vec<Cid>,
+    out_of_window: OutOfWindow,
+    /// Entries skipped for a timestamp outside the window.
+    rejected: usize,
+    /// Min and max of every timestamp offered, see [`Hot::timestamp_spread`].
+    spread: Option<(Timestamp, Timestamp)>,
+    /// Blob rows of [`removed`](Hot::remove) entries, ascending. The blob
+    /// builder is append-only, so they are skipped at freeze time.
+    removed: Vec<usize>,
     span: Window,
     pub timestamps: Vec<u32>,
     uids: Vec<Uid>,
 }
 
+/// Blob rows of the live entries, in entry order: all of the first
+/// `len + removed.len()` rows except the `removed` ones.
+fn live_rows(removed: &[usize], len: usize) -> Vec<usize> {
+    (0..len + removed.len())
+        .filter(|row| removed.binary_search(row).is_err())
+        .collect()
+}
+
 impl Hot {
     pub fn new(span: Window) -> Result<Self> {
         Ok(Self {
             blobs: BlobStoreBuilder::new()?,
-            cid_set: HashSet::new(),
+            cid_set: Some(HashSet::new()),
             cids: Vec::new(),
+            out_of_window: OutOfWindow::default(),
+            rejected: 0,
+            removed: Vec::new(),
             span,
+            spread: None,
             timestamps: Vec::new(),
             uids: Vec::new(),
         })
     }
 
+    /// Creates an arena that skips cid deduplication, saving the cid set's
+    /// memory and a hash per add.
+    ///
+    /// **The caller must guarantee unique cids.** Duplicates are stored as
+    /// separate entries, and cid lookups such as [`Cold::get`] will then
+    /// return an arbitrary one of them.
+    pub fn without_dedup(span: Window) -> Result<Self> {
+        Ok(Self {
+            cid_set: None,
+            ..Self::new(span)?
+        })
+    }
+
+    /// Sets what `add` and `add_bulk` do with timestamps outside the window;
+    /// they are rejected with an error by default.
+    pub fn on_out_of_window(mut self, policy: OutOfWindow) -> Self {
+        self.out_of_window = policy;
+        self
+    }
+
+    /// Number of entries skipped so far under [`OutOfWindow::Skip`].
+    pub fn rejected(&self) -> usize {
+        self.rejected
+    }
+
+    /// Earliest and latest timestamp passed to `add`, `add_bulk` or
+    /// `absorb`, `None` before the first. Rejected and removed entries count
+    /// too, so a spread wider than the window's duration flags a source
+    /// feeding this arena timestamps for several windows.
+    pub fn timestamp_spread(&self) -> Option<(Timestamp, Timestamp)> {
+        self.spread
+    }
+
+    /// Widens the spread to `ts`, warning once it first exceeds the window.
+    fn observe(&mut self, ts: Timestamp) {
+        let (lo, hi) = self
+            .spread
+            .map_or((ts, ts), |(lo, hi)| (lo.min(ts), hi.max(ts)));
+        let exceeds = |(lo, hi): (Timestamp, Timestamp)| hi - lo >= self.span.duration as u64;
+
+        if exceeds((lo, hi)) && !self.spread.is_some_and(exceeds) {
+            warn!(
+                epoch = self.span.epoch,
+                duration = self.span.duration,
+                first = lo,
+                last = hi,
+                "hot arena timestamps span more than its window"
+            );
+        }
+
+        self.spread = Some((lo, hi));
+    }
+
+    /// Applies the out-of-window policy to `ts`: the error to fail with, or
+    /// `None` once the skip is counted.
+    fn reject(&mut self, ts: Timestamp) -> Option<anyhow::Error> {
+        count!(counters::HOT_ENTRIES_REJECTED);
+
+        match self.out_of_window {
+            OutOfWindow::Reject => Some(
+                OutsideWindow {
+                    end_exclusive: self.span.end_exclusive(),
+                    epoch: self.span.epoch,
+                    ts,
+                }
+                .into(),
+            ),
+            OutOfWindow::Skip => {
+                self.rejected += 1;
+                None
+            }
+        }
+    }
+
+    /// Records `cid` as seen, returning whether it is new. Always true
+    /// without dedup.
+    fn claim(&mut self, cid: Cid) -> bool {
+        self.cid_set.as_mut().is_none_or(|set| set.insert(cid))
+    }
+
+    fn unclaim(&mut self, cid: &Cid) {
+        if let Some(set) = self.cid_set.as_mut() {
+            set.remove(cid);
+        }
+    }
+
+    /// Buffers an entry; a cid already buffered is ignored. A `ts` outside
+    /// the window is handled per [`on_out_of_window`](Self::on_out_of_window).
     pub fn add(&mut self, uid: Uid, cid: Cid, ts: Timestamp, blob: &[u8]) -> Result<()> {
-        if !self.cid_set.insert(cid) {
+        self.observe(ts);
+
+        if !self.span.contains(ts) {
+            return self.reject(ts).map_or(Ok(()), Err);
+        }
+
+        if !self.claim(cid) {
             return Ok(());
         }
 
-        self.blobs.append(&[cid], &[blob])?;
+        if let Err(e) = self.blobs.append(&[cid], &[blob]) {
+            self.unclaim(&cid);
+            return Err(e);
+        }
+
         self.cids.push(cid);
         self.timestamps.push(self.span.convert_to_relative(ts));
         self.uids.push(uid);
 
+        count!(counters::HOT_ENTRIES_ADDED);
         trace!(cid, "added to hot arena");
 
         Ok(())
     }
 
-    pub fn add_bulk<T, B>(&mut self, entries: T) -> Result<()>
+    /// Retracts `cid` before the freeze, returning whether it was buffered.
+    /// The blob stays in the spill but is skipped when freezing, and the cid
+    /// may be added again. Without dedup every entry with `cid` is removed.
+    /// O(n) in the buffered entries.
+    pub fn remove(&mut self, cid: Cid) -> bool {
+        if self.cid_set.as_ref().is_some_and(|set| !set.contains(&cid)) {
+            return false;
+        }
+
+        let mut found = false;
+        while let Some(pos) = self.cids.iter().position(|&c| c == cid) {
+            // The blob row of entry `pos` is the pos-th row not yet removed.
+            let mut row = pos;
+            for &dead in &self.removed {
+                if dead > row {
+                    break;
+                }
+                row += 1;
+            }
+
+            let at = self.removed.partition_point(|&dead| dead < row);
+            self.removed.insert(at, row);
+
+            self.cids.remove(pos);
+            self.timestamps.remove(pos);
+            self.uids.remove(pos);
+            found = true;
+        }
+
+        if found {
+            self.unclaim(&cid);
+            trace!(cid, "removed from hot arena");
+        }
+
+        found
+    }
+
+    /// Whether `cid` is already buffered, i.e. a further `add` would be a no-op.
+    /// Without dedup this scans all buffered cids.
+    pub fn contains_cid(&self, cid: Cid) -> bool {
+        match &self.cid_set {
+            Some(set) => set.contains(&cid),
+            None => self.cids.contains(&cid),
+        }
+    }
+
+    /// Adds entries in chunks. Blobs may be anything byte-like, [`Bytes`]
+    /// included; either way they are copied into the arena's spill buffer.
+    /// Returns how many entries were skipped for a timestamp outside the
+    /// window, always 0 unless [`OutOfWindow::Skip`] is set.
+    pub fn add_bulk<T, B>(&mut self, entries: T) -> Result<usize>
+    where
+        B: AsRef<[u8]>,
+        T: IntoIterator<Item = (Uid, Cid, Timestamp, B)>,
+    {
+        let before = self.rejected;
+
+        self.add_bulk_progress(entries)
+            .map(|_| self.rejected - before)
+            .map_err(|(_, e)| e)
+    }
+
+    /// Like [`add_bulk`], but reports how many input entries were processed.
+    ///
+    /// On error the count is the number of leading entries (including skipped
+    /// duplicates) that were committed, so callers can resume from that
+    /// offset. The failing chunk is rolled back entirely: no cid is recorded
+    /// without its blob. With [`OutOfWindow::Reject`], a timestamp outside
+    /// the window fails its chunk the same way.
+    ///
+    /// [`add_bulk`]: Hot::add_bulk
+    pub fn add_bulk_progress<T, B>(&mut self, entries: T) -> Result<usize, (usize, anyhow::Error)>
     where
         B: AsRef<[u8]>,
         T: IntoIterator<Item = (Uid, Cid, Timestamp, B)>,
     {
+        let op = debug_span!("add_bulk", epoch = self.span.epoch, len = field::Empty).entered();
+
         let mut cids: Vec<Cid> = Vec::with_capacity(BLOB_BATCH);
         let mut blobs: Vec<B> = Vec::with_capacity(BLOB_BATCH);
+        let mut timestamps: Vec<u32> = Vec::with_capacity(BLOB_BATCH);
+        let mut uids: Vec<Uid> = Vec::with_capacity(BLOB_BATCH);
+
+        let mut processed = 0;
 
         for chunk in &entries.into_iter().chunks(BLOB_BATCH) {
             cids.clear();
             blobs.clear();
+            timestamps.clear();
+            uids.clear();
+
+            let mut seen = 0;
 
             for (uid, cid, ts, blob) in chunk {
-                if !self.cid_set.insert(cid) {
+                seen += 1;
+                self.observe(ts);
+
+                if !self.span.contains(ts) {
+                    if let Some(e) = self.reject(ts) {
+                        for cid in &cids {
+                            self.unclaim(cid);
+                        }
+
+                        return Err((processed, e));
+                    }
                     continue;
                 }
 
-                self.cids.push(cid);
-                self.timestamps.push(self.span.convert_to_relative(ts));
-                self.uids.push(uid);
+                if !self.claim(cid) {
+                    continue;
+                }
 
                 cids.push(cid);
                 blobs.push(blob);
+                timestamps.push(self.span.convert_to_relative(ts));
+                uids.push(uid);
             }
 
             if !cids.is_empty() {
-                self.blobs.append(&cids, &blobs)?;
+                if let Err(e) = self.blobs.append(&cids, &blobs) {
+                    for cid in &cids {
+                        self.unclaim(cid);
+                    }
+
+                    return Err((processed, e));
+                }
+
+                self.cids.extend_from_slice(&cids);
+                self.timestamps.extend_from_slice(&timestamps);
+                self.uids.extend_from_slice(&uids);
+
+                count!(counters::HOT_ENTRIES_ADDED, cids.len());
                 trace!(len = cids.len(), "added chunk to arena");
             }
+
+            processed += seen;
         }
 
+        op.record("len", processed);
         trace!(len = self.cids.len(), "finished bulk add");
 
-        Ok(())
+        Ok(processed)
+    }
+
+    /// Moves `other`'s entries into this arena, as if added here after our
+    /// own, without resolving its blobs one by one. Both arenas must cover
+    /// the same window. With dedup, cids already here, or repeated within
+    /// `other`, are skipped. Ordering is left to the freeze, as for `add`.
+    ///
+    /// On a blob store failure, the entries whose blobs were written are
+    /// kept and the rest of `other` is dropped.
+    pub fn absorb(&mut self, other: Hot) -> Result<()> {
+        ensure!(
+            other.span == self.span,
+            "cannot absorb arena for {:?} into arena for {:?}",
+            other.span,
+            self.span
+        );
+
+        let Hot {
+            blobs,
+            cids,
+            removed,
+            spread,
+            timestamps,
+            uids,
+            ..
+        } = other;
+
+        if let Some((lo, hi)) = spread {
+            self.observe(lo);
+            self.observe(hi);
+        }
+
+        let keep: Vec<bool> = cids.iter().map(|&cid| self.claim(cid)).collect();
+
+        // The blob mask covers `other`'s removed rows too.
+        let live = live_rows(&removed, cids.len());
+        let mut mask = vec![false; cids.len() + removed.len()];
+        for (i, &row) in live.iter().enumerate() {
+            mask[row] = keep[i];
+        }
+
+        let (written, result) = match self.blobs.absorb(blobs, &mask) {
+            Ok(()) => (mask.len(), Ok(())),
+            Err((written, e)) => (written, Err(e)),
+        };
+
+        let before = self.cids.len();
+
+        for (i, &cid) in cids.iter().enumerate().filter(|&(i, _)| keep[i]) {
+            if live[i] >= written {
+                self.unclaim(&cid);
+                continue;
+            }
+
+            self.cids.push(cid);
+            self.timestamps.push(timestamps[i]);
+            self.uids.push(uids[i]);
+        }
+
+        count!(counters::HOT_ENTRIES_ADDED, self.cids.len() - before);
+
+        trace!(
+            len = self.cids.len(),
+            absorbed = written,
+            "absorbed hot arena"
+        );
+
+        result
     }
 
     #[cfg_attr(coverage_nightly, coverage(off))]
@@ -143,9 +706,19 @@ impl Entry<'_> {
     }
 
     pub fn resolve(&self) -> (Cid, &[u8]) {
+        count!(counters::BLOBS_RESOLVED);
         self.cold.blobs.resolve(self.idx)
     }
 
+    /// Like [`resolve`](Self::resolve), but the blob is an owned, `Send`
+    /// view into the arena's blob buffer rather than a borrow of the entry.
+    /// Nothing is copied. Holding it pins the whole buffer, and for a
+    /// file-backed arena the mapping, even after the arena is dropped.
+    pub fn resolve_bytes(&self) -> (Cid, Bytes) {
+        count!(counters::BLOBS_RESOLVED);
+        self.cold.blobs.resolve_bytes(self.idx)
+    }
+
     pub fn timestamp(&self) -> Timestamp {
         self.cold
             .span
@@ -153,93 +726,516 @@ impl Entry<'_> {
     }
 }
 
+impl From<&Entry<'_>> for OwnedEntry {
+    fn from(entry: &Entry<'_>) -> Self {
+        let (cid, blob) = entry.resolve_bytes();
+
+        OwnedEntry {
+            blob,
+            cid,
+            timestamp: entry.timestamp(),
+            uid: entry.uid,
+        }
+    }
+}
+
 impl ReportUsage for Hot {
     fn usage(&self) -> Usage {
         let mut u = Usage::default();
 
         // HashSet stores (hash, value) per bucket.
-        let bucket_bytes = size_of::<Cid>() + size_of::<u64>();
-        u.add_heap_usage(self.cid_set.capacity() * bucket_bytes);
-        u.add_heap_waste((self.cid_set.capacity() - self.cid_set.len()) * bucket_bytes);
+        if let Some(set) = &self.cid_set {
+            let bucket_bytes = size_of::<Cid>() + size_of::<u64>();
+            u.add_collection(set.len(), set.capacity(), bucket_bytes);
+        }
         u.add_vec(&self.cids);
+        u.add_vec(&self.removed);
         u.add_vec(&self.timestamps);
         u.add_vec(&self.uids);
+        u.add_items(self.len());
         u += self.blobs.usage();
 
         u
     }
 }
 
+/// Writes the rows of several arenas merged into ascending `(timestamp,
+/// cid)` order, in batches of `batch_rows`. Each source's rows must already
+/// be in that order; ties across sources keep the order of `sources`.
+/// Returns the number of rows written.
+pub(super) fn write_merged<'a, T: Write + Send>(
+    pq: &mut ArrowWriter<T>,
+    sources: impl IntoIterator<Item = (&'a Cold, impl Iterator<Item = usize>)>,
+    batch_rows: usize,
+) -> Result<usize> {
+    let merged = sources
+        .into_iter()
+        .enumerate()
+        .map(|(source, (cold, rows))| {
+            rows.map(move |i| {
+                let ts = cold.span.convert_to_absolute(cold.timestamps[i]);
+                ((ts, cold.cids()[i], source), cold, i)
+            })
+        })
+        .kmerge_by(|a, b| a.0 < b.0);
+
+    let mut n = 0;
+
+    for chunk in &merged.chunks(batch_rows) {
+        let batch = batch(chunk.map(|(_, cold, i)| (cold, i)), None)?;
+
+        pq.write(&batch).context("write parquet batch")?;
+
+        n += batch.num_rows();
+
+        trace!(len = batch.num_rows(), "exported merged chunk");
+    }
+
+    Ok(n)
+}
+
+/// Builds one batch with the export schema from `(arena, row)` pairs, in the
+/// given order, feeding each row to `hash` if given.
+fn batch<'a>(
+    rows: impl Iterator<Item = (&'a Cold, usize)>,
+    mut hash: Option<&mut Fnv64>,
+) -> Result<RecordBatch> {
+    let (lower, _) = rows.size_hint();
+
+    let mut blobs: Vec<&[u8]> = Vec::with_capacity(lower);
+    let mut cids: Vec<Cid> = Vec::with_capacity(lower);
+    let mut timestamps: Vec<Timestamp> = Vec::with_capacity(lower);
+    let mut uids: Vec<Uid> = Vec::with_capacity(lower);
+
+    for (cold, i) in rows {
+        let (cid, blob) = cold.blobs.resolve(i);
+        let timestamp = cold.span.convert_to_absolute(cold.timestamps[i]);
+
+        if let Some(h) = hash.as_deref_mut() {
+            h.write_row(cold.uids[i], cid, timestamp, blob);
+        }
+
+        blobs.push(blob);
+        cids.push(cid);
+        timestamps.push(timestamp);
+        uids.push(cold.uids[i]);
+    }
+
+    RecordBatch::try_new(
+        schema(),
+        vec![
+            Arc::new(PrimitiveArray::<UidType>::from_iter_values(uids)),
+            Arc::new(UInt64Array::from_iter_values(cids)),
+            Arc::new(UInt64Array::from_iter_values(timestamps)),
+            Arc::new(BinaryArray::from_iter_values(&blobs)),
+        ],
+    )
+    .context("create record batch")
+}
+
 impl Cold {
-    pub fn export<T: Write + Send>(&self, writer: T) -> Result<()> {
-        let compression = Compression::ZSTD(ZstdLevel::try_new(3)?);
-
-        let metadata = vec![
-            KeyValue::new("crimeline.epoch".into(), Some(self.span.epoch.to_string())),
-            KeyValue::new(
-                "crimeline.duration".into(),
-                Some(self.span.duration.to_string()),
-            ),
+    /// Exports the arena as parquet, returning the number of bytes written.
+    /// The footer carries the arena's [`fingerprint`](Self::fingerprint),
+    /// hashed in the same pass that writes the rows.
+    pub fn export<T: Write + Send>(&self, writer: T) -> Result<u64> {
+        self.export_with(writer, &ExportOptions::default())
+    }
+
+    pub fn export_with<T: Write + Send>(&self, writer: T, options: &ExportOptions) -> Result<u64> {
+        let writer = CountingWriter {
+            inner: writer,
+            written: 0,
+        };
+        let mut pq = Self::parquet_writer(writer, &self.span, Vec::new(), options)?;
+
+        let n = self.uids.len();
+        let op = debug_span!(
+            "export",
+            epoch = self.span.epoch,
+            len = n,
+            bytes = field::Empty
+        )
+        .entered();
+        let mut h = Fnv64::fingerprint(&self.span, n);
+        self.write_rows(&mut pq, 0..n, options.batch_rows, Some(&mut h))?;
+        pq.append_key_value_metadata(KeyValue::new(
+            FINGERPRINT_KEY.into(),
+            Some(format!("{:016x}", h.0)),
+        ));
+
+        let written = pq.into_inner().context("close parquet writer")?.written;
+        op.record("bytes", written);
+
+        count!(counters::BYTES_EXPORTED, written);
+        trace!(len = n, bytes = written, "exported arena");
+
+        Ok(written)
+    }
+
+    /// Exports only `uid`'s rows, in arena order, returning how many were
+    /// written. The result imports as a regular arena over the same window
+    /// and is flagged as a single-user extract by a `crimeline.uid` key.
+    pub fn export_uid<T: Write + Send>(
+        &self,
+        uid: Uid,
+        writer: T,
+        options: &ExportOptions,
+    ) -> Result<u64> {
+        let flag = KeyValue::new("crimeline.uid".into(), Some(uid.to_string()));
+        let n = self.export_rows(writer, vec![flag], options, self.rows_of(uid))?;
+
+        trace!(uid, len = n, "exported uid from arena");
+
+        Ok(n)
+    }
+
+    /// Exports the rows for which `keep(uid, cid, timestamp)` holds, in arena
+    /// order, returning how many were written. Blobs of dropped rows are
+    /// never read. The result imports as a regular arena over the same
+    /// window.
+    pub fn export_filtered<T: Write + Send>(
+        &self,
+        writer: T,
+        keep: impl Fn(Uid, Cid, Timestamp) -> bool,
+        options: &ExportOptions,
+    ) -> Result<u64> {
+        let cids = self.blobs.cids();
+        let rows = (0..self.len()).filter(|&i| {
+            keep(
+                self.uids[i],
+                cids[i],
+                self.span.convert_to_absolute(self.timestamps[i]),
+            )
+        });
+
+        let n = self.export_rows(writer, Vec::new(), options, rows)?;
+
+        trace!(len = n, of = self.len(), "exported filtered arena");
+
+        Ok(n)
+    }
+
+    fn export_rows<T: Write + Send>(
+        &self,
+        writer: T,
+        reserved: Vec<KeyValue>,
+        options: &ExportOptions,
+        rows: impl Iterator<Item = usize>,
+    ) -> Result<u64> {
+        let mut pq = Self::parquet_writer(writer, &self.span, reserved, options)?;
+
+        let n = self.write_rows(&mut pq, rows, options.batch_rows, None)?;
+
+        pq.close().context("close parquet writer")?;
+
+        Ok(n as u64)
+    }
+
+    /// Indices of `uid`'s rows, in arena order.
+    pub(super) fn rows_of(&self, uid: Uid) -> impl Iterator<Item = usize> {
+        self.uids.iter().positions(move |&u| u == uid)
+    }
+
+    /// Creates a parquet writer carrying `span` and `reserved` under the
+    /// `crimeline.` namespace, plus the caller's extra metadata. Fails before
+    /// writing if the options are invalid.
+    pub(super) fn parquet_writer<T: Write + Send>(
+        writer: T,
+        span: &Window,
+        reserved: Vec<KeyValue>,
+        options: &ExportOptions,
+    ) -> Result<ArrowWriter<T>> {
+        options.validate()?;
+
+        let compression = Compression::ZSTD(ZstdLevel::try_new(options.zstd_level)?);
+
+        let mut metadata = vec![
+            KeyValue::new("crimeline.epoch".into(), Some(span.epoch.to_string())),
+            KeyValue::new("crimeline.duration".into(), Some(span.duration.to_string())),
         ];
+        metadata.extend(reserved);
+
+        for (key, value) in &options.extra_metadata {
+            metadata.push(KeyValue::new(key.clone(), Some(value.clone())));
+        }
 
         let props = WriterProperties::builder()
             .set_compression(compression)
             .set_key_value_metadata(Some(metadata))
             .build();
 
-        let mut pq =
-            ArrowWriter::try_new(writer, schema(), Some(props)).context("create parquet writer")?;
+        ArrowWriter::try_new(writer, schema(), Some(props)).context("create parquet writer")
+    }
 
-        let n = self.uids.len();
+    /// Writes `rows` in the given order, in batches of `batch_rows`, feeding
+    /// each to `hash` if given. Returns the number of rows written.
+    pub(super) fn write_rows<T: Write + Send>(
+        &self,
+        pq: &mut ArrowWriter<T>,
+        rows: impl Iterator<Item = usize>,
+        batch_rows: usize,
+        mut hash: Option<&mut Fnv64>,
+    ) -> Result<usize> {
+        let mut n = 0;
 
-        let mut blobs: Vec<&[u8]> = Vec::with_capacity(BLOB_BATCH);
-        let mut cids: Vec<Cid> = Vec::with_capacity(BLOB_BATCH);
+        for chunk in &rows.chunks(batch_rows) {
+            let batch = batch(chunk.map(|i| (self, i)), hash.as_deref_mut())?;
 
-        for start in (0..n).step_by(BLOB_BATCH) {
-            let end = (start + BLOB_BATCH).min(n);
+            pq.write(&batch).context("write parquet batch")?;
 
-            blobs.clear();
-            cids.clear();
+            n += batch.num_rows();
 
-            for i in start..end {
-                let (cid, blob) = self.blobs.resolve(i);
+            trace!(len = batch.num_rows(), "exported chunk");
+        }
 
-                blobs.push(blob);
-                cids.push(cid);
-            }
+        Ok(n)
+    }
 
-            let batch = RecordBatch::try_new(
-                schema(),
-                vec![
-                    Arc::new(UInt32Array::from_iter_values(
-                        self.uids[start..end].iter().copied(),
+    /// Streams the arena as record batches of at most `batch_size` rows, in
+    /// timestamp order and with the export schema, without going through
+    /// parquet. Blobs are copied into each batch.
+    pub fn record_batches(&self, batch_size: usize) -> impl Iterator<Item = Result<RecordBatch>> {
+        let (len, batch_size) = (self.len(), batch_size.max(1));
+
+        (0..len)
+            .step_by(batch_size)
+            .map(move |lo| self.batch_columns(lo..(lo + batch_size).min(len), &ALL_COLUMNS))
+    }
+
+    /// Builds only the schema `columns` for a contiguous run of rows. Blobs
+    /// are resolved only if the blob column is requested.
+    pub(super) fn batch_columns(
+        &self,
+        rows: Range<usize>,
+        columns: &[usize],
+    ) -> Result<RecordBatch> {
+        let schema = Arc::new(schema().project(columns).context("project schema")?);
+
+        let arrays = columns
+            .iter()
+            .map(|&column| -> ArrayRef {
+                match column {
+                    0 => Arc::new(PrimitiveArray::<UidType>::from_iter_values(
+                        self.uids[rows.clone()].iter().copied(),
                     )),
-                    Arc::new(UInt64Array::from_iter_values(cids.iter().copied())),
-                    Arc::new(UInt64Array::from_iter_values(
-                        self.timestamps[start..end]
+                    1 => Arc::new(UInt64Array::from_iter_values(
+                        self.cids()[rows.clone()].iter().copied(),
+                    )),
+                    2 => Arc::new(UInt64Array::from_iter_values(
+                        self.timestamps[rows.clone()]
                             .iter()
-                            .map(|&t| self.span.convert_to_absolute(t)),
+                            .map(|&ts| self.span.convert_to_absolute(ts)),
                     )),
-                    Arc::new(BinaryArray::from_iter_values(&blobs)),
-                ],
-            )
-            .context("create export batch")?;
+                    _ => Arc::new(BinaryArray::from_iter_values(
+                        rows.clone().map(|i| self.blobs.resolve(i).1),
+                    )),
+                }
+            })
+            .collect();
 
-            pq.write(&batch).context("write parquet batch")?;
+        // An empty projection still carries the row count, e.g. for count(*).
+        let options = RecordBatchOptions::new().with_row_count(Some(rows.len()));
 
-            trace!(len = end - start, "exported chunk");
-        }
+        RecordBatch::try_new_with_options(schema, arrays, &options).context("create record batch")
+    }
 
-        pq.close().context("close parquet writer")?;
+    /// Rows with timestamps in `[start, end)`.
+    pub(super) fn rows_between(&self, start: Timestamp, end: Timestamp) -> Range<usize> {
+        let (start, end) = (
+            self.span.relative_bound(start),
+            self.span.relative_bound(end),
+        );
 
-        trace!(len = n, "exported arena");
+        let lo = self.timestamps.partition_point(|&ts| ts < start);
+        let hi = self.timestamps.partition_point(|&ts| ts < end);
 
-        Ok(())
+        lo..hi.max(lo)
     }
 
-    pub fn import(data: Bytes) -> Result<Arc<Self>> {
-        let builder =
-            ParquetRecordBatchReaderBuilder::try_new(data).context("open parquet reader")?;
+    /// Writes the frozen layout to `path` as a single arrow ipc file, so
+    /// [`open`](Self::open) can map it back without an import. Returns the
+    /// number of bytes written.
+    pub fn export_self_contained(&self, path: impl AsRef<Path>) -> Result<u64> {
+        let path = path.as_ref();
+
+        let mut metadata = self.metadata.clone();
+        metadata.insert("crimeline.epoch".into(), self.span.epoch.to_string());
+        metadata.insert("crimeline.duration".into(), self.span.duration.to_string());
+        metadata.insert("crimeline.format".into(), SELF_CONTAINED_FORMAT.into());
+
+        let schema = Arc::new(SELF_CONTAINED_SCHEMA.clone().with_metadata(metadata));
+
+        let (cids, blobs) = self.blobs.columns();
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                cids,
+                blobs,
+                Arc::new(PrimitiveArray::<UidType>::from_iter_values(
+                    self.uids.iter().copied(),
+                )),
+                Arc::new(UInt32Array::from_iter_values(
+                    self.timestamps.iter().copied(),
+                )),
+            ],
+        )
+        .context("create self-contained batch")?;
+
+        let file = File::create(path).with_context(|| format!("create {}", path.display()))?;
+        let writer = CountingWriter {
+            inner: BufWriter::new(file),
+            written: 0,
+        };
+
+        let mut ipc = FileWriter::try_new(writer, &schema).context("create arrow ipc writer")?;
+        ipc.write(&batch).context("write self-contained batch")?;
+        ipc.finish().context("finish arrow ipc writer")?;
+
+        let mut writer = ipc.into_inner().context("close arrow ipc writer")?;
+        writer.flush().context("flush self-contained file")?;
+
+        trace!(len = self.len(), bytes = writer.written, path = %path.display(), "exported self-contained arena");
+
+        Ok(writer.written)
+    }
+
+    /// Maps a file written by [`export_self_contained`](Self::export_self_contained).
+    /// Blobs and cids are read in place; only uids and timestamps are copied.
+    /// The file must not change while the arena is alive. Not available on
+    /// wasm32, which has no memory mapping.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn open(path: impl AsRef<Path>) -> Result<Arc<Self>> {
+        let path = path.as_ref();
+
+        let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        let (buffer, backing) =
+            BlobBacking::map_file(&file).with_context(|| format!("map {}", path.display()))?;
+
+        let (blobs, batch) = BlobStore::from_buffer(buffer, backing)?;
+        ensure!(
+            batch.num_columns() == SELF_CONTAINED_SCHEMA.fields().len(),
+            "expected {} columns, found {}",
+            SELF_CONTAINED_SCHEMA.fields().len(),
+            batch.num_columns()
+        );
+
+        let schema = batch.schema();
+        let reserved = |key: &str| {
+            schema
+                .metadata()
+                .get(key)
+                .with_context(|| format!("missing {key}"))
+        };
+
+        let format = reserved("crimeline.format")?;
+        ensure!(
+            format == SELF_CONTAINED_FORMAT,
+            "unsupported arena format {format}"
+        );
+
+        let span = Window::new(
+            reserved("crimeline.epoch")?
+                .parse()
+                .context("parse epoch")?,
+            reserved("crimeline.duration")?
+                .parse()
+                .context("parse duration")?,
+        );
+
+        let uids = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<PrimitiveArray<UidType>>()
+            .context("downcast uid column")?;
+
+        let timestamps = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<UInt32Array>()
+            .context("downcast timestamp column")?;
+
+        // The file is as untrusted as a parquet import: check what the
+        // arena relies on instead of mapping it blindly.
+        let cids = blobs.cids();
+        ensure!(
+            uids.len() == cids.len() && timestamps.len() == cids.len(),
+            "column lengths differ: {} cids, {} uids, {} timestamps",
+            cids.len(),
+            uids.len(),
+            timestamps.len()
+        );
+        if let Some(idx) = timestamps
+            .values()
+            .iter()
+            .position(|&ts| u64::from(ts) >= u64::from(span.duration))
+        {
+            bail!(
+                "row {idx}: timestamp {} outside [{}, {})",
+                span.convert_to_absolute(timestamps.value(idx)),
+                span.epoch,
+                span.end_exclusive()
+            );
+        }
+        if let Some(idx) = (1..cids.len())
+            .find(|&i| (timestamps.value(i - 1), cids[i - 1]) > (timestamps.value(i), cids[i]))
+        {
+            bail!("row {idx}: not in (timestamp, cid) order");
+        }
+
+        let metadata = schema
+            .metadata()
+            .iter()
+            .filter(|(key, _)| !key.starts_with(METADATA_NAMESPACE))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        trace!(len = uids.len(), path = %path.display(), "opened self-contained arena");
+
+        Ok(Arc::new(Cold {
+            blobs: Arc::new(Blobs::from(blobs)),
+            metadata,
+            span,
+            timestamps: timestamps.values().to_vec().into_boxed_slice(),
+            uids: uids.values().to_vec().into_boxed_slice(),
+        }))
+    }
+
+    pub fn import(data: Bytes) -> Result<Arc<Self>> {
+        Self::import_with(data, &ImportOptions::default())
+    }
+
+    pub fn import_with(data: Bytes, options: &ImportOptions) -> Result<Arc<Self>> {
+        Self::import_inner(data, options, |_| ControlFlow::Continue(()))
+    }
+
+    /// Like [`import`](Self::import), but calls `on_batch` with the rows read
+    /// so far after each parquet batch. Returning `Break` stops the import
+    /// with a [`Cancelled`] error; everything decoded so far is dropped,
+    /// including the spool tempfile of a large blob column.
+    pub fn import_with_progress(
+        data: Bytes,
+        on_batch: impl FnMut(usize) -> ControlFlow<()>,
+    ) -> Result<Arc<Self>> {
+        Self::import_inner(data, &ImportOptions::default(), on_batch)
+    }
+
+    fn import_inner(
+        data: Bytes,
+        options: &ImportOptions,
+        mut on_batch: impl FnMut(usize) -> ControlFlow<()>,
+    ) -> Result<Arc<Self>> {
+        count!(counters::BYTES_IMPORTED, data.len());
+        let input_len = data.len();
+        let op = debug_span!(
+            "import",
+            bytes = data.len(),
+            epoch = field::Empty,
+            len = field::Empty
+        )
+        .entered();
+
+        let builder =
+            ParquetRecordBatchReaderBuilder::try_new(data).context("open parquet reader")?;
 
         let metadata = builder
             .metadata()
@@ -267,15 +1263,50 @@ impl Cold {
                 .context("parse duration")?;
 
             span = Window::new(epoch, duration);
+            op.record("epoch", epoch);
         }
 
-        let num_rows = builder.metadata().file_metadata().num_rows() as usize;
+        let metadata: HashMap<String, String> = metadata
+            .iter()
+            .filter(|e| !e.key.starts_with(METADATA_NAMESPACE) && e.key != ARROW_SCHEMA_KEY)
+            .map(|e| (e.key.clone(), e.value.clone().unwrap_or_default()))
+            .collect();
+
+        // Capacity hints from the footer, which is untrusted: clamped so a
+        // forged count cannot reserve more than the input could hold.
+        let num_rows = usize::try_from(builder.metadata().file_metadata().num_rows())
+            .unwrap_or(0)
+            .min(input_len);
+        // Decoded size of the blob column, page headers and length prefixes
+        // included; an estimate only, dictionary pages make it smaller.
+        let blob_hint: i64 = builder
+            .metadata()
+            .row_groups()
+            .iter()
+            .filter_map(|rg| rg.columns().get(3))
+            .map(|c| c.uncompressed_size())
+            .fold(0, i64::saturating_add);
+        let blob_hint = usize::try_from(blob_hint).unwrap_or(0);
 
         let reader = builder.build().context("build parquet reader")?;
 
-        let mut blob_builder = BlobStoreBuilder::new()?;
+        // Small blob columns stay in memory until the end and are written
+        // once, as the final single-batch store, rather than spooled batch
+        // by batch and then copied again. Large ones are spooled so the heap
+        // never holds them all.
+        let mut blob_builder = if blob_hint > options.spill_blob_bytes {
+            ImportBlobs::Spill(BlobStoreBuilder::new()?)
+        } else {
+            ImportBlobs::Memory(PresortedBuilder::with_capacity(
+                num_rows,
+                blob_hint.min(input_len),
+            ))
+        };
         let mut timestamps: Vec<u32> = Vec::with_capacity(num_rows);
         let mut uids: Vec<Uid> = Vec::with_capacity(num_rows);
+        let mut rows = 0;
+        // Key of the last row kept, which every later row must sort after.
+        let mut last: Option<(Timestamp, Cid)> = None;
 
         for batch_result in reader {
             let batch = batch_result.context("read parquet batch")?;
@@ -283,7 +1314,7 @@ impl Cold {
             let uid_col = batch
                 .column(0)
                 .as_any()
-                .downcast_ref::<UInt32Array>()
+                .downcast_ref::<PrimitiveArray<UidType>>()
                 .context("downcast uid column")?;
 
             let cid_col = batch
@@ -305,41 +1336,281 @@ impl Cold {
                 .context("downcast blob column")?;
 
             let n = batch.num_rows();
-
-            let mut cids: Vec<Cid> = Vec::with_capacity(n);
-            let mut blobs: Vec<&[u8]> = Vec::with_capacity(n);
+            let mut batch_cids: Vec<Cid> = Vec::with_capacity(n);
+            let mut batch_blobs: Vec<&[u8]> = Vec::with_capacity(n);
 
             for i in 0..n {
-                blobs.push(blob_col.value(i));
-                cids.push(cid_col.value(i));
-                timestamps.push(span.convert_to_relative(ts_col.value(i)));
+                if blob_col.is_null(i) {
+                    match options.on_null_blob {
+                        NullBlob::Empty => {}
+                        NullBlob::Error => {
+                            bail!("null blob for cid {}", cid_col.value(i))
+                        }
+                        NullBlob::Skip => continue,
+                    }
+                }
+
+                let (ts, cid) = (ts_col.value(i), cid_col.value(i));
+                let row = rows + i;
+                ensure!(
+                    span.contains(ts),
+                    "row {row}: timestamp {ts} outside [{}, {})",
+                    span.epoch,
+                    span.end_exclusive()
+                );
+                ensure!(
+                    last < Some((ts, cid)),
+                    "row {row}: not in strictly ascending (timestamp, cid) order"
+                );
+                last = Some((ts, cid));
+
+                // A null slot's value is empty.
+                batch_cids.push(cid);
+                batch_blobs.push(blob_col.value(i));
+                timestamps.push(span.convert_to_relative(ts));
                 uids.push(uid_col.value(i));
             }
 
-            blob_builder.append(&cids, &blobs)?;
+            blob_builder.push(&batch_cids, &batch_blobs)?;
+
+            rows += n;
 
             trace!(len = n, "imported chunk");
+
+            if on_batch(rows).is_break() {
+                trace!(rows, "import cancelled");
+                return Err(Cancelled { rows }.into());
+            }
         }
 
-        let blobs = blob_builder.build_presorted()?;
+        let blobs = blob_builder.build()?;
 
+        op.record("len", uids.len());
         trace!(len = uids.len(), "imported arena");
 
         Ok(Arc::new(Cold {
-            blobs,
+            blobs: Arc::new(Blobs::from(blobs)),
+            metadata,
+            span,
+            timestamps: timestamps.into_boxed_slice(),
+            uids: uids.into_boxed_slice(),
+        }))
+    }
+
+    /// Builds a new arena keeping every `keep_every`th entry (starting with the
+    /// first) in sorted order, along with its blob. The window and metadata are
+    /// preserved.
+    pub fn downsample(&self, keep_every: usize) -> Result<Arc<Cold>> {
+        ensure!(keep_every > 0, "keep_every must be positive");
+
+        let kept: Vec<usize> = (0..self.len()).step_by(keep_every).collect();
+
+        trace!(len = kept.len(), keep_every, "downsampling arena");
+
+        self.subset(Window::new(self.span.epoch, self.span.duration), &kept)
+    }
+
+    /// Splits the arena at `cutoff` into `[epoch, cutoff)` and
+    /// `[cutoff, end)`, each with its own window, entries and blobs. Metadata
+    /// is copied to both. `cutoff` is clamped to the arena's window.
+    pub fn repartition(&self, cutoff: Timestamp) -> Result<(Arc<Cold>, Arc<Cold>)> {
+        let end = self.span.end_exclusive();
+        let cutoff = cutoff.clamp(self.span.epoch, end);
+
+        let rel = (cutoff - self.span.epoch) as u32;
+        let split = self.timestamps.partition_point(|&ts| ts < rel);
+
+        let older: Vec<usize> = (0..split).collect();
+        let newer: Vec<usize> = (split..self.len()).collect();
+
+        trace!(
+            cutoff,
+            older = older.len(),
+            newer = newer.len(),
+            "repartitioning arena"
+        );
+
+        Ok((
+            self.subset(Window::new(self.span.epoch, rel), &older)?,
+            self.subset(Window::new(cutoff, (end - cutoff) as u32), &newer)?,
+        ))
+    }
+
+    /// Returns a copy of the arena with every uid passed through `map`. Cids,
+    /// timestamps, order and metadata are kept, and the blob store is shared
+    /// rather than copied, so both arenas report its usage.
+    pub fn remap_uids(&self, map: impl Fn(Uid) -> Uid) -> Result<Arc<Cold>> {
+        let uids: Box<[Uid]> = self.uids.iter().map(|&uid| map(uid)).collect();
+
+        trace!(len = uids.len(), "remapped arena uids");
+
+        Ok(Arc::new(Cold {
+            blobs: Arc::clone(&self.blobs),
+            metadata: self.metadata.clone(),
+            span: Window::new(self.span.epoch, self.span.duration),
+            timestamps: self.timestamps.clone(),
+            uids,
+        }))
+    }
+
+    pub(super) fn blobs(&self) -> &Blobs {
+        &self.blobs
+    }
+
+    /// The store holding the blob bytes, as an address shared by every
+    /// arena resolving through it, and that store's `disk` usage. Pooled
+    /// arenas report the pool.
+    pub(super) fn blob_backing(&self) -> (*const (), u64) {
+        match &*self.blobs {
+            Blobs::Own(store) => (Arc::as_ptr(&self.blobs).cast(), store.usage().disk),
+            Blobs::Pooled(pooled) => (
+                Arc::as_ptr(pooled.pool()).cast(),
+                pooled.pool().usage().disk,
+            ),
+        }
+    }
+
+    /// Returns a copy of the arena whose blobs are resolved from `pool`
+    /// rather than its own store, so content repeated across arenas is kept
+    /// once. Fails if the pool lacks any of the arena's cids.
+    pub fn with_external_blobs(&self, pool: Arc<BlobPool>) -> Result<Arc<Cold>> {
+        let pooled = Pooled::new(self.blobs.cids(), pool)?;
+
+        trace!(len = self.len(), "pointed arena at blob pool");
+
+        Ok(Arc::new(Cold {
+            blobs: Arc::new(Blobs::Pooled(pooled)),
+            metadata: self.metadata.clone(),
+            span: Window::new(self.span.epoch, self.span.duration),
+            timestamps: self.timestamps.clone(),
+            uids: self.uids.clone(),
+        }))
+    }
+
+    /// Copies the sorted `rows` into a new arena over `span`, which must
+    /// contain all of their timestamps.
+    fn subset(&self, span: Window, rows: &[usize]) -> Result<Arc<Cold>> {
+        let mut blob_builder = BlobStoreBuilder::new()?;
+
+        for chunk in rows.chunks(BLOB_BATCH) {
+            let (cids, blobs): (Vec<Cid>, Vec<&[u8]>) =
+                chunk.iter().map(|&idx| self.blobs.resolve(idx)).unzip();
+
+            blob_builder.append(&cids, &blobs)?;
+        }
+
+        let timestamps: Vec<u32> = rows
+            .iter()
+            .map(|&idx| {
+                span.convert_to_relative(self.span.convert_to_absolute(self.timestamps[idx]))
+            })
+            .collect();
+        let uids: Vec<Uid> = rows.iter().map(|&idx| self.uids[idx]).collect();
+
+        Ok(Arc::new(Cold {
+            blobs: Arc::new(Blobs::from(blob_builder.build_presorted()?)),
+            metadata: self.metadata.clone(),
             span,
             timestamps: timestamps.into_boxed_slice(),
             uids: uids.into_boxed_slice(),
         }))
     }
 
+    /// Hashes the window and the ordered `(uid, cid, timestamp, blob)` rows.
+    /// Arenas with identical content fingerprint equal regardless of how
+    /// they were built, and the value is stable across processes.
+    pub fn fingerprint(&self) -> u64 {
+        let mut h = Fnv64::fingerprint(&self.span, self.len());
+
+        for idx in 0..self.len() {
+            let (cid, blob) = self.blobs.resolve(idx);
+
+            h.write_row(
+                self.uids[idx],
+                cid,
+                self.span.convert_to_absolute(self.timestamps[idx]),
+                blob,
+            );
+        }
+
+        h.0
+    }
+
+    /// Earliest entry timestamp, `None` if the arena is empty.
+    pub fn first_timestamp(&self) -> Option<Timestamp> {
+        self.timestamps
+            .first()
+            .map(|&ts| self.span.convert_to_absolute(ts))
+    }
+
+    /// Number of different authors in the arena.
+    pub fn distinct_uids(&self) -> usize {
+        self.uids.iter().collect::<HashSet<_>>().len()
+    }
+
+    /// Latest entry timestamp, `None` if the arena is empty.
+    pub fn last_timestamp(&self) -> Option<Timestamp> {
+        self.timestamps
+            .last()
+            .map(|&ts| self.span.convert_to_absolute(ts))
+    }
+
+    /// All cids in timestamp order.
+    pub(super) fn cids(&self) -> &[Cid] {
+        self.blobs.cids()
+    }
+
+    /// Looks up the entry for `cid`. The first lookup builds a cid index.
+    pub fn get(&self, cid: Cid) -> Option<Entry<'_>> {
+        self.blobs.position(cid).map(|idx| Entry::new(self, idx))
+    }
+
+    /// The blob, timestamp and author of `cid`, via the same lazily built cid
+    /// index as [`get`](Self::get): O(log n) after the first lookup.
+    pub fn resolve_by_cid(&self, cid: Cid) -> Option<(&[u8], Timestamp, Uid)> {
+        let idx = self.blobs.position(cid)?;
+        count!(counters::BLOBS_RESOLVED);
+
+        Some((
+            self.blobs.resolve(idx).1,
+            self.span.convert_to_absolute(self.timestamps[idx]),
+            self.uids[idx],
+        ))
+    }
+
+    /// Appends the cid and blob of each entry index in `indices`, in the given
+    /// order, to `out`. Sorting indices first gathers with better locality
+    /// on a mapped arena. Fails, leaving `out` untouched, if any index is out
+    /// of range.
+    pub fn resolve_many<'a>(
+        &'a self,
+        indices: &[usize],
+        out: &mut Vec<(Cid, &'a [u8])>,
+    ) -> Result<()> {
+        let len = self.len();
+        if let Some(&bad) = indices.iter().find(|&&idx| idx >= len) {
+            bail!("index {bad} out of range for arena of {len} entries");
+        }
+
+        out.reserve(indices.len());
+        out.extend(indices.iter().map(|&idx| self.blobs.resolve(idx)));
+        count!(counters::BLOBS_RESOLVED, indices.len());
+
+        Ok(())
+    }
+
+    /// Iterates entries in ascending cid order, building the cid index on
+    /// first use.
+    pub fn iter_by_cid(&self) -> impl Iterator<Item = Entry<'_>> {
+        self.blobs
+            .by_cid()
+            .iter()
+            .map(move |&idx| Entry::new(self, idx))
+    }
+
     pub fn iter(&self, order: Order, start: Timestamp) -> impl Iterator<Item = Entry<'_>> {
-        let skip_to = if start <= self.span.epoch {
-            0
-        } else {
-            let rel_start = (start - self.span.epoch) as u32;
-            self.timestamps.partition_point(|&ts| ts < rel_start)
-        };
+        let rel_start = self.span.relative_bound(start);
+        let skip_to = self.timestamps.partition_point(|&ts| ts < rel_start);
 
         let len = self.len();
 
@@ -348,6 +1619,24 @@ impl Cold {
             .map(move |idx| Entry::new(self, idx))
     }
 
+    /// Iterates the entries after the one with `cid` in `order`, so a sync
+    /// can resume by cid even across equal timestamps. `None` if `cid` is
+    /// not in this arena.
+    pub fn iter_after_cid(
+        &self,
+        cid: Cid,
+        order: Order,
+    ) -> Option<impl Iterator<Item = Entry<'_>>> {
+        let idx = self.blobs.position(cid)?;
+
+        let range = match order {
+            Order::Asc => idx + 1..self.len(),
+            Order::Desc => 0..idx,
+        };
+
+        Some(order.range(range).map(move |idx| Entry::new(self, idx)))
+    }
+
     #[cfg_attr(coverage_nightly, coverage(off))]
     pub fn is_empty(&self) -> bool {
         self.uids.is_empty()
@@ -356,6 +1645,11 @@ impl Cold {
     pub fn len(&self) -> usize {
         self.uids.len()
     }
+
+    /// Extra metadata carried over from import, excluding reserved keys.
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
 }
 
 impl ReportUsage for Cold {
@@ -363,36 +1657,124 @@ impl ReportUsage for Cold {
         let mut u = Usage::default();
         u.add_boxed_slice(&self.timestamps);
         u.add_boxed_slice(&self.uids);
+        // One blob row per entry, so items come from the blob store.
         u += self.blobs.usage();
         u
     }
 }
 
+impl ReportUsageTree for Cold {
+    fn usage_tree(&self) -> UsageTree {
+        let mut timestamps = Usage::default();
+        timestamps.add_boxed_slice(&self.timestamps);
+
+        let mut uids = Usage::default();
+        uids.add_boxed_slice(&self.uids);
+
+        UsageTree::new("arena", self.usage())
+            .with_child(UsageTree::new("blobs", self.blobs.usage()))
+            .with_child(UsageTree::new("timestamps", timestamps))
+            .with_child(UsageTree::new("uids", uids))
+    }
+}
+
 impl TryInto<Arc<Cold>> for Hot {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<Arc<Cold>> {
+        let cold = timed!(counters::ARENA_FREEZE_SECONDS, self.freeze())?;
+        count!(counters::ARENA_FREEZES);
+
+        Ok(cold)
+    }
+}
+
+impl Hot {
+    /// Like `try_into`, but trusts that entries were added in
+    /// `(timestamp, cid)` order, e.g. when replaying a sorted source, and
+    /// skips the sort. Blobs are reused as written rather than permuted into
+    /// a new store. Debug builds check the order and panic if it is broken.
+    pub fn freeze_assume_sorted(self) -> Result<Arc<Cold>> {
+        let cold = timed!(counters::ARENA_FREEZE_SECONDS, self.freeze_sorted())?;
+        count!(counters::ARENA_FREEZES);
+
+        Ok(cold)
+    }
+
+    fn freeze_sorted(self) -> Result<Arc<Cold>> {
+        let Self {
+            cid_set: _,
+            cids,
+            out_of_window: _,
+            rejected: _,
+            removed,
+            timestamps,
+            uids,
+            span,
+            spread: _,
+            blobs,
+        } = self;
+
+        let n = cids.len();
+        let _op = debug_span!("freeze", epoch = span.epoch, len = n).entered();
+
+        debug_assert!(
+            (1..n).all(|i| (timestamps[i - 1], cids[i - 1]) <= (timestamps[i], cids[i])),
+            "entries not in (timestamp, cid) order",
+        );
+
+        trace!(len = n, "froze presorted arena");
+
+        let store = if removed.is_empty() {
+            blobs.build_presorted()?
+        } else {
+            blobs.build_and_sort(&live_rows(&removed, n))?
+        };
+
+        Ok(Arc::new(Cold {
+            blobs: Arc::new(Blobs::from(store)),
+            metadata: HashMap::new(),
+            span,
+            timestamps: timestamps.into_boxed_slice(),
+            uids: uids.into_boxed_slice(),
+        }))
+    }
+
+    fn freeze(self) -> Result<Arc<Cold>> {
         let Self {
             cid_set: _,
             cids,
+            out_of_window: _,
+            rejected: _,
+            removed,
             timestamps,
             uids,
             span,
+            spread: _,
             blobs,
         } = self;
 
         let n = cids.len();
+        let _op = debug_span!("freeze", epoch = span.epoch, len = n).entered();
 
         let mut perm: Vec<usize> = (0..n).collect();
-        perm.sort_unstable_by_key(|&i| (timestamps[i], cids[i]));
+        par::sort_unstable_by_key(&mut perm, |&i| (timestamps[i], cids[i]));
 
         let (sorted_timestamps, sorted_uids): (Vec<u32>, Vec<Uid>) =
             perm.iter().map(|&i| (timestamps[i], uids[i])).unzip();
 
+        if !removed.is_empty() {
+            let live = live_rows(&removed, n);
+            for i in &mut perm {
+                *i = live[*i];
+            }
+        }
+
         trace!(len = n, "froze arena");
 
         Ok(Arc::new(Cold {
-            blobs: blobs.build_and_sort(&perm)?,
+            blobs: Arc::new(Blobs::from(blobs.build_and_sort(&perm)?)),
+            metadata: HashMap::new(),
             span,
             timestamps: sorted_timestamps.into_boxed_slice(),
             uids: sorted_uids.into_boxed_slice(),
@@ -403,6 +1785,7 @@ impl TryInto<Arc<Cold>> for Hot {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::content::test_util::rows;
 
     #[test]
     fn add_bulk_dedup_across_individual_and_bulk() -> Result<()> {
@@ -465,7 +1848,7 @@ mod tests {
 
         let mut individual = Hot::new(Window::new(1000, 100))?;
         for (uid, cid, ts, blob) in &entries {
-            individual.add(*uid, *cid, *ts, *blob)?;
+            individual.add(*uid, *cid, *ts, blob)?;
         }
 
         let cold_bulk: Arc<Cold> = bulk.try_into()?;
@@ -514,88 +1897,634 @@ mod tests {
     }
 
     #[test]
-    fn cold_empty() -> Result<()> {
-        let hot = Hot::new(Window::new(1000, 100))?;
-        let cold: Arc<Cold> = hot.try_into()?;
-        assert_eq!(cold.len(), 0);
-        assert_eq!(cold.iter(Order::Asc, 0).count(), 0);
+    fn absorb_matches_single_hot() -> Result<()> {
+        let span = || Window::new(1000, 1000);
+        let entry = |i: u64| (i as Uid, i, 1000 + (i * 37) % 1000, format!("blob {i}"));
+
+        // Overlapping cid ranges, with a duplicate inside the second part.
+        let parts: [Vec<u64>; 3] = [
+            (0..300).collect(),
+            (200..BLOB_BATCH as u64 + 400).chain([250, 260]).collect(),
+            (900..950).collect(),
+        ];
+
+        let mut merged = Hot::new(span())?;
+        merged.add_bulk(parts[0].iter().map(|&i| entry(i)))?;
+        for part in &parts[1..] {
+            let mut hot = Hot::new(span())?;
+            hot.add_bulk(part.iter().map(|&i| entry(i)))?;
+            merged.absorb(hot)?;
+        }
+
+        let mut single = Hot::new(span())?;
+        single.add_bulk(parts.iter().flatten().map(|&i| entry(i)))?;
+        assert_eq!(merged.len(), single.len());
+        assert_eq!(
+            merged.len(),
+            parts.iter().flatten().collect::<HashSet<_>>().len()
+        );
+
+        let merged: Arc<Cold> = merged.try_into()?;
+        let single: Arc<Cold> = single.try_into()?;
+        assert_eq!(merged.fingerprint(), single.fingerprint());
+
+        assert_eq!(rows(&merged), rows(&single));
+
+        let mut other = Hot::new(Window::new(2000, 1000))?;
+        other.add(1, 1, 2000, b"elsewhere")?;
+        let mut hot = Hot::new(span())?;
+        assert!(hot.absorb(other).is_err(), "windows differ");
+        assert!(hot.is_empty());
         Ok(())
     }
 
     #[test]
-    fn cold_iter_desc() -> Result<()> {
-        let mut hot = Hot::new(Window::new(1000, 100))?;
+    fn add_checks_window_bounds() -> Result<()> {
+        let span = || Window::new(1000, 100);
+        let end = span().end_exclusive();
+
+        let mut hot = Hot::new(span())?;
+        hot.add(1, 1, 1000, b"at epoch")?;
+        hot.add(1, 2, end - 1, b"last second")?;
+        for (cid, ts) in [(3, 999), (4, end), (5, u64::MAX)] {
+            let err = hot.add(1, cid, ts, b"outside").unwrap_err();
+            assert_eq!(
+                err.downcast_ref::<OutsideWindow>(),
+                Some(&OutsideWindow {
+                    end_exclusive: 1100,
+                    epoch: 1000,
+                    ts
+                })
+            );
+            assert!(!hot.contains_cid(cid));
+        }
+        assert_eq!(hot.len(), 2);
 
-        hot.add(1, 100, 1010, b"a")?;
-        hot.add(2, 200, 1020, b"b")?;
-        hot.add(3, 300, 1005, b"c")?;
+        let entries = |ts: &[Timestamp]| -> Vec<(Uid, Cid, Timestamp, Vec<u8>)> {
+            ts.iter()
+                .enumerate()
+                .map(|(i, &ts)| (1, 10 + i as Cid, ts, vec![i as u8]))
+                .collect()
+        };
+        let batch = entries(&[1000, 0, end - 1, end, 1050]);
+
+        let mut strict = Hot::new(span())?;
+        let (progress, err) = strict.add_bulk_progress(batch.clone()).unwrap_err();
+        assert_eq!(progress, 0, "the whole chunk is rolled back");
+        assert!(err.downcast_ref::<OutsideWindow>().is_some(), "{err}");
+        assert!(strict.is_empty() && !strict.contains_cid(10));
+
+        let mut lenient = Hot::new(span())?.on_out_of_window(OutOfWindow::Skip);
+        assert_eq!(lenient.add_bulk(batch)?, 2);
+        lenient.add(1, 99, 999, b"skipped")?;
+        assert_eq!(lenient.rejected(), 3);
+        assert_eq!(lenient.add_bulk([(1, 50, 1001, b"in window")])?, 0);
+
+        let cold: Arc<Cold> = lenient.try_into()?;
+        let ts: Vec<Timestamp> = cold.iter(Order::Asc, 0).map(|e| e.timestamp()).collect();
+        assert_eq!(ts, [1000, 1001, 1050, end - 1]);
+        Ok(())
+    }
 
-        let cold: Arc<Cold> = hot.try_into()?;
+    #[test]
+    fn timestamp_spread_tracks_extremes() -> Result<()> {
+        let mut hot = Hot::new(Window::new(3600, 3600))?.on_out_of_window(OutOfWindow::Skip);
+        assert_eq!(hot.timestamp_spread(), None);
+
+        hot.add(1, 1, 5000, b"a")?;
+        assert_eq!(hot.timestamp_spread(), Some((5000, 5000)));
+
+        hot.add_bulk([(1, 2, 4000, b"b"), (1, 3, 6000, b"c"), (1, 4, 4500, b"d")])?;
+        assert_eq!(hot.timestamp_spread(), Some((4000, 6000)));
+
+        // A misconfigured source: three hours into a one-hour window.
+        hot.add_bulk([(1, 5, 100, &b"early"[..]), (1, 6, 11_000, b"late")])?;
+        assert_eq!(hot.timestamp_spread(), Some((100, 11_000)));
+        assert_eq!(hot.rejected(), 2);
+
+        let mut other = Hot::new(Window::new(3600, 3600))?;
+        other.add(2, 7, 3600, b"e")?;
+        let mut merged = Hot::new(Window::new(3600, 3600))?;
+        merged.add(1, 8, 7000, b"f")?;
+        merged.absorb(other)?;
+        assert_eq!(merged.timestamp_spread(), Some((3600, 7000)));
+        Ok(())
+    }
 
-        // Desc: 200@1020, 100@1010, 300@1005
-        let entries: Vec<_> = cold.iter(Order::Desc, 0).collect();
-        assert_eq!(entries[0].uid, 2);
-        assert_eq!(entries[0].timestamp(), 1020);
-        assert_eq!(entries[1].uid, 1);
-        assert_eq!(entries[1].timestamp(), 1010);
-        assert_eq!(entries[2].uid, 3);
-        assert_eq!(entries[2].timestamp(), 1005);
+    #[test]
+    fn timestamp_spread_warns_once() -> Result<()> {
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_writer(captured.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || -> Result<()> {
+            let mut hot = Hot::new(Window::new(3600, 3600))?.on_out_of_window(OutOfWindow::Skip);
+            hot.add_bulk([(1, 1, 3600, b"a"), (1, 2, 7199, b"b")])?;
+            assert!(captured.output().is_empty(), "spread within the window");
+
+            hot.add(1, 3, 7200, b"next window")?;
+            hot.add(1, 4, 100, b"earlier still")?;
+            Ok(())
+        })?;
+
+        let out = captured.output();
+        let warnings: Vec<&str> = out
+            .lines()
+            .filter(|line| line.contains("timestamps span more than its window"))
+            .collect();
+        assert_eq!(warnings.len(), 1, "{out}");
+        assert!(warnings[0].contains("WARN"), "{out}");
+        assert!(warnings[0].contains("first=3600 last=7200"), "{out}");
         Ok(())
     }
 
     #[test]
-    fn cold_iter_start_skips_entries() -> Result<()> {
+    fn remove_retracts_before_freeze() -> Result<()> {
         let mut hot = Hot::new(Window::new(1000, 100))?;
-        hot.add(1, 100, 1010, b"a")?;
-        hot.add(2, 200, 1020, b"b")?;
-        hot.add(3, 300, 1050, b"c")?;
-        hot.add(4, 400, 1080, b"d")?;
+        for cid in 0..10u64 {
+            hot.add(cid as Uid, cid, 1010 - cid, format!("v1-{cid}").as_bytes())?;
+        }
 
-        let cold: Arc<Cold> = hot.try_into()?;
+        assert!(!hot.remove(42), "never added");
+        for cid in [3, 7, 0] {
+            assert!(hot.remove(cid));
+        }
+        assert!(!hot.remove(3), "already removed");
+        assert!(!hot.contains_cid(7));
 
-        // start=1020 should skip the entry at 1010
-        let entries: Vec<_> = cold.iter(Order::Asc, 1020).collect();
-        assert_eq!(entries.len(), 3, "asc: should skip 1 entry before start");
-        assert_eq!(entries[0].timestamp(), 1020);
-        assert_eq!(entries[1].timestamp(), 1050);
-        assert_eq!(entries[2].timestamp(), 1080);
+        hot.add(70, 7, 1050, b"v2-7")?;
+        assert_eq!(hot.len(), 8);
 
-        // Desc with start=1020 should yield 1080, 1050, 1020
-        let entries: Vec<_> = cold.iter(Order::Desc, 1020).collect();
-        assert_eq!(entries.len(), 3, "desc: should skip 1 entry before start");
-        assert_eq!(entries[0].timestamp(), 1080);
-        assert_eq!(entries[1].timestamp(), 1050);
-        assert_eq!(entries[2].timestamp(), 1020);
+        let mut expected: Vec<(Uid, Timestamp, Cid, Vec<u8>)> = [1, 2, 4, 5, 6, 8, 9]
+            .into_iter()
+            .map(|cid: u64| {
+                (
+                    cid as Uid,
+                    1010 - cid,
+                    cid,
+                    format!("v1-{cid}").into_bytes(),
+                )
+            })
+            .chain([(70, 1050, 7, b"v2-7".to_vec())])
+            .collect();
+        expected.sort_by_key(|&(_, ts, cid, _)| (ts, cid));
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert_eq!(rows(&cold), expected);
+
+        // Presorted freezing and absorbing skip removed blobs too.
+        let mut sorted = Hot::without_dedup(Window::new(1000, 100))?;
+        let mut other = Hot::new(Window::new(1000, 100))?;
+        for cid in 0..6u64 {
+            sorted.add(1, cid, 1000 + cid, format!("s{cid}").as_bytes())?;
+            other.add(2, 100 + cid, 1000 + cid, format!("o{cid}").as_bytes())?;
+        }
+        assert!(sorted.remove(4));
+        assert!(other.remove(101));
+        assert!(other.remove(104));
+
+        let mut merged = Hot::new(Window::new(1000, 100))?;
+        merged.absorb(other)?;
+        let merged: Arc<Cold> = merged.try_into()?;
+        let blobs: Vec<(Cid, Vec<u8>)> = rows(&merged).into_iter().map(|r| (r.2, r.3)).collect();
+        assert_eq!(
+            blobs,
+            [
+                (100, b"o0".to_vec()),
+                (102, b"o2".to_vec()),
+                (103, b"o3".to_vec()),
+                (105, b"o5".to_vec())
+            ]
+        );
+
+        let sorted = sorted.freeze_assume_sorted()?;
+        let cids: Vec<Cid> = rows(&sorted).into_iter().map(|r| r.2).collect();
+        assert_eq!(cids, [0, 1, 2, 3, 5]);
+        assert_eq!(
+            sorted.get(5).map(|e| e.resolve().1.to_vec()),
+            Some(b"s5".to_vec())
+        );
         Ok(())
     }
 
-    #[test]
-    fn cold_resolve_deduplicated() -> Result<()> {
-        let mut hot = Hot::new(Window::new(1000, 100))?;
+    /// Passes writes to the spill until `batches` runs out, then fails each
+    /// ipc message before any of its bytes are written, so the stream stays
+    /// readable once `batches` is raised again.
+    #[cfg(not(target_arch = "wasm32"))]
+    struct BatchLimit<W> {
+        batches: Arc<std::sync::atomic::AtomicUsize>,
+        inner: W,
+    }
 
-        // Add duplicate CIDs — only first blob should be kept
-        hot.add(1, 100, 1010, b"first")?;
-        hot.add(2, 100, 1020, b"second")?;
+    #[cfg(not(target_arch = "wasm32"))]
+    impl<W: Write> Write for BatchLimit<W> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            use std::sync::atomic::Ordering::Relaxed;
 
-        let cold: Arc<Cold> = hot.try_into()?;
-        assert_eq!(cold.len(), 1);
+            // Every ipc message opens with a lone continuation marker write.
+            if buf == [0xff; 4] {
+                let left = self.batches.load(Relaxed);
+                if left == 0 {
+                    return Err(std::io::Error::other("injected blob store failure"));
+                }
+                self.batches.store(left.saturating_sub(1), Relaxed);
+            }
+            self.inner.write(buf)
+        }
 
-        let entries: Vec<_> = cold.iter(Order::Asc, 0).collect();
-        let (cid, blob) = entries[0].resolve();
-        assert_eq!(cid, 100);
-        assert_eq!(blob, b"first");
-        Ok(())
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.inner.flush()
+        }
     }
 
-    #[test]
-    fn cold_resolve_retrieves_blobs() -> Result<()> {
-        let mut hot = Hot::new(Window::new(1000, 100))?;
+    /// A hot arena whose blob store accepts only as many more batches as the
+    /// returned counter holds; unlimited until the test lowers it.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn limited_hot() -> Result<(Hot, Arc<std::sync::atomic::AtomicUsize>)> {
+        use super::super::backing::Spill;
+
+        let batches = Arc::new(std::sync::atomic::AtomicUsize::new(usize::MAX));
+        let limit = Arc::clone(&batches);
+        let spill = Spill::wrapped("crimeline-test", |inner| {
+            Box::new(BatchLimit {
+                batches: limit,
+                inner,
+            })
+        })?;
 
-        hot.add(1, 100, 1010, b"blob_100")?;
-        hot.add(2, 200, 1020, b"blob_200")?;
-        hot.add(3, 300, 1005, b"blob_300")?;
+        let mut hot = Hot::new(Window::new(0, 10000))?;
+        hot.blobs = BlobStoreBuilder::with_spill(spill)?;
+        Ok((hot, batches))
+    }
 
-        let cold: Arc<Cold> = hot.try_into()?;
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn absorb_keeps_written_rows_on_failure() -> Result<()> {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let mut other = Hot::new(Window::new(0, 10000))?;
+        for chunk in 0..3u64 {
+            other.add_bulk((0..10).map(|i| (1, chunk * 10 + i, i, vec![chunk as u8])))?;
+        }
+
+        let (mut hot, batches) = limited_hot()?;
+        hot.add(1, 5, 5, b"dup")?;
+        batches.store(1, Relaxed);
+
+        assert!(hot.absorb(other).is_err());
+        // First batch written minus the duplicate; the second exceeds the limit.
+        assert_eq!(hot.len(), 10);
+        assert_eq!(hot.cid_set.as_ref().map(HashSet::len), Some(10));
+        assert!(!hot.contains_cid(10), "unwritten rows are unclaimed");
+
+        batches.store(usize::MAX, Relaxed);
+        let cold: Arc<Cold> = hot.try_into()?;
+        let cids: Vec<Cid> = cold.iter(Order::Asc, 0).map(|e| e.resolve().0).collect();
+        assert_eq!(cids, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(
+            cold.get(5).map(|e| e.resolve().1.to_vec()),
+            Some(b"dup".to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn add_bulk_progress_on_failure() -> Result<()> {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let n = 3 * BLOB_BATCH;
+        let entries: Vec<(Uid, Cid, Timestamp, Vec<u8>)> = (0..n)
+            .map(|i| (i as Uid, i as Cid, i as Timestamp, vec![i as u8]))
+            .collect();
+
+        let (mut hot, batches) = limited_hot()?;
+        batches.store(1, Relaxed);
+
+        let (progress, _) = hot
+            .add_bulk_progress(entries.clone())
+            .expect_err("blob store should fail in the second chunk");
+
+        assert_eq!(progress, BLOB_BATCH, "only the first chunk is committed");
+        assert_eq!(hot.len(), BLOB_BATCH);
+        assert_eq!(
+            hot.cid_set.as_ref().map(HashSet::len),
+            Some(BLOB_BATCH),
+            "rolled back chunk cids"
+        );
+        assert_eq!(hot.timestamps.len(), BLOB_BATCH);
+        assert_eq!(hot.uids.len(), BLOB_BATCH);
+
+        // resume from the reported offset once the store recovers
+        batches.store(usize::MAX, Relaxed);
+        let resumed = hot
+            .add_bulk_progress(entries.into_iter().skip(progress))
+            .map_err(|(_, e)| e)?;
+        assert_eq!(resumed, n - BLOB_BATCH);
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert_eq!(cold.len(), n);
+        for (i, e) in cold.iter(Order::Asc, 0).enumerate() {
+            assert_eq!(e.resolve(), (i as Cid, [i as u8].as_slice()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn without_dedup_builds_unique_input() -> Result<()> {
+        let entries: Vec<_> = (0..50u64)
+            .map(|i| (i as Uid, 1000 - i, 1049 - i, vec![i as u8; 3]))
+            .collect();
+
+        let mut deduped = Hot::new(Window::new(1000, 100))?;
+        deduped.add_bulk(entries.clone())?;
+
+        let mut hot = Hot::without_dedup(Window::new(1000, 100))?;
+        hot.add_bulk(entries[..25].to_vec())?;
+        for (uid, cid, ts, blob) in &entries[25..] {
+            hot.add(*uid, *cid, *ts, blob)?;
+        }
+
+        assert!(hot.contains_cid(990));
+        // In memory, the blobs themselves dominate the heap.
+        if !cfg!(target_arch = "wasm32") {
+            assert!(hot.usage().heap < deduped.usage().heap, "no cid set");
+        }
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        let expected: Arc<Cold> = deduped.try_into()?;
+        assert_eq!(cold.len(), 50);
+        assert_eq!(cold.fingerprint(), expected.fingerprint());
+
+        let entry = cold.get(990).expect("indexed by cid");
+        assert_eq!((entry.uid, entry.timestamp()), (10, 1039));
+        Ok(())
+    }
+
+    #[test]
+    fn batch_columns_projects() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..10u64 {
+            hot.add(i as Uid, 100 + i, 1000 + i, b"blob")?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let batch = cold.batch_columns(2..5, &[2, 1])?;
+        let names: Vec<&str> = batch
+            .schema_ref()
+            .fields()
+            .iter()
+            .map(|f| f.name().as_str())
+            .collect();
+        assert_eq!(names, ["timestamp", "cid"]);
+        let timestamps: &UInt64Array = batch.column(0).as_any().downcast_ref().unwrap();
+        assert_eq!(timestamps.values(), &[1002, 1003, 1004]);
+
+        let empty = cold.batch_columns(0..7, &[])?;
+        assert_eq!((empty.num_columns(), empty.num_rows()), (0, 7));
+
+        let all = cold.batch_columns(0..10, &ALL_COLUMNS)?;
+        assert_eq!(all.schema(), schema());
+        Ok(())
+    }
+
+    #[test]
+    fn iter_after_cid_resumes_within_equal_timestamps() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        // Cids 10..=14 all share one timestamp.
+        for cid in 10..15 {
+            hot.add(1, cid, 1005, b"same")?;
+        }
+        hot.add(2, 20, 1001, b"early")?;
+        hot.add(2, 30, 1009, b"late")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        let after = |cid, order| -> Option<Vec<Cid>> {
+            Some(
+                cold.iter_after_cid(cid, order)?
+                    .map(|e| e.resolve().0)
+                    .collect(),
+            )
+        };
+
+        let all: Vec<Cid> = cold.iter(Order::Asc, 0).map(|e| e.resolve().0).collect();
+        let mid = all.iter().position(|&c| c == 12).unwrap();
+
+        assert_eq!(after(12, Order::Asc).as_deref(), Some(&all[mid + 1..]));
+
+        let mut before = all[..mid].to_vec();
+        before.reverse();
+        assert_eq!(after(12, Order::Desc), Some(before));
+
+        assert_eq!(after(30, Order::Asc), Some(vec![]));
+        assert_eq!(after(99, Order::Asc), None);
+        Ok(())
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn usage_matches_allocator() {
+        use crate::usage::tests::assert_usage_within;
+
+        // The cid set estimate assumes a stored hash per bucket, and the
+        // arrow writer's buffers are not visible to us.
+        let hot = assert_usage_within(0.2, || {
+            let mut hot = Hot::new(Window::new(0, 100_000)).unwrap();
+            hot.add_bulk((0..5_000u64).map(|i| (i as Uid, i, i * 7 % 100_000, b"blob")))
+                .unwrap();
+            hot
+        });
+
+        struct Shared(Arc<Cold>);
+
+        impl ReportUsage for Shared {
+            fn usage(&self) -> Usage {
+                self.0.usage()
+            }
+        }
+
+        let cold: Arc<Cold> = hot.try_into().unwrap();
+        let mut buf = Vec::new();
+        cold.export(&mut buf).unwrap();
+        let data = Bytes::from(buf);
+
+        // Arrow array metadata and the tempfile handle are not counted. The
+        // input is kept alive so freeing it does not offset the arena.
+        let imported = assert_usage_within(0.3, || Shared(Cold::import(data.clone()).unwrap()));
+        assert_eq!(imported.0.fingerprint(), cold.fingerprint());
+    }
+
+    #[test]
+    fn contains_cid_tracks_dedup() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        assert!(!hot.contains_cid(100));
+
+        hot.add(1, 100, 1010, b"a")?;
+        assert!(hot.contains_cid(100));
+        assert!(!hot.contains_cid(200));
+
+        hot.add(2, 100, 1020, b"dup")?;
+        hot.add_bulk([(3, 200, 1030, b"b")])?;
+        assert!(hot.contains_cid(100));
+        assert!(hot.contains_cid(200));
+        assert_eq!(hot.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn usage_counts_entries_as_items() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..7u64 {
+            hot.add(1, i, 1000 + i, b"x")?;
+        }
+        hot.add(1, 0, 1000, b"dup")?;
+        assert_eq!(hot.usage().items, 7);
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        let u = cold.usage();
+        assert_eq!(u.items, 7);
+        assert!(u.bytes_per_item().is_some_and(|b| b > 0.0));
+        Ok(())
+    }
+
+    #[test]
+    fn fingerprint_depends_on_content_only() -> Result<()> {
+        let entries: Vec<(Uid, Cid, Timestamp, &[u8])> = vec![
+            (1, 100, 1010, b"a"),
+            (2, 200, 1020, b"bb"),
+            (3, 300, 1030, b"ccc"),
+        ];
+
+        let mut one_by_one = Hot::new(Window::new(1000, 100))?;
+        for &(uid, cid, ts, blob) in entries.iter().rev() {
+            one_by_one.add(uid, cid, ts, blob)?;
+        }
+        let mut bulk = Hot::new(Window::new(1000, 100))?;
+        bulk.add_bulk(entries.clone())?;
+
+        let a: Arc<Cold> = one_by_one.try_into()?;
+        let b: Arc<Cold> = bulk.try_into()?;
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        let mut buf = Vec::new();
+        a.export(&mut buf)?;
+        assert_eq!(
+            Cold::import(Bytes::from(buf))?.fingerprint(),
+            a.fingerprint()
+        );
+
+        let mut changed = Hot::new(Window::new(1000, 100))?;
+        changed.add_bulk(entries.iter().map(|&(u, c, t, _)| (u, c, t, b"x")))?;
+        let changed: Arc<Cold> = changed.try_into()?;
+        assert_ne!(changed.fingerprint(), a.fingerprint(), "different blobs");
+
+        let mut shifted = Hot::new(Window::new(1000, 200))?;
+        shifted.add_bulk(entries)?;
+        let shifted: Arc<Cold> = shifted.try_into()?;
+        assert_ne!(shifted.fingerprint(), a.fingerprint(), "different window");
+        Ok(())
+    }
+
+    #[test]
+    fn cold_empty() -> Result<()> {
+        let hot = Hot::new(Window::new(1000, 100))?;
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert_eq!(cold.len(), 0);
+        assert_eq!(cold.iter(Order::Asc, 0).count(), 0);
+        assert_eq!(cold.first_timestamp(), None);
+        assert_eq!(cold.last_timestamp(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn cold_first_last_timestamp_sparse() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 1000))?;
+        hot.add(1, 100, 1500, b"a")?;
+        hot.add(2, 200, 1042, b"b")?;
+        hot.add(3, 300, 1917, b"c")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert_eq!(cold.first_timestamp(), Some(1042));
+        assert_eq!(cold.last_timestamp(), Some(1917));
+        Ok(())
+    }
+
+    #[test]
+    fn cold_iter_desc() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+
+        hot.add(1, 100, 1010, b"a")?;
+        hot.add(2, 200, 1020, b"b")?;
+        hot.add(3, 300, 1005, b"c")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        // Desc: 200@1020, 100@1010, 300@1005
+        let entries: Vec<_> = cold.iter(Order::Desc, 0).collect();
+        assert_eq!(entries[0].uid, 2);
+        assert_eq!(entries[0].timestamp(), 1020);
+        assert_eq!(entries[1].uid, 1);
+        assert_eq!(entries[1].timestamp(), 1010);
+        assert_eq!(entries[2].uid, 3);
+        assert_eq!(entries[2].timestamp(), 1005);
+        Ok(())
+    }
+
+    #[test]
+    fn cold_iter_start_skips_entries() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        hot.add(1, 100, 1010, b"a")?;
+        hot.add(2, 200, 1020, b"b")?;
+        hot.add(3, 300, 1050, b"c")?;
+        hot.add(4, 400, 1080, b"d")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        // start=1020 should skip the entry at 1010
+        let entries: Vec<_> = cold.iter(Order::Asc, 1020).collect();
+        assert_eq!(entries.len(), 3, "asc: should skip 1 entry before start");
+        assert_eq!(entries[0].timestamp(), 1020);
+        assert_eq!(entries[1].timestamp(), 1050);
+        assert_eq!(entries[2].timestamp(), 1080);
+
+        // Desc with start=1020 should yield 1080, 1050, 1020
+        let entries: Vec<_> = cold.iter(Order::Desc, 1020).collect();
+        assert_eq!(entries.len(), 3, "desc: should skip 1 entry before start");
+        assert_eq!(entries[0].timestamp(), 1080);
+        assert_eq!(entries[1].timestamp(), 1050);
+        assert_eq!(entries[2].timestamp(), 1020);
+        Ok(())
+    }
+
+    #[test]
+    fn cold_resolve_deduplicated() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+
+        // Add duplicate CIDs — only first blob should be kept
+        hot.add(1, 100, 1010, b"first")?;
+        hot.add(2, 100, 1020, b"second")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert_eq!(cold.len(), 1);
+
+        let entries: Vec<_> = cold.iter(Order::Asc, 0).collect();
+        let (cid, blob) = entries[0].resolve();
+        assert_eq!(cid, 100);
+        assert_eq!(blob, b"first");
+        Ok(())
+    }
+
+    #[test]
+    fn cold_resolve_retrieves_blobs() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+
+        hot.add(1, 100, 1010, b"blob_100")?;
+        hot.add(2, 200, 1020, b"blob_200")?;
+        hot.add(3, 300, 1005, b"blob_300")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
 
         // Sorted by (timestamp, cid): 300@1005, 100@1010, 200@1020
         let entries: Vec<_> = cold.iter(Order::Asc, 0).collect();
@@ -616,6 +2545,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
     fn cold_usage_traits() -> Result<()> {
         let mut hot = Hot::new(Window::new(1000, 100))?;
         hot.add(1, 100, 1010, b"test1")?;
@@ -629,13 +2562,740 @@ mod tests {
         Ok(())
     }
 
+    /// Log output of a `tracing_subscriber::fmt` subscriber.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Captured {
+        fn output(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl tracing_subscriber::fmt::MakeWriter<'_> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&self) -> Self {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn spans_cover_freeze_and_export() -> Result<()> {
+        use tracing_subscriber::fmt::format::FmtSpan;
+
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::DEBUG)
+            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(captured.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || -> Result<()> {
+            let mut hot = Hot::new(Window::new(1000, 100))?;
+            hot.add_bulk((0..10u64).map(|i| (i as Uid, i, 1000 + i, b"blob")))?;
+            let cold: Arc<Cold> = hot.try_into()?;
+
+            let mut buf = Vec::new();
+            cold.export(&mut buf)?;
+            Cold::import(buf.into())?;
+            Ok(())
+        })?;
+
+        let out = captured.output();
+
+        // Close events read `DEBUG outer{..}:inner{..}: target: close time.busy=..`,
+        // keyed here by the innermost span.
+        let closed: HashMap<&str, &str> = out
+            .lines()
+            .filter_map(|line| {
+                let (spans, _) = line.split_once(": crimeline")?;
+                let inner = spans.rsplit("}:").next()?.rsplit("DEBUG ").next()?;
+                Some((inner.split_once('{')?.0, line))
+            })
+            .filter(|(_, line)| line.contains("close"))
+            .collect();
+
+        let find = |name: &str| {
+            *closed
+                .get(name)
+                .unwrap_or_else(|| panic!("no {name} span in:\n{out}"))
+        };
+
+        for (name, fields) in [
+            ("add_bulk", &["epoch=1000", "len=10"][..]),
+            ("freeze", &["epoch=1000", "len=10"]),
+            ("build_blobs", &["len=10", "blob_bytes=40"]),
+            ("export", &["epoch=1000", "len=10", "bytes="]),
+            ("import", &["epoch=1000", "len=10", "bytes="]),
+        ] {
+            let line = find(name);
+            assert!(line.contains("time.busy"), "{line}");
+            for field in fields {
+                assert!(line.contains(field), "{field} missing from {line}");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_bytes_outlives_arena() -> Result<()> {
+        let blobs: Vec<Bytes> = (0..100)
+            .map(|i| Bytes::from(format!("blob number {i}")))
+            .collect();
+
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        hot.add_bulk(
+            blobs
+                .iter()
+                .enumerate()
+                .map(|(i, b)| (i as Uid, i as Cid, 1000 + i as u64, b.clone())),
+        )?;
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let held: Vec<(Cid, Bytes)> = cold
+            .iter(Order::Asc, 0)
+            .map(|e| {
+                let owned = e.resolve_bytes();
+                let (cid, borrowed) = e.resolve();
+                assert_eq!((owned.0, owned.1.as_ref()), (cid, borrowed));
+                assert_eq!(owned.1.as_ptr(), borrowed.as_ptr(), "no copy");
+                owned
+            })
+            .collect();
+
+        let timeline = crate::Timeline::new(vec![cold]);
+        drop(timeline);
+
+        let check = std::thread::spawn(move || {
+            for (cid, blob) in &held {
+                assert_eq!(blob, &blobs[*cid as usize]);
+            }
+            held.len()
+        });
+        assert_eq!(check.join().unwrap(), 100);
+        Ok(())
+    }
+
+    #[test]
+    fn cold_usage_counts_blob_bytes() -> Result<()> {
+        let blobs: Vec<Vec<u8>> = (0..50).map(|i| vec![b'x'; i * 7]).collect();
+
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for (i, blob) in blobs.iter().enumerate() {
+            hot.add(i as Uid, i as Cid, 1000 + i as u64, blob)?;
+        }
+        hot.add(0, 0, 1000, b"duplicate, not stored")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        let expected: usize = blobs.iter().map(Vec::len).sum();
+
+        let u = cold.usage();
+        assert_eq!(u.blob_bytes, expected as u64);
+        if !cfg!(target_arch = "wasm32") {
+            assert!(u.blob_bytes <= u.disk, "{} > {}", u.blob_bytes, u.disk);
+        }
+
+        let empty: Arc<Cold> = Hot::new(Window::new(1000, 100))?.try_into()?;
+        assert_eq!(empty.usage().blob_bytes, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn downsample_keeps_every_nth() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 1000))?;
+        for i in 0..100u64 {
+            hot.add(i as Uid, i, 1000 + i, format!("blob{i}").as_bytes())?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let sampled = cold.downsample(10)?;
+        assert_eq!(sampled.span, cold.span);
+
+        let expected: Vec<(Uid, Timestamp, Cid, Vec<u8>)> = (0..100u64)
+            .step_by(10)
+            .map(|i| (i as Uid, 1000 + i, i, format!("blob{i}").into_bytes()))
+            .collect();
+        assert_eq!(rows(&sampled), expected);
+
+        assert_eq!(cold.downsample(1)?.len(), 100);
+        assert_eq!(cold.downsample(1000)?.len(), 1);
+        assert_eq!(cold.downsample(1000)?.first_timestamp(), Some(1000));
+        assert!(cold.downsample(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn repartition_splits_window_and_entries() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..10u64 {
+            hot.add(i as Uid, i, 1000 + i * 10, format!("blob{i}").as_bytes())?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let (older, newer) = cold.repartition(1042)?;
+        assert_eq!(older.span, Window::new(1000, 42));
+        assert_eq!(newer.span, Window::new(1042, 58));
+
+        let mut rejoined = rows(&older);
+        assert_eq!(rejoined.len(), 5);
+        rejoined.extend(rows(&newer));
+        assert_eq!(rejoined, rows(&cold));
+
+        let (all, none) = cold.repartition(5000)?;
+        assert_eq!((all.len(), none.len()), (10, 0));
+        assert_eq!(none.span, Window::new(1100, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn iter_with_extreme_starts() -> Result<()> {
+        let epoch = u64::MAX - 100;
+        let mut hot = Hot::new(Window::new(epoch, 1000))?;
+        for i in 0..100u64 {
+            hot.add(1, i, epoch + i, b"x")?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let timestamps = |start| -> Vec<Timestamp> {
+            cold.iter(Order::Asc, start)
+                .map(|e| e.timestamp())
+                .collect()
+        };
+        assert_eq!(timestamps(0).len(), 100);
+        assert_eq!(timestamps(u64::MAX - 2), [u64::MAX - 2, u64::MAX - 1]);
+        assert!(timestamps(u64::MAX).is_empty());
+
+        let timeline = crate::Timeline::new(vec![Arc::clone(&cold)]);
+        assert_eq!(
+            timeline
+                .iter(u64::MAX - 1, Order::Desc)
+                .drain_owned()
+                .entries
+                .len(),
+            1
+        );
+        assert_eq!(timeline.coverage(), [(epoch, u64::MAX)]);
+
+        // A start more than u32::MAX past the epoch must not truncate into
+        // the window.
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        hot.add(1, 1, 1005, b"x")?;
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert_eq!(cold.iter(Order::Asc, 1000 + (1 << 32) + 5).count(), 0);
+        assert_eq!(cold.iter(Order::Asc, u64::MAX).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_by_cid_finds_surviving_entry() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 1000))?;
+        for i in 0..100u64 {
+            hot.add(
+                i as Uid % 7,
+                500 - i,
+                1000 + i % 10,
+                format!("blob{i}").as_bytes(),
+            )?;
+        }
+        hot.add(99, 450, 1999, b"duplicate")?;
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        for i in [0u64, 42, 99] {
+            assert_eq!(
+                cold.resolve_by_cid(500 - i),
+                Some((format!("blob{i}").as_bytes(), 1000 + i % 10, i as Uid % 7))
+            );
+        }
+        assert_eq!(
+            cold.resolve_by_cid(450),
+            Some((&b"blob50"[..], 1000, 50 % 7)),
+            "deduped cid keeps the first add"
+        );
+        assert_eq!(cold.resolve_by_cid(7), None);
+        assert_eq!(cold.resolve_by_cid(501), None);
+        Ok(())
+    }
+
+    #[test]
+    fn resolve_many_matches_single_resolves() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 1000))?;
+        for i in 0..100u64 {
+            hot.add(1, i, 1000 + i, format!("blob{i}").as_bytes())?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let indices = [97, 3, 42, 3, 0, 99, 58];
+        let mut out = vec![(7, &b"kept"[..])];
+        cold.resolve_many(&indices, &mut out)?;
+
+        let expected: Vec<(Cid, &[u8])> = std::iter::once((7, &b"kept"[..]))
+            .chain(indices.iter().map(|&i| cold.blobs.resolve(i)))
+            .collect();
+        assert_eq!(out, expected);
+
+        let err = cold.resolve_many(&[1, 100, 2], &mut out).unwrap_err();
+        assert!(err.to_string().contains("index 100 out of range"), "{err}");
+        assert_eq!(out.len(), 1 + indices.len(), "untouched on error");
+
+        cold.resolve_many(&[], &mut out)?;
+        assert_eq!(out.len(), 1 + indices.len());
+        Ok(())
+    }
+
+    #[test]
+    fn iter_by_cid_ascending() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 1000))?;
+        for i in 0..200u64 {
+            // Cids scattered against timestamp order.
+            hot.add(
+                (i % 9) as Uid,
+                i * 7919 % 1009,
+                1000 + i,
+                format!("blob{i}").as_bytes(),
+            )?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let by_cid: Vec<(Cid, Uid, Timestamp, Vec<u8>)> = cold
+            .iter_by_cid()
+            .map(|e| {
+                let (cid, blob) = e.resolve();
+                (cid, e.uid, e.timestamp(), blob.to_vec())
+            })
+            .collect();
+        assert_eq!(by_cid.len(), cold.len());
+        assert!(by_cid.windows(2).all(|w| w[0].0 < w[1].0));
+
+        let mut by_ts: Vec<_> = cold
+            .iter(Order::Asc, 0)
+            .map(|e| {
+                let (cid, blob) = e.resolve();
+                (cid, e.uid, e.timestamp(), blob.to_vec())
+            })
+            .collect();
+        by_ts.sort();
+        assert_eq!(by_cid, by_ts);
+        Ok(())
+    }
+
+    #[test]
+    fn remap_uids_shares_blobs() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..20u64 {
+            hot.add(
+                (i % 4) as Uid,
+                100 - i,
+                1000 + i / 2,
+                format!("blob{i}").as_bytes(),
+            )?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let remapped = cold.remap_uids(|uid| uid + 10)?;
+        assert_eq!(remapped.span, cold.span);
+        assert!(Arc::ptr_eq(&remapped.blobs, &cold.blobs));
+
+        let expected: Vec<_> = rows(&cold)
+            .into_iter()
+            .map(|(uid, ts, cid, blob)| (uid + 10, ts, cid, blob))
+            .collect();
+        assert_eq!(rows(&remapped), expected);
+        assert_eq!(remapped.get(95).map(|e| e.uid), Some(5 % 4 + 10));
+
+        drop(cold);
+        assert_eq!(
+            remapped.get(100).map(|e| e.resolve().1.to_vec()),
+            Some(b"blob0".to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn external_blobs_share_one_pool() -> Result<()> {
+        let arena = |epoch: Timestamp, cids: std::ops::Range<Cid>| -> Result<Arc<Cold>> {
+            let mut hot = Hot::new(Window::new(epoch, 1000))?;
+            for (i, cid) in cids.enumerate() {
+                hot.add(
+                    i as Uid,
+                    cid,
+                    epoch + i as u64,
+                    format!("blob{cid}").as_bytes(),
+                )?;
+            }
+            hot.try_into()
+        };
+        let a = arena(0, 0..10)?;
+        let b = arena(1000, 5..15)?;
+
+        let pool = Arc::new(BlobPool::from_arenas([&a, &b])?);
+        assert_eq!(pool.len(), 15, "overlapping cids stored once");
+
+        let pooled_a = a.with_external_blobs(Arc::clone(&pool))?;
+        let pooled_b = b.with_external_blobs(Arc::clone(&pool))?;
+
+        for (own, pooled) in [(&a, &pooled_a), (&b, &pooled_b)] {
+            assert_eq!(rows(pooled), rows(own));
+            assert_eq!(pooled.fingerprint(), own.fingerprint());
+        }
+
+        let (entry_a, entry_b) = (pooled_a.get(7).unwrap(), pooled_b.get(7).unwrap());
+        let (from_a, from_b) = (entry_a.resolve().1, entry_b.resolve().1);
+        assert_eq!(from_a, b"blob7");
+        assert!(
+            std::ptr::eq(from_a, from_b),
+            "common cid resolves to one copy"
+        );
+        let (entry_a, entry_b) = (a.get(7).unwrap(), b.get(7).unwrap());
+        assert!(!std::ptr::eq(entry_a.resolve().1, entry_b.resolve().1));
+
+        let partial = Arc::new(BlobPool::from_arenas([&a])?);
+        let err = b.with_external_blobs(partial).err().unwrap();
+        assert!(err.to_string().contains("cid 10 missing"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn export_uid_only_that_author() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..30u64 {
+            hot.add((i % 3) as Uid, i, 1000 + i, format!("blob{i}").as_bytes())?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let mut buf = Vec::new();
+        let written = cold.export_uid(1, &mut buf, &ExportOptions::default())?;
+        assert_eq!(written, 10);
+
+        let imported = Cold::import(Bytes::from(buf))?;
+        assert_eq!(imported.span, cold.span);
+        assert!(imported.metadata().is_empty(), "uid flag is reserved");
+
+        let rows: Vec<(Uid, Timestamp, Vec<u8>)> = imported
+            .iter(Order::Asc, 0)
+            .map(|e| (e.uid, e.timestamp(), e.resolve().1.to_vec()))
+            .collect();
+        let expected: Vec<(Uid, Timestamp, Vec<u8>)> = (0..30u64)
+            .filter(|i| i % 3 == 1)
+            .map(|i| (1, 1000 + i, format!("blob{i}").into_bytes()))
+            .collect();
+        assert_eq!(rows, expected);
+
+        let mut buf = Vec::new();
+        assert_eq!(cold.export_uid(99, &mut buf, &ExportOptions::default())?, 0);
+        assert_eq!(Cold::import(Bytes::from(buf))?.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn export_filtered_drops_odd_uids() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..40u64 {
+            // Cids against timestamp order, so sorting is visible.
+            hot.add(
+                (i % 5) as Uid,
+                100 - i,
+                1000 + i / 2,
+                format!("blob{i}").as_bytes(),
+            )?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let options = ExportOptions {
+            extra_metadata: vec![("moderation".into(), "even".into())],
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        let written = cold.export_filtered(&mut buf, |uid, _, _| uid % 2 == 0, &options)?;
+        assert_eq!(written, 24);
+
+        let imported = Cold::import(Bytes::from(buf))?;
+        assert_eq!(imported.span, cold.span);
+        assert_eq!(
+            imported.metadata().get("moderation").map(String::as_str),
+            Some("even")
+        );
+
+        let expected: Vec<_> = rows(&cold).into_iter().filter(|r| r.0 % 2 == 0).collect();
+        assert_eq!(rows(&imported), expected);
+
+        let mut buf = Vec::new();
+        let early = cold.export_filtered(&mut buf, |_, cid, ts| ts < 1005 && cid > 95, &options)?;
+        assert_eq!(early, 5);
+        assert_eq!(
+            Cold::import(Bytes::from(buf))?.cids(),
+            [99, 100, 97, 98, 96]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn import_with_progress_reports_and_cancels() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 10_000))?;
+        for i in 0..2500u64 {
+            hot.add(1, i, 1000 + i, format!("post {i}").as_bytes())?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+        let mut buf = Vec::new();
+        cold.export(&mut buf)?;
+        let data = Bytes::from(buf);
+
+        let mut seen = Vec::new();
+        let imported = Cold::import_with_progress(data.clone(), |rows| {
+            seen.push(rows);
+            ControlFlow::Continue(())
+        })?;
+        assert_eq!(imported.fingerprint(), cold.fingerprint());
+        assert!(seen.len() > 1, "{seen:?}");
+        assert!(seen.is_sorted());
+        assert_eq!(seen.last(), Some(&2500));
+
+        let err = Cold::import_with_progress(data, |_| ControlFlow::Break(())).err();
+        let cancelled = err.as_ref().and_then(|e| e.downcast_ref::<Cancelled>());
+        assert_eq!(cancelled, Some(&Cancelled { rows: seen[0] }));
+        assert_eq!(
+            err.map(|e| e.to_string()),
+            Some(format!("import cancelled after {} rows", seen[0]))
+        );
+        Ok(())
+    }
+
+    /// Import writes blobs once, as a single-batch store of the same size as
+    /// the exported arena's, however many parquet batches they arrive in.
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn import_spills_blobs_once() -> Result<()> {
+        let mut hot = Hot::new(Window::new(0, 10_000))?;
+        for i in 0..3000u64 {
+            hot.add(1, i, i, &[i as u8; 64])?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+        let mut buf = Vec::new();
+        cold.export(&mut buf)?;
+
+        let mut batches = 0;
+        let imported = Cold::import_with_progress(buf.into(), |_| {
+            batches += 1;
+            ControlFlow::Continue(())
+        })?;
+
+        assert!(batches > 1);
+        assert_eq!(imported.usage().disk, cold.usage().disk);
+        assert_eq!(imported.fingerprint(), cold.fingerprint());
+        Ok(())
+    }
+
+    #[test]
+    fn import_spools_large_blob_columns() -> Result<()> {
+        let mut hot = Hot::new(Window::new(0, 10_000))?;
+        for i in 0..3000u64 {
+            hot.add(1, i, i, &[i as u8; 64])?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+        let mut buf = Vec::new();
+        cold.export(&mut buf)?;
+
+        let options = ImportOptions {
+            spill_blob_bytes: 1024,
+            ..Default::default()
+        };
+        let spooled = Cold::import_with(buf.into(), &options)?;
+
+        assert_eq!(spooled.len(), 3000);
+        assert_eq!(spooled.fingerprint(), cold.fingerprint());
+        assert_eq!(spooled.get(2999).map(|e| e.resolve().1.len()), Some(64));
+        Ok(())
+    }
+
+    #[test]
+    fn freeze_assume_sorted_matches_freeze() -> Result<()> {
+        let fill = |hot: &mut Hot| -> Result<()> {
+            for i in 0..1000u64 {
+                // Shared timestamps, ordered by cid within each.
+                hot.add(
+                    (i % 5) as Uid,
+                    5000 + i,
+                    1000 + i / 3,
+                    format!("{i}").as_bytes(),
+                )?;
+            }
+            Ok(())
+        };
+
+        let mut a = Hot::new(Window::new(1000, 1000))?;
+        let mut b = Hot::new(Window::new(1000, 1000))?;
+        fill(&mut a)?;
+        fill(&mut b)?;
+
+        let sorted = a.freeze_assume_sorted()?;
+        let frozen: Arc<Cold> = b.try_into()?;
+        assert_eq!(sorted.fingerprint(), frozen.fingerprint());
+        assert_eq!(
+            sorted.get(5500).map(|e| e.resolve().1.to_vec()),
+            Some(b"500".to_vec())
+        );
+
+        let empty = Hot::new(Window::new(0, 10))?.freeze_assume_sorted()?;
+        assert!(empty.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not in (timestamp, cid) order")]
+    fn freeze_assume_sorted_catches_unsorted_in_debug() {
+        let mut hot = Hot::new(Window::new(1000, 100)).unwrap();
+        hot.add(1, 2, 1010, b"b").unwrap();
+        hot.add(1, 1, 1010, b"a").unwrap();
+
+        let _ = hot.freeze_assume_sorted();
+    }
+
+    #[test]
+    fn import_rejects_out_of_window_and_unsorted_rows() -> Result<()> {
+        let parquet = |rows: &[(Timestamp, Cid)]| -> Result<Bytes> {
+            let batch = RecordBatch::try_new(
+                schema(),
+                vec![
+                    Arc::new(PrimitiveArray::<UidType>::from_iter_values(
+                        rows.iter().map(|_| 1),
+                    )),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.1))),
+                    Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.0))),
+                    Arc::new(BinaryArray::from_iter_values(rows.iter().map(|_| b"x"))),
+                ],
+            )?;
+            let props = WriterProperties::builder()
+                .set_key_value_metadata(Some(vec![
+                    KeyValue::new("crimeline.epoch".into(), "1000".to_string()),
+                    KeyValue::new("crimeline.duration".into(), "100".to_string()),
+                ]))
+                .build();
+            let mut buf = Vec::new();
+            let mut writer = ArrowWriter::try_new(&mut buf, schema(), Some(props))?;
+            writer.write(&batch)?;
+            writer.close()?;
+            Ok(Bytes::from(buf))
+        };
+        let import_err = |rows: &[(Timestamp, Cid)]| -> Result<String> {
+            Ok(Cold::import(parquet(rows)?).err().unwrap().to_string())
+        };
+
+        assert_eq!(
+            import_err(&[(1001, 1), (1100, 2)])?,
+            "row 1: timestamp 1100 outside [1000, 1100)"
+        );
+        assert_eq!(
+            import_err(&[(999, 1)])?,
+            "row 0: timestamp 999 outside [1000, 1100)"
+        );
+        assert_eq!(
+            import_err(&[(1001, 1), (1003, 2), (1002, 3)])?,
+            "row 2: not in strictly ascending (timestamp, cid) order"
+        );
+        assert_eq!(
+            import_err(&[(1001, 5), (1001, 4)])?,
+            "row 1: not in strictly ascending (timestamp, cid) order"
+        );
+        assert_eq!(
+            import_err(&[(1001, 5), (1001, 5)])?,
+            "row 1: not in strictly ascending (timestamp, cid) order"
+        );
+
+        assert_eq!(
+            Cold::import(parquet(&[(1001, 5), (1001, 6), (1099, 1)])?)?.len(),
+            3
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn import_null_blob_policies() -> Result<()> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("uid", UidType::DATA_TYPE, false),
+            Field::new("cid", DataType::UInt64, false),
+            Field::new("timestamp", DataType::UInt64, false),
+            Field::new("blob", DataType::Binary, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(PrimitiveArray::<UidType>::from_iter_values([1, 2, 3])),
+                Arc::new(UInt64Array::from_iter_values([10, 20, 30])),
+                Arc::new(UInt64Array::from_iter_values([1001, 1002, 1003])),
+                Arc::new(BinaryArray::from(vec![
+                    Some(b"a".as_slice()),
+                    None,
+                    Some(b"c"),
+                ])),
+            ],
+        )?;
+
+        let props = WriterProperties::builder()
+            .set_key_value_metadata(Some(vec![
+                KeyValue::new("crimeline.epoch".into(), "1000".to_string()),
+                KeyValue::new("crimeline.duration".into(), "100".to_string()),
+            ]))
+            .build();
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buf, schema, Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        let data = Bytes::from(buf);
+
+        let import = |on_null_blob| {
+            Cold::import_with(
+                data.clone(),
+                &ImportOptions {
+                    on_null_blob,
+                    ..Default::default()
+                },
+            )
+        };
+        let blobs = |cold: &Cold| -> Vec<(Cid, Vec<u8>)> {
+            cold.iter(Order::Asc, 0)
+                .map(|e| {
+                    let (cid, blob) = e.resolve();
+                    (cid, blob.to_vec())
+                })
+                .collect()
+        };
+
+        let empty = import(NullBlob::Empty)?;
+        assert_eq!(
+            blobs(&empty),
+            [(10, b"a".to_vec()), (20, vec![]), (30, b"c".to_vec())]
+        );
+
+        let skipped = import(NullBlob::Skip)?;
+        assert_eq!(blobs(&skipped), [(10, b"a".to_vec()), (30, b"c".to_vec())]);
+        assert_eq!(skipped.get(30).map(|e| e.uid), Some(3));
+
+        let err = import(NullBlob::Error).err().expect("null blob rejected");
+        assert!(err.to_string().contains("null blob for cid 20"), "{err:#}");
+        let default = Cold::import(data)?;
+        assert_eq!(blobs(&default), blobs(&empty), "empty is the default");
+        Ok(())
+    }
+
     #[test]
     fn export_import_empty() -> Result<()> {
         let hot = Hot::new(Window::new(5000, 200))?;
         let cold: Arc<Cold> = hot.try_into()?;
 
         let mut buf = Vec::new();
-        cold.export(&mut buf)?;
+        let written = cold.export(&mut buf)?;
+        assert_eq!(written, buf.len() as u64);
 
         let imported = Cold::import(Bytes::from(buf))?;
 
@@ -656,7 +3316,8 @@ mod tests {
         let cold: Arc<Cold> = hot.try_into()?;
 
         let mut buf = Vec::new();
-        cold.export(&mut buf)?;
+        let written = cold.export(&mut buf)?;
+        assert_eq!(written, buf.len() as u64);
 
         let imported = Cold::import(Bytes::from(buf))?;
 
@@ -682,10 +3343,15 @@ mod tests {
         let cold: Arc<Cold> = hot.try_into()?;
 
         let mut buf = Vec::new();
-        cold.export(&mut buf)?;
+        let written = cold.export(&mut buf)?;
+        assert_eq!(written, buf.len() as u64);
 
         let imported = Cold::import(Bytes::from(buf))?;
 
+        let mut archives = Usage::default();
+        archives.add_export_bytes(written);
+        assert_eq!(archives.disk, written);
+
         assert_eq!(imported.span, cold.span);
         assert_eq!(imported.span.end_exclusive(), cold.span.end_exclusive());
         assert_eq!(imported.len(), cold.len());
@@ -704,7 +3370,219 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn self_contained_reopens_verbatim() -> Result<()> {
+        let n = 20_000u64;
+        let mut hot = Hot::new(Window::new(1000, 10_000))?;
+        hot.add_bulk(
+            (0..n).map(|i| (i as Uid % 50, n - i, 1000 + i * 7 % 10_000, [i as u8; 200])),
+        )?;
+        let built: Arc<Cold> = hot.try_into()?;
+
+        let options = ExportOptions {
+            extra_metadata: vec![("ingest.run".into(), "run-7".into())],
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        built.export_with(&mut buf, &options)?;
+        let cold = Cold::import(Bytes::from(buf))?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("arena.crimeline");
+        let written = cold.export_self_contained(&path)?;
+        assert_eq!(written, std::fs::metadata(&path)?.len());
+
+        #[cfg(feature = "alloc-stats")]
+        let opened = {
+            let (opened, delta) = crate::usage::measure(|| Cold::open(&path));
+            // Only uids and timestamps are copied, blobs stay in the mapping.
+            let columns = n as usize * (size_of::<Uid>() + size_of::<u32>());
+            assert!(
+                delta.allocated < 2 * columns,
+                "open allocated {} bytes for {columns} bytes of columns",
+                delta.allocated
+            );
+            opened?
+        };
+        #[cfg(not(feature = "alloc-stats"))]
+        let opened = Cold::open(&path)?;
+
+        assert_eq!(opened.fingerprint(), cold.fingerprint());
+        assert_eq!(opened.metadata(), cold.metadata());
+        assert_eq!(opened.span, cold.span);
+        assert_eq!(
+            opened.get(n / 2).map(|e| e.resolve().1.to_vec()),
+            cold.get(n / 2).map(|e| e.resolve().1.to_vec())
+        );
+
+        let usage = opened.usage();
+        assert_eq!(usage.disk, 0, "the caller owns the file");
+        assert!(usage.mapped >= n * 200);
+        assert_eq!(usage.heap, cold.usage().heap);
+
+        std::fs::write(&path, b"not an arena")?;
+        assert!(Cold::open(&path).is_err());
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn open_rejects_corrupt_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("arena.crimeline");
+        let write = |cids: &[Cid], timestamps: &[u32]| -> Result<()> {
+            let metadata = HashMap::from([
+                ("crimeline.epoch".to_string(), "1000".to_string()),
+                ("crimeline.duration".to_string(), "100".to_string()),
+                (
+                    "crimeline.format".to_string(),
+                    SELF_CONTAINED_FORMAT.to_string(),
+                ),
+            ]);
+            let schema = Arc::new(SELF_CONTAINED_SCHEMA.clone().with_metadata(metadata));
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(UInt64Array::from_iter_values(cids.iter().copied())),
+                    Arc::new(BinaryArray::from_iter_values(cids.iter().map(|_| b"x"))),
+                    Arc::new(PrimitiveArray::<UidType>::from_iter_values(
+                        cids.iter().map(|_| 1),
+                    )),
+                    Arc::new(UInt32Array::from_iter_values(timestamps.iter().copied())),
+                ],
+            )?;
+            let mut ipc = FileWriter::try_new(File::create(&path)?, &schema)?;
+            ipc.write(&batch)?;
+            ipc.finish()?;
+            Ok(())
+        };
+        let open_err = || Cold::open(&path).err().map(|e| e.to_string());
+
+        write(&[1, 2, 3], &[10, 20, 30])?;
+        assert_eq!(Cold::open(&path)?.len(), 3);
+
+        write(&[1, 2, 3], &[10, 30, 20])?;
+        assert_eq!(
+            open_err(),
+            Some("row 2: not in (timestamp, cid) order".into())
+        );
+
+        write(&[1, 2, 3], &[10, 20, 100])?;
+        assert_eq!(
+            open_err(),
+            Some("row 2: timestamp 1100 outside [1000, 1100)".into())
+        );
+
+        // Cut the body out from under the footer's block offsets.
+        write(&[1, 2, 3], &[10, 20, 30])?;
+        let mut data = std::fs::read(&path)?;
+        data.drain(8..data.len() / 2);
+        std::fs::write(&path, data)?;
+        assert_eq!(open_err(), Some("arrow ipc block out of bounds".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn export_import_extra_metadata() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        hot.add(1, 100, 1010, b"a")?;
+
+        let cold: Arc<Cold> = hot.try_into()?;
+        assert!(cold.metadata().is_empty());
+
+        let options = ExportOptions {
+            extra_metadata: vec![("ingest.run".into(), "run-42".into())],
+            ..Default::default()
+        };
+
+        let mut buf = Vec::new();
+        let written = cold.export_with(&mut buf, &options)?;
+        assert_eq!(written, buf.len() as u64);
+
+        let imported = Cold::import(Bytes::from(buf))?;
+        assert_eq!(
+            imported.metadata().len(),
+            1,
+            "reserved keys are not exposed"
+        );
+        assert_eq!(
+            imported.metadata().get("ingest.run").map(String::as_str),
+            Some("run-42"),
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn export_rejects_reserved_metadata() -> Result<()> {
+        let hot = Hot::new(Window::new(1000, 100))?;
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let options = ExportOptions {
+            extra_metadata: vec![("crimeline.epoch".into(), "0".into())],
+            ..Default::default()
+        };
+
+        assert!(cold.export_with(Vec::new(), &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn export_options_validate() -> Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..10 {
+            hot.add(1, i, 1000 + i, b"blob")?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let zero_batch = ExportOptions {
+            batch_rows: 0,
+            ..Default::default()
+        };
+        let err = zero_batch.validate().unwrap_err();
+        assert!(err.to_string().contains("batch_rows"), "{err}");
+
+        let bad_level = ExportOptions {
+            zstd_level: 23,
+            ..Default::default()
+        };
+        let err = bad_level.validate().unwrap_err();
+        assert!(err.to_string().contains("zstd level 23"), "{err}");
+
+        let repeated = ExportOptions {
+            extra_metadata: vec![
+                ("ingest.run".into(), "run-1".into()),
+                ("ingest.run".into(), "run-2".into()),
+            ],
+            ..Default::default()
+        };
+        let err = repeated.validate().unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{err}");
+        assert!(cold.export_with(Vec::new(), &repeated).is_err());
+
+        let mut buf = Vec::new();
+        assert!(cold.export_with(&mut buf, &bad_level).is_err());
+        assert!(buf.is_empty(), "nothing written before failing");
+
+        let sane = ExportOptions {
+            batch_rows: 3,
+            extra_metadata: vec![("ingest.run".into(), "run-1".into())],
+            zstd_level: 19,
+        };
+        sane.validate()?;
+        cold.export_with(&mut buf, &sane)?;
+        assert_eq!(
+            Cold::import(Bytes::from(buf))?.fingerprint(),
+            cold.fingerprint()
+        );
+        Ok(())
+    }
+
     #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
     fn hot_usage_traits() -> Result<()> {
         let mut hot = Hot::new(Window::new(1000, 100))?;
         hot.add(1, 100, 1010, b"test")?;
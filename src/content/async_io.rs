@@ -0,0 +1,278 @@
+//! Async wrappers behind the `tokio` feature. Sorting, parquet encoding and
+//! decoding run on the blocking pool so they never stall the runtime.
+
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result, ensure};
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc, watch};
+use tokio::task::{JoinSet, spawn_blocking};
+use tracing::trace;
+
+use super::Timeline;
+use super::arena::{Cold, Hot};
+
+/// Size of the chunks an export hands from the blocking pool to the writer.
+const EXPORT_CHUNK_BYTES: usize = 64 << 10;
+
+/// Chunks an export may encode ahead of the writer.
+const EXPORT_CHUNKS_IN_FLIGHT: usize = 4;
+
+impl Hot {
+    /// Like `try_into`, but sorts and rewrites blobs on the blocking pool.
+    pub async fn freeze_async(self) -> Result<Arc<Cold>> {
+        spawn_blocking(move || self.try_into())
+            .await
+            .context("join freeze task")?
+    }
+}
+
+impl Cold {
+    /// Like [`export`](Cold::export), but encodes on the blocking pool and
+    /// streams to `writer` asynchronously, holding at most a few chunks in
+    /// memory. Returns the number of bytes written.
+    pub async fn export_async<W: AsyncWrite + Unpin>(
+        self: &Arc<Self>,
+        mut writer: W,
+    ) -> Result<u64> {
+        let (tx, mut rx) = mpsc::channel(EXPORT_CHUNKS_IN_FLIGHT);
+
+        let cold = Arc::clone(self);
+        let encode = spawn_blocking(move || -> Result<()> {
+            let mut out = BufWriter::with_capacity(EXPORT_CHUNK_BYTES, ChannelWriter(tx));
+            cold.export(&mut out)?;
+            out.flush().context("flush export")
+        });
+
+        let mut written = 0;
+        while let Some(chunk) = rx.recv().await {
+            // Dropping `rx` on error fails the encoder's next send.
+            writer.write_all(&chunk).await.context("write export")?;
+            written += chunk.len() as u64;
+        }
+
+        encode.await.context("join export task")??;
+        writer.flush().await.context("flush export")?;
+
+        Ok(written)
+    }
+
+    /// Like [`import`](Cold::import), but decodes on the blocking pool.
+    pub async fn import_async(data: Bytes) -> Result<Arc<Self>> {
+        spawn_blocking(move || Cold::import(data))
+            .await
+            .context("join import task")?
+    }
+}
+
+impl Timeline {
+    /// Writes every arena to `dir` as `{epoch}.parquet`, at most
+    /// `concurrency` at a time. Returns the number of arenas written.
+    pub async fn save_to_dir_async(
+        &self,
+        dir: impl AsRef<Path>,
+        concurrency: usize,
+    ) -> Result<usize> {
+        ensure!(concurrency > 0, "concurrency must be positive");
+
+        let dir = dir.as_ref();
+        tokio::fs::create_dir_all(dir)
+            .await
+            .with_context(|| format!("create {}", dir.display()))?;
+
+        let snapshot = self.snapshot();
+        let mut tasks = JoinSet::new();
+
+        for arena in snapshot.iter() {
+            if tasks.len() >= concurrency {
+                join_one(&mut tasks).await?;
+            }
+
+            let arena = Arc::clone(arena);
+            let path = dir.join(format!("{}.parquet", arena.span.epoch));
+
+            tasks.spawn(async move {
+                let file = tokio::fs::File::create(&path)
+                    .await
+                    .with_context(|| format!("create {}", path.display()))?;
+                arena.export_async(file).await?;
+                Ok(())
+            });
+        }
+
+        while !tasks.is_empty() {
+            join_one(&mut tasks).await?;
+        }
+
+        trace!(len = snapshot.len(), dir = %dir.display(), "saved timeline");
+
+        Ok(snapshot.len())
+    }
+
+    /// Loads every `*.parquet` file in `dir` as an arena, importing at most
+    /// `concurrency` at a time.
+    pub async fn load_from_dir_async(
+        dir: impl AsRef<Path>,
+        concurrency: usize,
+    ) -> Result<Timeline> {
+        ensure!(concurrency > 0, "concurrency must be positive");
+
+        let dir = dir.as_ref();
+        let mut entries = tokio::fs::read_dir(dir)
+            .await
+            .with_context(|| format!("read {}", dir.display()))?;
+
+        let mut paths: Vec<PathBuf> = Vec::new();
+        while let Some(entry) = entries.next_entry().await.context("read dir entry")? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "parquet") {
+                paths.push(path);
+            }
+        }
+
+        let mut tasks = JoinSet::new();
+        let mut arenas = Vec::with_capacity(paths.len());
+
+        for path in paths {
+            if tasks.len() >= concurrency {
+                arenas.push(join_one(&mut tasks).await?);
+            }
+
+            tasks.spawn(async move {
+                let data = tokio::fs::read(&path)
+                    .await
+                    .with_context(|| format!("read {}", path.display()))?;
+                Cold::import_async(Bytes::from(data))
+                    .await
+                    .with_context(|| format!("import {}", path.display()))
+            });
+        }
+
+        while !tasks.is_empty() {
+            arenas.push(join_one(&mut tasks).await?);
+        }
+
+        trace!(len = arenas.len(), dir = %dir.display(), "loaded timeline");
+
+        Ok(Timeline::new(arenas))
+    }
+
+    /// Returns a receiver whose value is bumped each time an arena is added or
+    /// removed; await `changed()` and then read the timeline.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.changes.subscribe()
+    }
+}
+
+/// Sends each write to the async side of [`Cold::export_async`], blocking
+/// while the channel is full.
+struct ChannelWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export writer gone"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn join_one<T: 'static>(tasks: &mut JoinSet<Result<T>>) -> Result<T> {
+    tasks
+        .join_next()
+        .await
+        .context("no task to join")?
+        .context("join task")?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::arena::Hot;
+    use crate::content::test_util::make_arena;
+    use crate::content::{Order, Window};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn dir_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let timeline = Timeline::new((0..5).map(|i| make_arena(1000 + i * 100, 100, 3)).collect());
+
+        assert_eq!(timeline.save_to_dir_async(dir.path(), 2).await?, 5);
+        tokio::fs::write(dir.path().join("notes.txt"), b"ignored").await?;
+
+        let loaded = Timeline::load_from_dir_async(dir.path(), 2).await?;
+        assert_eq!(loaded.len(), 5);
+
+        let fingerprints =
+            |t: &Timeline| -> Vec<u64> { t.snapshot().iter().map(|a| a.fingerprint()).collect() };
+        assert_eq!(fingerprints(&loaded), fingerprints(&timeline));
+
+        let mut slice = loaded.iter_all(Order::Asc);
+        assert_eq!(slice.next().map(|e| e.timestamp()), Some(1000));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn export_import_async() -> Result<()> {
+        let arena = make_arena(1000, 100, 10);
+
+        let mut buf = Vec::new();
+        let written = arena.export_async(&mut buf).await?;
+        assert_eq!(written, buf.len() as u64);
+
+        let imported = Cold::import_async(Bytes::from(buf)).await?;
+        assert_eq!(imported.fingerprint(), arena.fingerprint());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn freeze_and_export_large_arena_async() -> Result<()> {
+        let mut hot = Hot::new(Window::new(0, 100_000))?;
+        for i in 0..20_000u64 {
+            // Incompressible, so the export spans several chunks.
+            let blob: Vec<u8> = (0..4u64)
+                .flat_map(|k| {
+                    (i.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ k)
+                        .wrapping_mul(0xbf58_476d_1ce4_e5b9)
+                        .to_le_bytes()
+                })
+                .collect();
+            hot.add(1, 20_000 - i, i * 7 % 100_000, &blob)?;
+        }
+        let frozen = hot.freeze_async().await?;
+        assert_eq!(frozen.len(), 20_000);
+
+        let mut buf = Vec::new();
+        let written = frozen.export_async(&mut buf).await?;
+        assert!(written > (EXPORT_CHUNKS_IN_FLIGHT * EXPORT_CHUNK_BYTES) as u64);
+        assert_eq!(written, buf.len() as u64);
+
+        let imported = Cold::import_async(Bytes::from(buf)).await?;
+        assert_eq!(imported.fingerprint(), frozen.fingerprint());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subscribe_sees_changes() -> Result<()> {
+        let timeline = Timeline::new(vec![]);
+        let mut rx = timeline.subscribe();
+
+        timeline.add(make_arena(1000, 100, 1));
+        rx.changed().await?;
+        assert_eq!(*rx.borrow_and_update(), 1);
+
+        timeline.remove(1000);
+        assert!(timeline.add_checked(make_arena(1000, 100, 1)).is_ok());
+        rx.changed().await?;
+        assert_eq!(*rx.borrow_and_update(), 3);
+        assert!(!rx.has_changed()?);
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+//! Where blob bytes live. Natively they spill to a tempfile that is then
+//! memory-mapped; on wasm32 there is no filesystem to spill to and they stay
+//! in a heap buffer.
+
+#[cfg(not(target_arch = "wasm32"))]
+mod imp {
+    use std::fs::File;
+    use std::io::{BufWriter, Write};
+
+    use anyhow::{Context, Result};
+    use arrow_buffer::Buffer;
+    use bytes::Bytes;
+    use memmap2::Mmap;
+    use tempfile::NamedTempFile;
+
+    use crate::usage::Usage;
+
+    /// Buffered writer to a fresh tempfile.
+    pub(crate) struct Spill {
+        file: NamedTempFile,
+        writer: Box<dyn Write + Send>,
+    }
+
+    /// Keeps a mapped buffer's file alive, if we own it.
+    pub(crate) enum BlobBacking {
+        /// A caller's file, mapped in place.
+        Mapped,
+        Temp(NamedTempFile),
+    }
+
+    impl Spill {
+        pub(crate) fn new(prefix: &str) -> Result<Self> {
+            Self::wrapped(prefix, |writer| Box::new(writer))
+        }
+
+        /// Like [`new`](Self::new), but every write goes through `wrap`,
+        /// e.g. a test's writer that fails on demand.
+        pub(crate) fn wrapped(
+            prefix: &str,
+            wrap: impl FnOnce(BufWriter<File>) -> Box<dyn Write + Send>,
+        ) -> Result<Self> {
+            let file = NamedTempFile::with_prefix(prefix)
+                .with_context(|| format!("create tempfile for {prefix}"))?;
+            let handle = file
+                .as_file()
+                .try_clone()
+                .context("clone tempfile handle")?;
+
+            Ok(Self {
+                file,
+                writer: wrap(BufWriter::new(handle)),
+            })
+        }
+
+        /// Flushes and maps everything written so far.
+        pub(crate) fn finish(mut self) -> Result<(Buffer, BlobBacking)> {
+            self.writer.flush().context("flush buffered writer")?;
+            drop(self.writer);
+            let buffer = map(self.file.as_file())?;
+
+            Ok((buffer, BlobBacking::Temp(self.file)))
+        }
+
+        pub(crate) fn add_usage(&self, u: &mut Usage) {
+            u.add_disk_usage(file_len(self.file.as_file()));
+        }
+    }
+
+    impl Write for Spill {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.writer.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.writer.flush()
+        }
+    }
+
+    impl BlobBacking {
+        /// Maps a caller's file. It must not change while the buffer lives.
+        pub(crate) fn map_file(file: &File) -> Result<(Buffer, BlobBacking)> {
+            Ok((map(file)?, BlobBacking::Mapped))
+        }
+
+        /// A caller's file is theirs to account for, only our tempfile
+        /// counts as disk.
+        pub(crate) fn add_usage(&self, u: &mut Usage, buffer: &Buffer) {
+            if let BlobBacking::Temp(file) = self {
+                u.add_disk_usage(file_len(file.as_file()));
+            }
+            u.add_mapped_usage(buffer.len() as u64);
+        }
+    }
+
+    fn map(file: &File) -> Result<Buffer> {
+        let mmap = unsafe { Mmap::map(file).context("mmap blob file")? };
+
+        Ok(Buffer::from(Bytes::from_owner(mmap)))
+    }
+
+    fn file_len(file: &File) -> u64 {
+        file.metadata().map(|m| m.len()).unwrap_or(0)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod imp {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use arrow_buffer::Buffer;
+
+    use crate::usage::Usage;
+
+    /// Growable heap buffer.
+    pub(crate) struct Spill(Vec<u8>);
+
+    /// Nothing to keep alive, the buffer owns its bytes.
+    pub(crate) struct BlobBacking;
+
+    impl Spill {
+        pub(crate) fn new(_prefix: &str) -> Result<Self> {
+            Ok(Self(Vec::new()))
+        }
+
+        pub(crate) fn finish(self) -> Result<(Buffer, BlobBacking)> {
+            Ok((Buffer::from_vec(self.0), BlobBacking))
+        }
+
+        pub(crate) fn add_usage(&self, u: &mut Usage) {
+            u.add_vec(&self.0);
+        }
+    }
+
+    impl Write for Spill {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl BlobBacking {
+        pub(crate) fn add_usage(&self, u: &mut Usage, buffer: &Buffer) {
+            u.add_collection(buffer.len(), buffer.capacity(), 1);
+        }
+    }
+}
+
+pub(crate) use imp::{BlobBacking, Spill};
@@ -1,22 +1,26 @@
-use std::io::{BufWriter, Cursor};
-use std::sync::{Arc, LazyLock};
+use std::sync::{Arc, LazyLock, OnceLock};
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, ensure};
 use arrow::{
-    array::{BinaryArray, RecordBatch, UInt64Array},
+    array::{ArrayRef, BinaryArray, BinaryBuilder, BooleanArray, RecordBatch, UInt64Array},
+    compute::filter_record_batch,
     datatypes::{DataType, Field, Schema},
-    ipc::{reader::FileReader, writer::FileWriter},
+    ipc::{
+        Block,
+        convert::fb_to_schema,
+        reader::{FileDecoder, read_footer_length},
+        root_as_footer,
+        writer::FileWriter,
+    },
 };
 use arrow_buffer::Buffer;
 use bytes::Bytes;
-use memmap2::Mmap;
-use tempfile::NamedTempFile;
-
-use tracing::trace;
+use tracing::{debug_span, field, trace};
 
 use crate::usage::{ReportUsage, Usage};
 
 use super::Cid;
+use super::backing::{BlobBacking, Spill};
 
 static ARROW_SCHEMA: LazyLock<Arc<Schema>> = LazyLock::new(|| {
     Arc::new(Schema::new(vec![
@@ -30,24 +34,33 @@ fn schema() -> Arc<Schema> {
 }
 
 pub struct BlobStoreBuilder {
-    writer: FileWriter<BufWriter<NamedTempFile>>,
+    writer: FileWriter<Spill>,
+}
+
+/// Rows already in their final order, collected in memory and written once
+/// as a single-batch store. Unlike [`BlobStoreBuilder`] nothing is spooled
+/// before [`build`](Self::build), so the only tempfile is the store's own.
+pub struct PresortedBuilder {
+    blobs: BinaryBuilder,
+    cids: Vec<Cid>,
 }
 
 pub struct BlobStore {
-    _buffer: Buffer,
-    _file: NamedTempFile,
+    backing: BlobBacking,
     blobs: BinaryArray,
+    buffer: Buffer,
+    /// Row indices sorted by cid, built on first cid lookup.
+    by_cid: OnceLock<Box<[usize]>>,
     cids: UInt64Array,
 }
 
 impl BlobStoreBuilder {
     pub fn new() -> Result<Self> {
-        let file = NamedTempFile::with_prefix("crimeline-hot-arena")
-            .context("create tempfile for blob arena")?;
-
-        let file = BufWriter::new(file);
+        Self::with_spill(Spill::new("crimeline-hot-arena")?)
+    }
 
-        let writer = FileWriter::try_new(file, &schema()).context("create arrow ipc writer")?;
+    pub(super) fn with_spill(spill: Spill) -> Result<Self> {
+        let writer = FileWriter::try_new(spill, &schema()).context("create arrow ipc writer")?;
 
         Ok(Self { writer })
     }
@@ -62,36 +75,79 @@ impl BlobStoreBuilder {
         )
         .context("create blob batch")?;
 
-        self.writer.write(&batch).context("write blob batch")?;
+        self.write(&batch)?;
 
         trace!(len = cids.len(), "appended to blob store");
 
         Ok(())
     }
 
-    pub fn build_and_sort(self, perm: &[usize]) -> Result<BlobStore> {
-        let buffered = self.writer.into_inner().context("finish arrow writer")?;
+    /// Appends the rows of `other` whose entry in `keep` is true, batch by
+    /// batch as `other` wrote them. On error, returns how many of `other`'s
+    /// rows were consumed by the batches written before the failure.
+    pub fn absorb(
+        &mut self,
+        other: BlobStoreBuilder,
+        keep: &[bool],
+    ) -> Result<(), (usize, anyhow::Error)> {
+        let spill = other
+            .writer
+            .into_inner()
+            .context("finish arrow writer")
+            .map_err(|e| (0, e))?;
+        let (buffer, _backing) = spill.finish().map_err(|e| (0, e))?;
 
-        let old_file = buffered.into_inner().context("flush buffered writer")?;
+        let batches = decode_batches(&buffer).map_err(|e| (0, e))?;
+
+        let mut consumed = 0;
+
+        for batch in &batches {
+            let rows = self.absorb_batch(batch, &keep[consumed.min(keep.len())..]);
+
+            consumed += rows.map_err(|e| (consumed, e))?;
+        }
+
+        trace!(len = consumed, "absorbed blob store");
+
+        Ok(())
+    }
+
+    /// Writes the rows of `batch` that `keep` (starting at its first row)
+    /// selects, returning the batch's full length.
+    fn absorb_batch(&mut self, batch: &RecordBatch, keep: &[bool]) -> Result<usize> {
+        let mask = keep
+            .get(..batch.num_rows())
+            .context("keep mask shorter than blob rows")?;
+
+        if mask.iter().all(|&k| k) {
+            self.write(batch)?;
+        } else if mask.iter().any(|&k| k) {
+            let mask = BooleanArray::from(mask.to_vec());
+            self.write(&filter_record_batch(batch, &mask).context("filter blob batch")?)?;
+        }
+
+        Ok(batch.num_rows())
+    }
+
+    fn write(&mut self, batch: &RecordBatch) -> Result<()> {
+        self.writer.write(batch).context("write blob batch")
+    }
+
+    pub fn build_and_sort(self, perm: &[usize]) -> Result<BlobStore> {
+        let op = debug_span!("build_blobs", len = perm.len(), blob_bytes = field::Empty).entered();
+        let spill = self.writer.into_inner().context("finish arrow writer")?;
 
         if perm.is_empty() {
             let empty: Vec<&[u8]> = vec![];
             return BlobStore::new(&[], &empty);
         }
 
-        let mmap = unsafe { Mmap::map(old_file.as_file()).context("mmap blob file")? };
-
-        let buffer = Buffer::from(Bytes::from_owner(mmap));
+        let (buffer, _backing) = spill.finish()?;
+        let batches = decode_batches(&buffer)?;
 
-        let reader = FileReader::try_new(Cursor::new(buffer.as_slice()), None)
-            .context("open arrow reader")?;
-
-        let mut batches = Vec::new();
         let mut offsets = vec![0usize];
 
-        for batch_result in reader {
-            let batch = batch_result.context("read blob batch")?;
-
+        for batch in &batches {
             offsets.push(
                 offsets
                     .last()
@@ -99,25 +155,9 @@ impl BlobStoreBuilder {
                     .context("offset vec unexpectedly empty")?
                     + batch.num_rows(),
             );
-
-            batches.push(batch);
         }
 
-        let typed: Vec<(&UInt64Array, &BinaryArray)> = batches
-            .iter()
-            .map(|b| -> Result<_> {
-                Ok((
-                    b.column(0)
-                        .as_any()
-                        .downcast_ref::<UInt64Array>()
-                        .context("downcast cid column")?,
-                    b.column(1)
-                        .as_any()
-                        .downcast_ref::<BinaryArray>()
-                        .context("downcast blob column")?,
-                ))
-            })
-            .collect::<Result<_>>()?;
+        let typed = typed_columns(&batches)?;
 
         // collect sorted rows via perm — blob refs point into the mmap
         let n = perm.len();
@@ -135,105 +175,102 @@ impl BlobStoreBuilder {
 
         trace!(len = cids.len(), "built and sorted blob store");
 
-        BlobStore::new(&cids, &blobs)
+        let store = BlobStore::new(&cids, &blobs)?;
+        op.record("blob_bytes", store.blob_bytes());
+
+        Ok(store)
     }
 
     pub fn build_presorted(self) -> Result<BlobStore> {
-        let buffered = self.writer.into_inner().context("finish arrow writer")?;
-
-        let old_file = buffered.into_inner().context("flush buffered writer")?;
+        let spill = self.writer.into_inner().context("finish arrow writer")?;
 
-        let mmap = unsafe { Mmap::map(old_file.as_file()).context("mmap blob file")? };
-
-        let buffer = Buffer::from(Bytes::from_owner(mmap));
-
-        let reader = FileReader::try_new(Cursor::new(buffer.as_slice()), None)
-            .context("open arrow reader")?;
-
-        let mut batches = Vec::new();
-
-        for batch_result in reader {
-            batches.push(batch_result.context("read blob batch")?);
-        }
+        let (buffer, backing) = spill.finish()?;
+        let batches = decode_batches(&buffer)?;
 
         // break if already single batch
-        if batches.len() == 1 {
-            drop(batches);
-            drop(buffer);
-            return BlobStore::from_file(old_file);
+        match batches.len() {
+            0 => return BlobStore::new::<&[u8]>(&[], &[]),
+            1 => {
+                drop(batches);
+                return BlobStore::from_buffer(buffer, backing).map(|(store, _)| store);
+            }
+            _ => {}
         }
 
-        let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        let typed = typed_columns(&batches)?;
 
-        let mut blobs: Vec<&[u8]> = Vec::with_capacity(total_rows);
-        let mut cids: Vec<Cid> = Vec::with_capacity(total_rows);
+        // One copy out of the mapped spill, which is dropped once the store
+        // has its own file.
+        let rows = typed.iter().map(|(cids, _)| cids.len()).sum();
+        let bytes = typed.iter().map(|(_, blobs)| blobs.values().len()).sum();
+        let mut builder = PresortedBuilder::with_capacity(rows, bytes);
 
-        for batch in &batches {
-            let cid_col = batch
-                .column(0)
-                .as_any()
-                .downcast_ref::<UInt64Array>()
-                .context("downcast cid column")?;
-
-            let blob_col = batch
-                .column(1)
-                .as_any()
-                .downcast_ref::<BinaryArray>()
-                .context("downcast blob column")?;
-
-            for i in 0..batch.num_rows() {
-                blobs.push(blob_col.value(i));
-                cids.push(cid_col.value(i));
+        for (cids, blobs) in typed {
+            for i in 0..cids.len() {
+                builder.push(cids.value(i), blobs.value(i));
             }
         }
 
-        trace!(len = total_rows, "built and presorted blob store");
+        builder.build()
+    }
+}
+
+impl PresortedBuilder {
+    /// `blob_bytes` is a capacity hint for the blob data.
+    pub fn with_capacity(rows: usize, blob_bytes: usize) -> Self {
+        Self {
+            blobs: BinaryBuilder::with_capacity(rows, blob_bytes),
+            cids: Vec::with_capacity(rows),
+        }
+    }
+
+    pub fn push(&mut self, cid: Cid, blob: &[u8]) {
+        self.blobs.append_value(blob);
+        self.cids.push(cid);
+    }
+
+    pub fn build(mut self) -> Result<BlobStore> {
+        trace!(len = self.cids.len(), "built presorted blob store");
 
-        BlobStore::new(&cids, &blobs)
+        BlobStore::from_columns(UInt64Array::from(self.cids), self.blobs.finish())
     }
 }
 
 impl BlobStore {
     /// Create a blob store from parallel cid/blob slices.
     pub fn new<T: AsRef<[u8]>>(cids: &[Cid], blobs: &[T]) -> Result<Self> {
-        let file =
-            NamedTempFile::with_prefix("crimeline-cold-arena").context("create blob tempfile")?;
+        Self::from_columns(
+            UInt64Array::from_iter_values(cids.iter().copied()),
+            BinaryArray::from_iter_values(blobs),
+        )
+    }
 
-        {
-            let batch = RecordBatch::try_new(
-                schema(),
-                vec![
-                    Arc::new(UInt64Array::from_iter_values(cids.iter().copied())),
-                    Arc::new(BinaryArray::from_iter_values(blobs)),
-                ],
-            )
-            .context("create blob batch")?;
+    /// Writes the columns as one batch to a fresh spill and maps it back.
+    fn from_columns(cids: UInt64Array, blobs: BinaryArray) -> Result<Self> {
+        let mut spill = Spill::new("crimeline-cold-arena")?;
 
-            let writer = BufWriter::new(file.as_file());
+        {
+            let batch = RecordBatch::try_new(schema(), vec![Arc::new(cids), Arc::new(blobs)])
+                .context("create blob batch")?;
 
             let mut writer =
-                FileWriter::try_new(writer, &schema()).context("create arrow writer")?;
+                FileWriter::try_new(&mut spill, &schema()).context("create arrow writer")?;
 
             writer.write(&batch).context("write blob batch")?;
 
             writer.finish().context("finish arrow writer")?;
         }
 
-        Self::from_file(file)
-    }
-
-    fn from_file(file: NamedTempFile) -> Result<Self> {
-        let mmap = unsafe { Mmap::map(file.as_file()).context("mmap blob file")? };
+        let (buffer, backing) = spill.finish()?;
 
-        let buffer = Buffer::from(Bytes::from_owner(mmap));
-
-        let mut reader = FileReader::try_new(Cursor::new(buffer.as_slice()), None)
-            .context("open arrow reader")?;
+        Self::from_buffer(buffer, backing).map(|(store, _)| store)
+    }
 
-        let batch = reader
-            .next()
-            .context("empty blob file")?
-            .context("read blob batch")?;
+    /// Reads a single-batch arrow ipc file whose first two columns are cid and
+    /// blob, decoding in place. Returns the batch too, so callers can keep
+    /// further columns stored next to the blobs.
+    pub(super) fn from_buffer(buffer: Buffer, backing: BlobBacking) -> Result<(Self, RecordBatch)> {
+        let batch = decode_first_batch(&buffer)?;
 
         let cids = batch
             .column(0)
@@ -249,18 +286,102 @@ impl BlobStore {
             .context("downcast blob column")?
             .clone();
 
-        Ok(Self {
-            _file: file,
-            _buffer: buffer,
-            cids,
+        let store = Self {
+            backing,
             blobs,
+            buffer,
+            by_cid: OnceLock::new(),
+            cids,
+        };
+
+        Ok((store, batch))
+    }
+
+    /// The cid and blob columns as stored, for persisting them verbatim.
+    pub(super) fn columns(&self) -> (ArrayRef, ArrayRef) {
+        (Arc::new(self.cids.clone()), Arc::new(self.blobs.clone()))
+    }
+
+    /// Gathers rows from `stores` into a new store in one pass. `order` lists
+    /// `(store_idx, row_idx)` pairs in the order the rows should appear.
+    pub fn concat(stores: &[&BlobStore], order: &[(usize, usize)]) -> Result<Self> {
+        let mut blobs: Vec<&[u8]> = Vec::with_capacity(order.len());
+        let mut cids: Vec<Cid> = Vec::with_capacity(order.len());
+
+        for &(store_idx, row) in order {
+            let store = stores
+                .get(store_idx)
+                .with_context(|| format!("no blob store at index {store_idx}"))?;
+            ensure!(
+                row < store.len(),
+                "row {row} out of bounds for blob store {store_idx} of {} rows",
+                store.len()
+            );
+
+            let (cid, blob) = store.resolve(row);
+            cids.push(cid);
+            blobs.push(blob);
+        }
+
+        trace!(
+            len = cids.len(),
+            stores = stores.len(),
+            "concatenated blob stores"
+        );
+
+        BlobStore::new(&cids, &blobs)
+    }
+
+    /// Row indices in ascending `(cid, row)` order, built on first use.
+    pub(super) fn by_cid(&self) -> &[usize] {
+        self.by_cid.get_or_init(|| {
+            let cids = self.cids.values();
+            let mut order: Vec<usize> = (0..cids.len()).collect();
+            order.sort_unstable_by_key(|&idx| (cids[idx], idx));
+
+            trace!(len = order.len(), "built cid index");
+
+            order.into_boxed_slice()
         })
     }
 
+    /// Yields all entries sorted by cid.
+    pub fn iter_by_cid(&self) -> impl Iterator<Item = (Cid, &[u8])> {
+        self.by_cid().iter().map(|&idx| self.resolve(idx))
+    }
+
+    /// Finds the index of `cid` via the cid index, building it on first use.
+    pub fn position(&self, cid: Cid) -> Option<usize> {
+        let cids = self.cids.values();
+        let order = self.by_cid();
+
+        order
+            .binary_search_by_key(&cid, |&idx| cids[idx])
+            .ok()
+            .map(|pos| order[pos])
+    }
+
+    pub fn cids(&self) -> &[Cid] {
+        self.cids.values()
+    }
+
     pub fn resolve(&self, idx: usize) -> (Cid, &[u8]) {
         (self.cids.value(idx), self.blobs.value(idx))
     }
 
+    /// Like [`resolve`](Self::resolve), but returns a refcounted view into
+    /// the store's buffer instead of a borrow. Nothing is copied, and the
+    /// buffer, including any file mapping, stays alive while it is held.
+    pub fn resolve_bytes(&self, idx: usize) -> (Cid, Bytes) {
+        let offsets = self.blobs.value_offsets();
+        let start = offsets[idx] as usize;
+        let len = offsets[idx + 1] as usize - start;
+
+        let blob = self.blobs.values().slice_with_length(start, len);
+
+        (self.cids.value(idx), Bytes::from_owner(Pinned(blob)))
+    }
+
     #[cfg_attr(coverage_nightly, coverage(off))]
     pub fn is_empty(&self) -> bool {
         self.cids.is_empty()
@@ -269,20 +390,112 @@ impl BlobStore {
     pub fn len(&self) -> usize {
         self.cids.len()
     }
+
+    /// Sum of all blob lengths, from the offset buffer.
+    pub fn blob_bytes(&self) -> u64 {
+        let offsets = self.blobs.value_offsets();
+
+        (offsets[offsets.len() - 1] - offsets[0]) as u64
+    }
+}
+
+/// Lets a [`Buffer`] own the memory behind a [`Bytes`].
+struct Pinned(Buffer);
+
+impl AsRef<[u8]> for Pinned {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+/// The cid and blob columns of each batch.
+fn typed_columns(batches: &[RecordBatch]) -> Result<Vec<(&UInt64Array, &BinaryArray)>> {
+    batches
+        .iter()
+        .map(|b| -> Result<_> {
+            Ok((
+                b.column(0)
+                    .as_any()
+                    .downcast_ref::<UInt64Array>()
+                    .context("downcast cid column")?,
+                b.column(1)
+                    .as_any()
+                    .downcast_ref::<BinaryArray>()
+                    .context("downcast blob column")?,
+            ))
+        })
+        .collect()
+}
+
+/// Decodes every batch of an arrow ipc file in place; their bodies stay in
+/// `buffer`.
+fn decode_batches(buffer: &Buffer) -> Result<Vec<RecordBatch>> {
+    let (decoder, blocks) = open_ipc(buffer)?;
+
+    blocks
+        .iter()
+        .map(|block| read_block(&decoder, buffer, block))
+        .collect()
+}
+
+/// Decodes the first record batch of an arrow ipc file without copying, so
+/// its arrays point into `buffer`.
+fn decode_first_batch(buffer: &Buffer) -> Result<RecordBatch> {
+    let (decoder, blocks) = open_ipc(buffer)?;
+    let block = blocks.first().context("empty blob file")?;
+
+    read_block(&decoder, buffer, block)
+}
+
+/// Parses the footer of an arrow ipc file: a decoder for its schema and the
+/// location of each record batch.
+fn open_ipc(buffer: &Buffer) -> Result<(FileDecoder, Vec<Block>)> {
+    let trailer = buffer
+        .len()
+        .checked_sub(10)
+        .context("arrow ipc file too short")?;
+    let footer_len = read_footer_length(buffer[trailer..].try_into()?)?;
+    let footer = trailer
+        .checked_sub(footer_len)
+        .context("arrow ipc footer out of bounds")?;
+
+    let footer = root_as_footer(&buffer[footer..trailer])
+        .map_err(|e| anyhow!("parse arrow ipc footer: {e}"))?;
+    let schema = footer.schema().context("arrow ipc file without schema")?;
+
+    let decoder = FileDecoder::new(Arc::new(fb_to_schema(schema)), footer.version());
+    let blocks = footer
+        .recordBatches()
+        .map(|blocks| blocks.iter().copied().collect())
+        .unwrap_or_default();
+
+    Ok((decoder, blocks))
+}
+
+fn read_block(decoder: &FileDecoder, buffer: &Buffer, block: &Block) -> Result<RecordBatch> {
+    let offset = usize::try_from(block.offset()).context("negative arrow ipc block offset")?;
+    let len = usize::try_from(block.bodyLength())
+        .ok()
+        .and_then(|body| body.checked_add(usize::try_from(block.metaDataLength()).ok()?))
+        .context("arrow ipc block length out of range")?;
+    ensure!(
+        offset
+            .checked_add(len)
+            .is_some_and(|end| end <= buffer.len()),
+        "arrow ipc block out of bounds"
+    );
+    let data = buffer.slice_with_length(offset, len);
+
+    decoder
+        .read_record_batch(block, &data)
+        .context("read blob batch")?
+        .context("empty blob file")
 }
 
 impl ReportUsage for BlobStoreBuilder {
     fn usage(&self) -> Usage {
         let mut u = Usage::default();
-        let disk = self
-            .writer
-            .get_ref()
-            .get_ref()
-            .as_file()
-            .metadata()
-            .map(|m| m.len())
-            .unwrap_or(0);
-        u.add_disk_usage(disk);
+        self.writer.get_ref().add_usage(&mut u);
         u
     }
 }
@@ -290,13 +503,12 @@ impl ReportUsage for BlobStoreBuilder {
 impl ReportUsage for BlobStore {
     fn usage(&self) -> Usage {
         let mut u = Usage::default();
-        let disk = self
-            ._file
-            .as_file()
-            .metadata()
-            .map(|m| m.len())
-            .unwrap_or(0);
-        u.add_disk_usage(disk);
+        self.backing.add_usage(&mut u, &self.buffer);
+        u.add_items(self.len());
+        u.add_blob_bytes(self.blob_bytes());
+        if let Some(by_cid) = self.by_cid.get() {
+            u.add_boxed_slice(by_cid);
+        }
         u
     }
 }
@@ -306,11 +518,20 @@ mod tests {
     use super::*;
 
     #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
     fn empty_store() -> Result<()> {
         let empty: Vec<&[u8]> = vec![];
         let store = BlobStore::new(&[], &empty)?;
         assert_eq!(store.len(), 0);
         assert!(store.usage().disk > 0, "empty IPC file has header");
+        assert_eq!(
+            store.usage().mapped,
+            store.usage().disk,
+            "whole file is mapped"
+        );
         Ok(())
     }
 
@@ -332,6 +553,62 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn position_finds_cid() -> Result<()> {
+        let cids = [300u64, 100, 200];
+        let blobs: Vec<&[u8]> = vec![b"ccc", b"aaa", b"bbb"];
+
+        let store = BlobStore::new(&cids, &blobs)?;
+        assert_eq!(store.position(100), Some(1));
+        assert_eq!(store.position(300), Some(0));
+        assert_eq!(store.position(999), None);
+        Ok(())
+    }
+
+    #[test]
+    fn iter_by_cid_sorted_and_complete() -> Result<()> {
+        let cids = [300u64, 100, 500, 200, 400];
+        let blobs: Vec<&[u8]> = vec![b"ccc", b"aaa", b"eee", b"bbb", b"ddd"];
+
+        let store = BlobStore::new(&cids, &blobs)?;
+        let unindexed = store.usage().heap;
+        if !cfg!(target_arch = "wasm32") {
+            assert_eq!(unindexed, 0, "blobs are mapped, index is built lazily");
+        }
+
+        let got: Vec<(Cid, &[u8])> = store.iter_by_cid().collect();
+        let expected: Vec<(Cid, &[u8])> = vec![
+            (100, b"aaa"),
+            (200, b"bbb"),
+            (300, b"ccc"),
+            (400, b"ddd"),
+            (500, b"eee"),
+        ];
+        assert_eq!(got, expected);
+        assert_eq!(
+            store.usage().heap - unindexed,
+            cids.len() * size_of::<usize>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn concat_interleaves_stores() -> Result<()> {
+        let a = BlobStore::new(&[1u64, 3, 5], &[b"a1".as_slice(), b"a3", b"a5"])?;
+        let b = BlobStore::new(&[2u64, 4], &[b"b2".as_slice(), b"b4"])?;
+
+        let merged = BlobStore::concat(&[&a, &b], &[(0, 0), (1, 0), (0, 1), (1, 1), (0, 2)])?;
+        let got: Vec<(Cid, &[u8])> = (0..merged.len()).map(|i| merged.resolve(i)).collect();
+        let expected: Vec<(Cid, &[u8])> =
+            vec![(1, b"a1"), (2, b"b2"), (3, b"a3"), (4, b"b4"), (5, b"a5")];
+        assert_eq!(got, expected);
+        assert_eq!(merged.position(4), Some(3));
+
+        assert!(BlobStore::concat(&[&a, &b], &[(2, 0)]).is_err());
+        assert!(BlobStore::concat(&[&a, &b], &[(1, 2)]).is_err());
+        Ok(())
+    }
+
     #[test]
     fn builder_empty() -> Result<()> {
         let builder = BlobStoreBuilder::new()?;
@@ -361,6 +638,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
     fn builder_disk_usage() -> Result<()> {
         let mut builder = BlobStoreBuilder::new()?;
         builder.append(&[1], &[b"x".as_slice()])?;
@@ -387,6 +668,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn presorted_builder_keeps_order() -> Result<()> {
+        let mut builder = PresortedBuilder::with_capacity(3, 0);
+        builder.push(300, b"ccc");
+        builder.push(100, b"");
+        builder.push(200, b"bb");
+
+        let store = builder.build()?;
+        assert_eq!(store.cids(), [300, 100, 200]);
+        assert_eq!(store.resolve(1), (100, b"".as_slice()));
+        assert_eq!(store.resolve(2), (200, b"bb".as_slice()));
+        assert_eq!(store.blob_bytes(), 5);
+
+        assert!(PresortedBuilder::with_capacity(0, 0).build()?.is_empty());
+        Ok(())
+    }
+
     #[test]
     fn builder_identity_empty() -> Result<()> {
         let builder = BlobStoreBuilder::new()?;
@@ -0,0 +1,134 @@
+//! Operation counters on the content hot paths, emitted through the `metrics`
+//! facade with the `metrics` feature. Without it they compile down to nothing;
+//! only trivially cheap arguments such as lengths are passed.
+
+/// Entries buffered by `Hot::add*`, excluding skipped duplicates.
+pub(crate) const HOT_ENTRIES_ADDED: &str = "crimeline_hot_entries_added";
+/// Entries `Hot::add*` refused for a timestamp outside the window.
+pub(crate) const HOT_ENTRIES_REJECTED: &str = "crimeline_hot_entries_rejected";
+/// Hot arenas frozen into cold ones.
+pub(crate) const ARENA_FREEZES: &str = "crimeline_arena_freezes";
+/// Histogram of freeze durations, in seconds.
+pub(crate) const ARENA_FREEZE_SECONDS: &str = "crimeline_arena_freeze_seconds";
+pub(crate) const TIMELINE_ARENAS_ADDED: &str = "crimeline_timeline_arenas_added";
+pub(crate) const TIMELINE_ARENAS_REMOVED: &str = "crimeline_timeline_arenas_removed";
+pub(crate) const SLICES_OPENED: &str = "crimeline_slices_opened";
+pub(crate) const SLICE_ENTRIES_YIELDED: &str = "crimeline_slice_entries_yielded";
+pub(crate) const BLOBS_RESOLVED: &str = "crimeline_blobs_resolved";
+pub(crate) const BYTES_EXPORTED: &str = "crimeline_bytes_exported";
+pub(crate) const BYTES_IMPORTED: &str = "crimeline_bytes_imported";
+
+/// Increments the counter `$name` by `$n`, or by one.
+macro_rules! count {
+    ($name:expr) => {
+        $crate::content::counters::count!($name, 1)
+    };
+    ($name:expr, $n:expr) => {
+        #[cfg(feature = "metrics")]
+        metrics::counter!($name).increment($n as u64);
+        #[cfg(not(feature = "metrics"))]
+        let _ = ($name, $n);
+    };
+}
+
+/// Evaluates `$body` and records how long it took into the histogram `$name`.
+macro_rules! timed {
+    ($name:expr, $body:expr) => {{
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        #[cfg(not(feature = "metrics"))]
+        let _ = $name;
+        let result = $body;
+        #[cfg(feature = "metrics")]
+        metrics::histogram!($name).record(started.elapsed().as_secs_f64());
+        result
+    }};
+}
+
+pub(crate) use {count, timed};
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+
+    use metrics_util::debugging::DebugValue;
+
+    use super::*;
+    use crate::Uid;
+    use crate::content::arena::Cold;
+    use crate::content::test_util;
+    use crate::content::{Order, Timeline, Window};
+
+    fn freeze(epoch: u64, entries: &[(Uid, u64, u64, &str)]) -> Arc<Cold> {
+        test_util::freeze(Window::new(epoch, 100), entries.iter().copied())
+    }
+
+    /// Counter totals and histogram sample counts, by name.
+    fn capture(f: impl FnOnce()) -> BTreeMap<String, u64> {
+        crate::usage::tests::capture_metrics(f, |value| match value {
+            DebugValue::Counter(n) => Some(n),
+            DebugValue::Histogram(samples) => Some(samples.len() as u64),
+            DebugValue::Gauge(_) => None,
+        })
+        .into_iter()
+        .map(|((name, _), n)| (name, n))
+        .collect()
+    }
+
+    #[test]
+    fn counters_fire_end_to_end() {
+        let counts = capture(|| {
+            let timeline = Timeline::new(vec![
+                freeze(
+                    1000,
+                    &[
+                        (10, 1, 1010, "gm from alice"),
+                        (20, 2, 1020, "bob's hot take"),
+                        (10, 1, 1010, "duplicate"),
+                    ],
+                ),
+                freeze(2000, &[(30, 10, 2010, "carol ships code")]),
+            ]);
+
+            let mut slice = timeline.iter_all(Order::Asc);
+            while let Some(e) = slice.next() {
+                e.resolve();
+            }
+
+            timeline.add(freeze(3000, &[(10, 20, 3010, "alice late post")]));
+            timeline.remove(1000);
+            timeline.remove(1000);
+
+            let mut buf = Vec::new();
+            let written = timeline.snapshot()[0].export(&mut buf).unwrap();
+            assert_eq!(written, buf.len() as u64);
+            Cold::import(buf.into()).unwrap();
+
+            let mut slice = timeline.iter(2005, Order::Desc);
+            slice.next();
+        });
+
+        let exported = counts[BYTES_EXPORTED];
+        assert!(exported > 0);
+
+        assert_eq!(
+            counts,
+            BTreeMap::from(
+                [
+                    (HOT_ENTRIES_ADDED, 4),
+                    (ARENA_FREEZES, 3),
+                    (ARENA_FREEZE_SECONDS, 3),
+                    (TIMELINE_ARENAS_ADDED, 1),
+                    (TIMELINE_ARENAS_REMOVED, 1),
+                    (SLICES_OPENED, 2),
+                    (SLICE_ENTRIES_YIELDED, 4),
+                    (BLOBS_RESOLVED, 3),
+                    (BYTES_EXPORTED, exported),
+                    (BYTES_IMPORTED, exported),
+                ]
+                .map(|(name, n)| (name.to_string(), n))
+            )
+        );
+    }
+}
@@ -1,13 +1,35 @@
 pub mod arena;
+#[cfg(feature = "tokio")]
+mod async_io;
+mod backing;
 pub mod blobs;
+mod counters;
 mod order;
+mod partition;
+mod pool;
+mod rotator;
+mod snapshot;
+#[cfg(feature = "datafusion")]
+mod sql;
+mod stats;
+#[cfg(test)]
+mod test_util;
 mod window;
 
 mod timeline;
-pub use timeline::{Slice, Timeline};
+pub use timeline::{
+    DrainResult, ImportErrors, OverlapError, Slice, StopReason, Timeline, VisibleSlice,
+};
 
 pub type Cid = u64;
 pub type Timestamp = u64;
 
 pub use order::Order;
+pub use partition::{ExportReport, MANIFEST, Partition, PartitionScheme};
+pub use pool::BlobPool;
+pub use rotator::{FreezeHook, Rotator, RotatorOptions, Stray, StrayError};
+pub use snapshot::{SnapshotDiff, TimelineSnapshot};
+#[cfg(feature = "datafusion")]
+pub use sql::TimelineTable;
+pub use stats::{ArenaStats, STATS_SIDECAR};
 pub use window::Window;
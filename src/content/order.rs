@@ -1,7 +1,14 @@
 use std::iter::Rev;
 use std::ops::Range;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Iteration direction. With the `serde` feature, serializes as `"asc"` or
+/// `"desc"`.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Order {
     Asc,
     Desc,
@@ -52,6 +59,16 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() -> serde_json::Result<()> {
+        for (order, name) in [(Order::Asc, "asc"), (Order::Desc, "desc")] {
+            assert_eq!(serde_json::to_value(order)?, name);
+            assert_eq!(serde_json::from_value::<Order>(name.into())?, order);
+        }
+        Ok(())
+    }
+
     proptest! {
         #[test]
         fn fuzz_sort_and_range(mut v: Vec<u64>, start in 0..50usize, len in 0..50usize) {
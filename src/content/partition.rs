@@ -0,0 +1,431 @@
+//! Hive-style partitioned parquet export of a [`Timeline`], for query
+//! engines that prune on `dt=` / `hour=` directories. The export writes a
+//! JSON manifest, so it needs the `serde` feature.
+
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
+#[cfg(feature = "serde")]
+use std::fs::{self, File};
+#[cfg(feature = "serde")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "serde")]
+use std::ops::Range;
+#[cfg(feature = "serde")]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(feature = "serde")]
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use anyhow::{Context, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde_json::json;
+#[cfg(feature = "serde")]
+use tracing::{debug_span, trace};
+
+#[cfg(feature = "serde")]
+use super::Timeline;
+#[cfg(feature = "serde")]
+use super::arena::{self, Cold, ExportOptions, Fnv64};
+use super::{Timestamp, Window};
+
+/// File name of the manifest written next to the partitions.
+pub const MANIFEST: &str = "_manifest.json";
+
+/// How entries are grouped into directories, by UTC timestamp. With the
+/// `serde` feature, serializes as `"daily"` or `"hourly"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum PartitionScheme {
+    /// `dt=YYYY-MM-DD/part.parquet`
+    Daily,
+    /// `dt=YYYY-MM-DD/hour=HH/part.parquet`
+    Hourly,
+}
+
+/// One written partition file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Partition {
+    pub bytes: u64,
+    /// 64-bit FNV-1a of the file contents.
+    pub checksum: u64,
+    /// Relative to the export root.
+    pub path: PathBuf,
+    pub rows: u64,
+    /// Window the partition covers; imported, the file spans exactly this.
+    pub span: Window,
+}
+
+/// What [`Timeline::export_partitioned`] wrote, oldest partition first.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ExportReport {
+    pub partitions: Vec<Partition>,
+}
+
+impl PartitionScheme {
+    /// Width of one partition in seconds.
+    pub fn grain(self) -> u32 {
+        match self {
+            PartitionScheme::Daily => 86_400,
+            PartitionScheme::Hourly => 3_600,
+        }
+    }
+
+    /// Directory of the partition starting at `epoch`, relative to the root.
+    pub fn dir(self, epoch: Timestamp) -> PathBuf {
+        let (year, month, day) = civil_from_days((epoch / 86_400) as i64);
+        let dt = format!("dt={year:04}-{month:02}-{day:02}");
+
+        match self {
+            PartitionScheme::Daily => PathBuf::from(dt),
+            PartitionScheme::Hourly => {
+                PathBuf::from(dt).join(format!("hour={:02}", epoch % 86_400 / 3_600))
+            }
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn name(self) -> &'static str {
+        match self {
+            PartitionScheme::Daily => "daily",
+            PartitionScheme::Hourly => "hourly",
+        }
+    }
+}
+
+impl ExportReport {
+    pub fn rows(&self) -> u64 {
+        self.partitions.iter().map(|p| p.rows).sum()
+    }
+
+    /// The manifest as JSON: the scheme and, per partition, its path, window,
+    /// row count, size and checksum (as 16 hex digits).
+    #[cfg(feature = "serde")]
+    fn manifest(&self, scheme: PartitionScheme) -> serde_json::Value {
+        let partitions: Vec<_> = self
+            .partitions
+            .iter()
+            .map(|p| {
+                json!({
+                    "path": p.path.to_string_lossy().replace('\\', "/"),
+                    "epoch": p.span.epoch,
+                    "duration": p.span.duration,
+                    "rows": p.rows,
+                    "bytes": p.bytes,
+                    "fnv64": format!("{:016x}", p.checksum),
+                })
+            })
+            .collect();
+
+        json!({
+            "format": "crimeline-partitioned-v1",
+            "scheme": scheme.name(),
+            "rows": self.rows(),
+            "partitions": partitions,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Timeline {
+    /// Writes the timeline under `root` as one `part.parquet` per partition
+    /// of `scheme`, plus a [`MANIFEST`]. Arenas crossing partition
+    /// boundaries are split; rows of arenas sharing a partition are merged
+    /// into one file in `(timestamp, cid)` order. Empty partitions are
+    /// skipped.
+    pub fn export_partitioned(
+        &self,
+        root: impl AsRef<Path>,
+        scheme: PartitionScheme,
+    ) -> Result<ExportReport> {
+        let root = root.as_ref();
+        let _op = debug_span!("export_partitioned", root = %root.display(), scheme = scheme.name())
+            .entered();
+
+        let mut report = ExportReport::default();
+
+        for (epoch, parts) in partitions(&self.snapshot(), scheme.grain())? {
+            let span = Window::new(epoch, scheme.grain());
+            let path = scheme.dir(epoch).join("part.parquet");
+
+            let full = root.join(&path);
+            fs::create_dir_all(full.parent().unwrap_or(root))
+                .with_context(|| format!("create {}", full.display()))?;
+            let file = File::create(&full).with_context(|| format!("create {}", full.display()))?;
+
+            let mut writer = ChecksumWriter {
+                hash: Fnv64::new(),
+                inner: BufWriter::new(file),
+                written: 0,
+            };
+            let options = ExportOptions::default();
+            let mut pq = Cold::parquet_writer(&mut writer, &span, Vec::new(), &options)?;
+
+            let rows = arena::write_merged(
+                &mut pq,
+                parts.iter().map(|(arena, range)| (&**arena, range.clone())),
+                options.batch_rows,
+            )?;
+
+            pq.close().context("close parquet writer")?;
+            writer
+                .flush()
+                .with_context(|| format!("flush {}", full.display()))?;
+
+            trace!(path = %path.display(), rows, bytes = writer.written, "exported partition");
+
+            report.partitions.push(Partition {
+                bytes: writer.written,
+                checksum: writer.hash.0,
+                path,
+                rows: rows as u64,
+                span,
+            });
+        }
+
+        let manifest = root.join(MANIFEST);
+        fs::create_dir_all(root).with_context(|| format!("create {}", root.display()))?;
+        let file =
+            File::create(&manifest).with_context(|| format!("create {}", manifest.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &report.manifest(scheme))
+            .with_context(|| format!("write {}", manifest.display()))?;
+        writer
+            .flush()
+            .with_context(|| format!("flush {}", manifest.display()))?;
+
+        trace!(
+            partitions = report.partitions.len(),
+            rows = report.rows(),
+            "exported timeline partitions"
+        );
+
+        Ok(report)
+    }
+}
+
+/// Row ranges of the arenas falling into one partition.
+#[cfg(feature = "serde")]
+type Parts = Vec<(Arc<Cold>, Range<usize>)>;
+
+/// Non-empty row ranges of each arena, keyed by the epoch of the `grain`
+/// partition they fall into.
+#[cfg(feature = "serde")]
+fn partitions(arenas: &[Arc<Cold>], grain: u32) -> Result<BTreeMap<Timestamp, Parts>> {
+    let mut out: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+    for arena in arenas {
+        let end = arena.span.end_exclusive();
+        let mut start = arena.span.epoch;
+
+        while start < end {
+            let bucket = Window::aligned_for(start, grain)?;
+            let stop = bucket.end_exclusive().min(end);
+            let rows = arena.rows_between(start, stop);
+
+            if !rows.is_empty() {
+                out.entry(bucket.epoch)
+                    .or_default()
+                    .push((Arc::clone(arena), rows));
+            }
+
+            start = stop;
+        }
+    }
+
+    Ok(out)
+}
+
+/// Passes writes through, hashing and counting the bytes accepted.
+#[cfg(feature = "serde")]
+struct ChecksumWriter<W> {
+    hash: Fnv64,
+    inner: W,
+    written: u64,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hash.write(&buf[..n]);
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Proleptic Gregorian `(year, month, day)` of `days` since 1970-01-01,
+/// after Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[cfg(feature = "serde")]
+    use bytes::Bytes;
+
+    use super::*;
+    #[cfg(feature = "serde")]
+    use crate::Uid;
+    #[cfg(feature = "serde")]
+    use crate::content::test_util::freeze;
+
+    /// 2024-01-01T00:00:00Z
+    const DAY_ONE: Timestamp = 1_704_067_200;
+
+    /// `n` entries spread evenly over `[epoch, epoch + duration)`.
+    #[cfg(feature = "serde")]
+    fn make_arena(epoch: Timestamp, duration: u32, n: u64) -> Arc<Cold> {
+        freeze(
+            Window::new(epoch, duration),
+            (0..n).map(|i| {
+                let ts = epoch + i * duration as u64 / n;
+                ((i % 7) as Uid, epoch + i, ts, format!("{ts}"))
+            }),
+        )
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_723), (2024, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+
+        assert_eq!(
+            PartitionScheme::Hourly.dir(DAY_ONE + 86_400 + 5 * 3_600 + 59),
+            Path::new("dt=2024-01-02/hour=05")
+        );
+        assert_eq!(
+            PartitionScheme::Daily.dir(DAY_ONE + 86_399),
+            Path::new("dt=2024-01-01")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn two_days_hourly() -> Result<()> {
+        // Day one in hourly arenas of h + 1 entries; day two in two-hour
+        // arenas, split at the hour, of 10 entries each.
+        let mut arenas: Vec<_> = (0..24)
+            .map(|h| make_arena(DAY_ONE + h * 3_600, 3_600, h + 1))
+            .collect();
+        arenas.extend((0..12).map(|i| make_arena(DAY_ONE + 86_400 + i * 7_200, 7_200, 10)));
+        let timeline = Timeline::new(arenas);
+
+        let root = tempfile::tempdir()?;
+        let report = timeline.export_partitioned(root.path(), PartitionScheme::Hourly)?;
+
+        assert_eq!(report.partitions.len(), 48);
+        assert_eq!(report.rows(), (1..=24).sum::<u64>() + 12 * 10);
+
+        for (i, p) in report.partitions.iter().enumerate() {
+            let (day, hour) = (i / 24 + 1, i % 24);
+            assert_eq!(
+                p.path,
+                PathBuf::from(format!("dt=2024-01-0{day}/hour={hour:02}/part.parquet"))
+            );
+
+            let expected = if day == 1 { hour as u64 + 1 } else { 5 };
+            assert_eq!(p.rows, expected, "{}", p.path.display());
+
+            let data = fs::read(root.path().join(&p.path))?;
+            assert_eq!(data.len() as u64, p.bytes);
+
+            let mut hash = Fnv64::new();
+            hash.write(&data);
+            assert_eq!(hash.0, p.checksum);
+
+            let cold = Cold::import(Bytes::from(data))?;
+            assert_eq!(cold.len() as u64, p.rows);
+            assert_eq!(cold.span, p.span);
+            assert!(
+                cold.iter(crate::Order::Asc, 0)
+                    .all(|e| p.span.contains(e.timestamp()))
+            );
+        }
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(root.path().join(MANIFEST))?)?;
+        assert_eq!(manifest["scheme"], "hourly");
+        assert_eq!(manifest["rows"], report.rows());
+        assert_eq!(manifest["partitions"].as_array().map(Vec::len), Some(48));
+        assert_eq!(
+            manifest["partitions"][25],
+            serde_json::json!({
+                "path": "dt=2024-01-02/hour=01/part.parquet",
+                "epoch": DAY_ONE + 86_400 + 3_600,
+                "duration": 3_600,
+                "rows": 5,
+                "bytes": report.partitions[25].bytes,
+                "fnv64": format!("{:016x}", report.partitions[25].checksum),
+            })
+        );
+
+        let daily =
+            timeline.export_partitioned(root.path().join("daily"), PartitionScheme::Daily)?;
+        let rows: Vec<_> = daily
+            .partitions
+            .iter()
+            .map(|p| (p.path.clone(), p.rows))
+            .collect();
+        assert_eq!(
+            rows,
+            [
+                (PathBuf::from("dt=2024-01-01/part.parquet"), 300),
+                (PathBuf::from("dt=2024-01-02/part.parquet"), 120),
+            ]
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn overlapping_arenas_merge_within_partition() -> Result<()> {
+        // The second arena starts half an hour into the first.
+        let timeline = Timeline::new(vec![
+            make_arena(DAY_ONE, 3_600, 10),
+            make_arena(DAY_ONE + 1_800, 3_600, 10),
+        ]);
+
+        let root = tempfile::tempdir()?;
+        let report = timeline.export_partitioned(root.path(), PartitionScheme::Hourly)?;
+        let rows: Vec<_> = report.partitions.iter().map(|p| p.rows).collect();
+        assert_eq!(rows, [15, 5]);
+
+        let data = fs::read(root.path().join(&report.partitions[0].path))?;
+        let cold = Cold::import(Bytes::from(data))?;
+        let timestamps: Vec<_> = cold
+            .iter(crate::Order::Asc, 0)
+            .map(|e| e.timestamp() - DAY_ONE)
+            .collect();
+        assert_eq!(
+            timestamps,
+            [
+                0, 360, 720, 1_080, 1_440, 1_800, 1_800, 2_160, 2_160, 2_520, 2_520, 2_880, 2_880,
+                3_240, 3_240
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! Blobs stored once per cid and shared across arenas, see
+//! [`Cold::with_external_blobs`](super::arena::Cold::with_external_blobs).
+
+use std::sync::{Arc, OnceLock};
+
+use anyhow::{Context, Result};
+use arrow::array::{ArrayRef, BinaryArray, UInt64Array};
+use bytes::Bytes;
+use tracing::trace;
+
+use crate::usage::{ReportUsage, Usage};
+
+use super::Cid;
+use super::arena::Cold;
+use super::blobs::{BlobStore, PresortedBuilder};
+
+/// A blob store with one row per cid, in ascending cid order. Arenas that
+/// repeat content can point into a shared pool instead of each keeping a
+/// copy of the blob.
+pub struct BlobPool {
+    store: BlobStore,
+}
+
+impl BlobPool {
+    /// Pools `(cid, blob)` pairs, keeping the first blob seen for each cid.
+    pub fn new<'a>(entries: impl IntoIterator<Item = (Cid, &'a [u8])>) -> Result<Self> {
+        let mut entries: Vec<(Cid, &[u8])> = entries.into_iter().collect();
+        entries.sort_by_key(|&(cid, _)| cid);
+        entries.dedup_by_key(|&mut (cid, _)| cid);
+
+        let blob_bytes = entries.iter().map(|(_, blob)| blob.len()).sum();
+        let mut builder = PresortedBuilder::with_capacity(entries.len(), blob_bytes);
+        for (cid, blob) in entries {
+            builder.push(cid, blob);
+        }
+
+        let store = builder.build()?;
+        trace!(len = store.len(), "built blob pool");
+
+        Ok(Self { store })
+    }
+
+    /// Pools the blobs of every arena in `arenas`.
+    pub fn from_arenas<'a>(arenas: impl IntoIterator<Item = &'a Arc<Cold>>) -> Result<Self> {
+        Self::new(arenas.into_iter().flat_map(|arena| {
+            let blobs = arena.blobs();
+            (0..blobs.cids().len()).map(|idx| blobs.resolve(idx))
+        }))
+    }
+
+    /// Row of `cid` in the pool.
+    pub fn position(&self, cid: Cid) -> Option<usize> {
+        self.store.cids().binary_search(&cid).ok()
+    }
+
+    pub fn get(&self, cid: Cid) -> Option<&[u8]> {
+        self.position(cid).map(|row| self.store.resolve(row).1)
+    }
+
+    pub fn contains(&self, cid: Cid) -> bool {
+        self.position(cid).is_some()
+    }
+
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+}
+
+impl ReportUsage for BlobPool {
+    fn usage(&self) -> Usage {
+        self.store.usage()
+    }
+}
+
+/// Where an arena's blobs live: its own store, or rows of a shared pool.
+#[allow(clippy::large_enum_variant)] // always behind the arena's `Arc`
+pub(super) enum Blobs {
+    Own(BlobStore),
+    Pooled(Pooled),
+}
+
+pub(super) struct Pooled {
+    /// Row indices sorted by cid, built on first cid lookup.
+    by_cid: OnceLock<Box<[usize]>>,
+    cids: Box<[Cid]>,
+    pool: Arc<BlobPool>,
+    /// Pool row of each entry.
+    rows: Box<[usize]>,
+}
+
+impl Pooled {
+    /// Points each of `cids` at its row in `pool`, failing on the first cid
+    /// the pool lacks.
+    pub(super) fn new(cids: &[Cid], pool: Arc<BlobPool>) -> Result<Self> {
+        let rows = cids
+            .iter()
+            .map(|&cid| {
+                pool.position(cid)
+                    .with_context(|| format!("cid {cid} missing from blob pool"))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            by_cid: OnceLock::new(),
+            cids: cids.into(),
+            pool,
+            rows,
+        })
+    }
+
+    pub(super) fn pool(&self) -> &Arc<BlobPool> {
+        &self.pool
+    }
+}
+
+impl From<BlobStore> for Blobs {
+    fn from(store: BlobStore) -> Self {
+        Self::Own(store)
+    }
+}
+
+impl Blobs {
+    /// The cid and blob columns, gathered from the pool if the blobs are
+    /// not the arena's own.
+    pub(super) fn columns(&self) -> (ArrayRef, ArrayRef) {
+        match self {
+            Self::Own(store) => store.columns(),
+            Self::Pooled(pooled) => (
+                Arc::new(UInt64Array::from_iter_values(pooled.cids.iter().copied())),
+                Arc::new(BinaryArray::from_iter_values(
+                    (0..pooled.cids.len()).map(|idx| self.resolve(idx).1),
+                )),
+            ),
+        }
+    }
+
+    /// Row indices in ascending `(cid, row)` order, built on first use.
+    pub(super) fn by_cid(&self) -> &[usize] {
+        match self {
+            Self::Own(store) => store.by_cid(),
+            Self::Pooled(pooled) => pooled.by_cid.get_or_init(|| {
+                let mut order: Vec<usize> = (0..pooled.cids.len()).collect();
+                order.sort_unstable_by_key(|&idx| (pooled.cids[idx], idx));
+                order.into_boxed_slice()
+            }),
+        }
+    }
+
+    pub(super) fn position(&self, cid: Cid) -> Option<usize> {
+        match self {
+            Self::Own(store) => store.position(cid),
+            Self::Pooled(pooled) => {
+                let order = self.by_cid();
+                order
+                    .binary_search_by_key(&cid, |&idx| pooled.cids[idx])
+                    .ok()
+                    .map(|pos| order[pos])
+            }
+        }
+    }
+
+    pub(super) fn cids(&self) -> &[Cid] {
+        match self {
+            Self::Own(store) => store.cids(),
+            Self::Pooled(pooled) => &pooled.cids,
+        }
+    }
+
+    pub(super) fn resolve(&self, idx: usize) -> (Cid, &[u8]) {
+        match self {
+            Self::Own(store) => store.resolve(idx),
+            Self::Pooled(pooled) => pooled.pool.store.resolve(pooled.rows[idx]),
+        }
+    }
+
+    pub(super) fn resolve_bytes(&self, idx: usize) -> (Cid, Bytes) {
+        match self {
+            Self::Own(store) => store.resolve_bytes(idx),
+            Self::Pooled(pooled) => pooled.pool.store.resolve_bytes(pooled.rows[idx]),
+        }
+    }
+}
+
+/// A pooled arena reports only its row mapping; the pool's storage is
+/// reported by the [`BlobPool`] itself, once.
+impl ReportUsage for Blobs {
+    fn usage(&self) -> Usage {
+        match self {
+            Self::Own(store) => store.usage(),
+            Self::Pooled(pooled) => {
+                let mut u = Usage::default();
+                u.add_items(pooled.cids.len());
+                u.add_boxed_slice(&pooled.cids);
+                u.add_boxed_slice(&pooled.rows);
+                if let Some(by_cid) = pooled.by_cid.get() {
+                    u.add_boxed_slice(by_cid);
+                }
+                u
+            }
+        }
+    }
+}
@@ -0,0 +1,553 @@
+//! Ingest orchestration: one [`Hot`] per open grid-aligned window, frozen
+//! into the [`Timeline`] once the window closes.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{Result, ensure};
+use parking_lot::Mutex;
+use tracing::trace;
+
+use super::arena::{Cold, Hot};
+use super::{Cid, Order, Timeline, Timestamp, Window};
+use crate::Uid;
+
+/// What [`Rotator::add`] does with an event outside the windows it accepts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Stray {
+    /// Buffer it anyway. A late event is merged into its window's published
+    /// arena on the next tick.
+    Accept,
+    /// Skip it, counting it in [`Rotator::dropped`].
+    Drop,
+    /// Fail with a [`StrayError`].
+    #[default]
+    Reject,
+}
+
+/// Returned, wrapped in an [`anyhow::Error`], for events rejected per
+/// [`RotatorOptions`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum StrayError {
+    /// The event's window closed more than the grace period ago.
+    Late {
+        ts: Timestamp,
+        closed_until: Timestamp,
+    },
+    /// The event's window starts more than `max_future` after the last tick.
+    Early {
+        ts: Timestamp,
+        open_until: Timestamp,
+    },
+}
+
+impl fmt::Display for StrayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrayError::Late { ts, closed_until } => {
+                write!(
+                    f,
+                    "event at {ts} is late: windows before {closed_until} are closed"
+                )
+            }
+            StrayError::Early { ts, open_until } => {
+                write!(
+                    f,
+                    "event at {ts} is early: windows after {open_until} are not open"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for StrayError {}
+
+/// Called by [`Rotator`] with each arena it publishes.
+pub type FreezeHook = Box<dyn Fn(&Arc<Cold>) + Send + Sync>;
+
+#[derive(Default)]
+pub struct RotatorOptions {
+    /// Seconds a window stays open after its end, for late events.
+    pub grace: u64,
+    /// Handling of events for windows already frozen.
+    pub late: Stray,
+    /// Freeze a window early once it buffers this many entries. Further
+    /// events for it start a new buffer, merged into the published arena
+    /// when it is frozen in turn.
+    pub max_entries: Option<usize>,
+    /// Seconds past the last tick a window may start and still be opened;
+    /// later ones are handled per `early`. Unlimited if `None`.
+    pub max_future: Option<u64>,
+    /// Handling of events beyond `max_future`.
+    pub early: Stray,
+    /// Called with every arena after it is published, e.g. to export it.
+    pub on_freeze: Option<FreezeHook>,
+}
+
+/// Routes events into per-window [`Hot`] arenas on a grid of
+/// `window_duration`, and publishes them to a [`Timeline`] as [`tick`]
+/// closes them. `add` and `tick` may run on different threads; freezing
+/// happens outside the lock `add` takes.
+///
+/// [`tick`]: Rotator::tick
+pub struct Rotator {
+    duration: u32,
+    options: RotatorOptions,
+    /// Held while publishing, so merges into a window are not lost to a
+    /// concurrent merge.
+    publishing: Mutex<()>,
+    state: Mutex<State>,
+    timeline: Arc<Timeline>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Windows ending at or before this are frozen.
+    closed_until: Timestamp,
+    dropped: u64,
+    /// Latest tick, if any.
+    now: Option<Timestamp>,
+    /// Open arenas by window epoch.
+    open: BTreeMap<Timestamp, Hot>,
+}
+
+impl Rotator {
+    /// Fails if `window_duration` is zero.
+    pub fn new(
+        timeline: Arc<Timeline>,
+        window_duration: u32,
+        options: RotatorOptions,
+    ) -> Result<Self> {
+        ensure!(window_duration > 0, "window_duration must be positive");
+        Ok(Self {
+            duration: window_duration,
+            options,
+            publishing: Mutex::default(),
+            state: Mutex::default(),
+            timeline,
+        })
+    }
+
+    /// Buffers an event in the arena of its window, opening it if needed.
+    /// Events for closed or far-future windows are handled per options.
+    pub fn add(&self, uid: Uid, cid: Cid, ts: Timestamp, blob: &[u8]) -> Result<()> {
+        let window = Window::aligned_for(ts, self.duration)?;
+        let epoch = window.epoch;
+
+        let full = {
+            let mut state = self.state.lock();
+
+            let policy = if window.end_exclusive() <= state.closed_until {
+                Some((
+                    self.options.late,
+                    StrayError::Late {
+                        ts,
+                        closed_until: state.closed_until,
+                    },
+                ))
+            } else {
+                let open_until = self
+                    .options
+                    .max_future
+                    .zip(state.now)
+                    .map(|(ahead, now)| now.saturating_add(ahead));
+
+                open_until
+                    .filter(|&until| window.epoch > until)
+                    .map(|open_until| (self.options.early, StrayError::Early { ts, open_until }))
+            };
+
+            match policy {
+                Some((Stray::Reject, err)) => return Err(err.into()),
+                Some((Stray::Drop, _)) => {
+                    state.dropped += 1;
+                    trace!(ts, "dropped stray event");
+                    return Ok(());
+                }
+                Some((Stray::Accept, _)) | None => {}
+            }
+
+            let hot = match state.open.entry(epoch) {
+                std::collections::btree_map::Entry::Occupied(e) => e.into_mut(),
+                std::collections::btree_map::Entry::Vacant(e) => e.insert(Hot::new(window)?),
+            };
+            hot.add(uid, cid, ts, blob)?;
+
+            match self.options.max_entries {
+                Some(max) if hot.len() >= max => state.open.remove(&epoch),
+                _ => None,
+            }
+        };
+
+        if let Some(hot) = full {
+            trace!(epoch, "rotating full window");
+            self.publish(vec![(epoch, hot)])?;
+        }
+
+        Ok(())
+    }
+
+    /// Advances the clock to `now` (never backwards) and freezes and
+    /// publishes every window that ended at least the grace period before
+    /// it, oldest first. Returns the published arenas. On error, arenas
+    /// frozen before the failure stay published; the failing window and
+    /// any after it are lost.
+    pub fn tick(&self, now: Timestamp) -> Result<Vec<Arc<Cold>>> {
+        let closed = {
+            let mut state = self.state.lock();
+
+            let now = state.now.map_or(now, |prev| prev.max(now));
+            state.now = Some(now);
+            state.closed_until = state
+                .closed_until
+                .max(now.saturating_sub(self.options.grace));
+
+            // Windows end at epoch + duration, so epochs up to this close.
+            let last = state
+                .closed_until
+                .checked_sub(self.duration as u64)
+                .map(|e| e + 1);
+            match last {
+                Some(last) => {
+                    let open = state.open.split_off(&last);
+                    std::mem::replace(&mut state.open, open)
+                }
+                None => BTreeMap::new(),
+            }
+        };
+
+        self.publish(closed.into_iter().collect())
+    }
+
+    /// Freezes and publishes every open window, e.g. on shutdown.
+    pub fn flush(&self) -> Result<Vec<Arc<Cold>>> {
+        let open = std::mem::take(&mut self.state.lock().open);
+
+        self.publish(open.into_iter().collect())
+    }
+
+    /// Events skipped under [`Stray::Drop`].
+    pub fn dropped(&self) -> u64 {
+        self.state.lock().dropped
+    }
+
+    /// Open windows and their buffered entries, oldest first.
+    pub fn pending(&self) -> Vec<(Timestamp, usize)> {
+        let state = self.state.lock();
+
+        state
+            .open
+            .iter()
+            .map(|(&epoch, hot)| (epoch, hot.len()))
+            .collect()
+    }
+
+    /// Freezes each window and adds it to the timeline, or, if the timeline
+    /// already holds an arena over that window, re-freezes that arena with
+    /// the new entries merged in and swaps it in, keeping one arena per
+    /// window. The hook sees the merged arena.
+    fn publish(&self, hots: Vec<(Timestamp, Hot)>) -> Result<Vec<Arc<Cold>>> {
+        let _publishing = self.publishing.lock();
+        let mut published = Vec::with_capacity(hots.len());
+
+        for (epoch, hot) in hots {
+            let window = Window::new(epoch, self.duration);
+            let prior = self
+                .timeline
+                .snapshot()
+                .iter()
+                .find(|a| a.span == window)
+                .cloned();
+
+            let cold: Arc<Cold> = match prior {
+                Some(prior) => {
+                    let mut merged = Hot::new(window)?;
+                    merged.add_bulk(prior.iter(Order::Asc, 0).map(|e| {
+                        let (cid, blob) = e.resolve_bytes();
+                        (e.uid, cid, e.timestamp(), blob)
+                    }))?;
+                    merged.absorb(hot)?;
+
+                    let cold: Arc<Cold> = merged.try_into()?;
+                    self.timeline.replace(Arc::clone(&cold));
+                    cold
+                }
+                None => {
+                    let cold: Arc<Cold> = hot.try_into()?;
+                    self.timeline.add(Arc::clone(&cold));
+                    cold
+                }
+            };
+            if let Some(hook) = &self.options.on_freeze {
+                hook(&cold);
+            }
+
+            trace!(
+                epoch = cold.span.epoch,
+                len = cold.len(),
+                "published window"
+            );
+            published.push(cold);
+        }
+
+        Ok(published)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::*;
+
+    /// 2024-01-01T00:00:00Z
+    const T0: Timestamp = 1_704_067_200;
+    const WINDOW: u32 = 600;
+
+    /// Cids published per window epoch, merged across arenas.
+    fn contents(timeline: &Timeline) -> BTreeMap<Timestamp, BTreeSet<Cid>> {
+        let mut out: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for arena in timeline.snapshot().iter() {
+            out.entry(arena.span.epoch)
+                .or_default()
+                .extend(arena.cids().iter().copied());
+        }
+        out
+    }
+
+    /// Three hours of events, 20 per minute, stamped by sources whose clocks
+    /// lag the ticking clock by up to `max_skew` seconds. Yields
+    /// `(tick, cid, ts)`.
+    fn events(max_skew: u64) -> Vec<(Timestamp, Cid, Timestamp)> {
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        (0..180u64)
+            .flat_map(|minute| (0..20).map(move |i| (minute, i)))
+            .map(|(minute, i)| {
+                let tick = T0 + minute * 60;
+                let ts = tick + next() % 60 - next() % (max_skew + 1);
+                (tick, minute * 100 + i, ts)
+            })
+            .collect()
+    }
+
+    fn expected(events: &[(Timestamp, Cid, Timestamp)]) -> BTreeMap<Timestamp, BTreeSet<Cid>> {
+        let mut out: BTreeMap<_, BTreeSet<_>> = BTreeMap::new();
+        for &(_, cid, ts) in events {
+            out.entry(ts - ts % WINDOW as u64).or_default().insert(cid);
+        }
+        out
+    }
+
+    #[test]
+    fn skew_within_grace_lands_in_one_arena_per_window() -> Result<()> {
+        let timeline = Arc::new(Timeline::new(vec![]));
+        let frozen = Arc::new(AtomicUsize::new(0));
+        let hook = Arc::clone(&frozen);
+
+        let rotator = Rotator::new(
+            Arc::clone(&timeline),
+            WINDOW,
+            RotatorOptions {
+                grace: 120,
+                on_freeze: Some(Box::new(move |_| {
+                    hook.fetch_add(1, Ordering::Relaxed);
+                })),
+                ..Default::default()
+            },
+        )?;
+
+        let events = events(100);
+        let mut published = 0;
+        for &(tick, cid, ts) in &events {
+            rotator.add(1, cid, ts, b"x")?;
+            published += rotator.tick(tick)?.len();
+        }
+        published += rotator.flush()?.len();
+
+        let contents = contents(&timeline);
+        assert_eq!(contents, expected(&events));
+        assert_eq!(timeline.len(), contents.len(), "one arena per window");
+        assert_eq!(published, contents.len());
+        assert_eq!(frozen.load(Ordering::Relaxed), published);
+        assert!(timeline.validate().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn late_events_merged_into_published_window() -> Result<()> {
+        let timeline = Arc::new(Timeline::new(vec![]));
+        let rotator = Rotator::new(
+            Arc::clone(&timeline),
+            WINDOW,
+            RotatorOptions {
+                grace: 60,
+                late: Stray::Accept,
+                ..Default::default()
+            },
+        )?;
+
+        // Skew beyond window plus grace: some events miss their window.
+        let events = events(900);
+        for &(tick, cid, ts) in &events {
+            rotator.add(1, cid, ts, b"x")?;
+            rotator.tick(tick)?;
+        }
+        rotator.flush()?;
+
+        assert_eq!(contents(&timeline), expected(&events));
+        assert_eq!(
+            timeline.len(),
+            contents(&timeline).len(),
+            "one arena per window"
+        );
+        assert!(timeline.validate().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn strays_rejected_or_dropped() -> Result<()> {
+        let timeline = Arc::new(Timeline::new(vec![]));
+        let options = |policy| RotatorOptions {
+            grace: 60,
+            late: policy,
+            max_future: Some(3600),
+            early: policy,
+            ..Default::default()
+        };
+
+        let rotator = Rotator::new(Arc::clone(&timeline), WINDOW, options(Stray::Reject))?;
+        rotator.add(1, 1, T0 + 10, b"a")?;
+        assert!(
+            rotator.tick(T0 + WINDOW as u64 + 59)?.is_empty(),
+            "in grace"
+        );
+        rotator.add(1, 2, T0 + 20, b"late but in grace")?;
+
+        let published = rotator.tick(T0 + WINDOW as u64 + 60)?;
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].cids(), [1, 2]);
+
+        let late = rotator.add(1, 3, T0 + 30, b"too late").unwrap_err();
+        assert_eq!(
+            late.downcast_ref::<StrayError>(),
+            Some(&StrayError::Late {
+                ts: T0 + 30,
+                closed_until: T0 + WINDOW as u64
+            })
+        );
+
+        let far = T0 + 2 * 3600;
+        let early = rotator.add(1, 4, far, b"too early").unwrap_err();
+        assert!(matches!(
+            early.downcast_ref::<StrayError>(),
+            Some(StrayError::Early { .. })
+        ));
+        assert!(early.to_string().contains("early"));
+
+        // Next window, and one an hour ahead, are open.
+        rotator.add(1, 5, T0 + WINDOW as u64 + 1, b"next")?;
+        rotator.add(1, 6, T0 + 3600 + 600, b"ahead")?;
+        assert_eq!(rotator.pending().len(), 2);
+
+        let dropping = Rotator::new(Arc::clone(&timeline), WINDOW, options(Stray::Drop))?;
+        dropping.tick(T0 + WINDOW as u64 + 60)?;
+        dropping.add(1, 7, T0 + 30, b"late")?;
+        dropping.add(1, 8, far, b"early")?;
+        assert_eq!(dropping.dropped(), 2);
+        assert!(dropping.pending().is_empty());
+
+        // Ticks never move the clock back.
+        dropping.tick(T0)?;
+        dropping.add(1, 9, T0 + 30, b"still late")?;
+        assert_eq!(dropping.dropped(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn zero_window_rejected() {
+        let timeline = Arc::new(Timeline::new(vec![]));
+        let err = Rotator::new(timeline, 0, RotatorOptions::default())
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("window_duration"), "{err}");
+    }
+
+    #[test]
+    fn full_window_rotates_early() -> Result<()> {
+        let timeline = Arc::new(Timeline::new(vec![]));
+        let rotator = Rotator::new(
+            Arc::clone(&timeline),
+            WINDOW,
+            RotatorOptions {
+                max_entries: Some(4),
+                ..Default::default()
+            },
+        )?;
+
+        // Timestamps interleave across the early rotations.
+        let offsets = [50, 10, 90, 30, 40, 0, 80, 20, 70, 60];
+        for (cid, offset) in (0..).zip(offsets) {
+            rotator.add(1, cid, T0 + offset, b"x")?;
+        }
+        assert_eq!(timeline.len(), 1, "merged into one arena");
+        assert_eq!(timeline.snapshot()[0].len(), 8);
+        assert_eq!(rotator.pending(), [(T0, 2)]);
+
+        rotator.flush()?;
+        assert_eq!(contents(&timeline)[&T0].len(), 10);
+        assert!(rotator.pending().is_empty());
+        assert!(timeline.validate().is_ok());
+
+        let mut slice = timeline.iter_all(Order::Asc);
+        let mut timestamps = Vec::new();
+        while let Some(e) = slice.next() {
+            timestamps.push(e.timestamp() - T0);
+        }
+        assert_eq!(timestamps, (0..10).map(|i| i * 10).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn concurrent_ingest_and_ticks() -> Result<()> {
+        let timeline = Arc::new(Timeline::new(vec![]));
+        let rotator = Rotator::new(
+            Arc::clone(&timeline),
+            WINDOW,
+            RotatorOptions {
+                grace: 120,
+                ..Default::default()
+            },
+        )?;
+
+        let events = events(100);
+        let (ticks_tx, ticks_rx) = std::sync::mpsc::channel();
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                for tick in ticks_rx {
+                    rotator.tick(tick).unwrap();
+                }
+            });
+
+            for &(tick, cid, ts) in &events {
+                rotator.add(1, cid, ts, b"x").unwrap();
+                ticks_tx.send(tick).unwrap();
+            }
+            drop(ticks_tx);
+        });
+        rotator.flush()?;
+
+        assert_eq!(contents(&timeline), expected(&events));
+        Ok(())
+    }
+}
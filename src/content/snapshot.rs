@@ -0,0 +1,155 @@
+//! Point-in-time views of a [`Timeline`]'s arena list, and the difference
+//! between two of them for incremental re-indexing.
+
+use std::sync::Arc;
+
+use itertools::{EitherOrBoth, Itertools};
+
+use super::Timeline;
+use super::arena::Cold;
+
+/// The arenas of a [`Timeline`] as of [`Timeline::capture`], sorted by
+/// epoch. Holding it keeps those arenas alive.
+#[derive(Clone)]
+pub struct TimelineSnapshot {
+    arenas: Arc<Vec<Arc<Cold>>>,
+}
+
+/// Returned by [`TimelineSnapshot::diff`]; both lists are sorted by epoch.
+#[derive(Default)]
+pub struct SnapshotDiff {
+    pub added: Vec<Arc<Cold>>,
+    pub removed: Vec<Arc<Cold>>,
+}
+
+impl Timeline {
+    /// Snapshots the current arena list, O(1).
+    pub fn capture(&self) -> TimelineSnapshot {
+        TimelineSnapshot {
+            arenas: self.snapshot(),
+        }
+    }
+}
+
+impl TimelineSnapshot {
+    pub fn arenas(&self) -> &[Arc<Cold>] {
+        &self.arenas
+    }
+
+    /// Arenas in `later` but not here (`added`) and here but not in `later`
+    /// (`removed`), matched by epoch and then by identity among arenas
+    /// sharing an epoch. An arena replaced at the same epoch is listed in
+    /// both.
+    pub fn diff(&self, later: &TimelineSnapshot) -> SnapshotDiff {
+        let same_epoch = |a: &Arc<Cold>, b: &Arc<Cold>| a.span.epoch == b.span.epoch;
+        let mut diff = SnapshotDiff::default();
+
+        for pair in self
+            .arenas
+            .chunk_by(same_epoch)
+            .merge_join_by(later.arenas.chunk_by(same_epoch), |a, b| {
+                a[0].span.epoch.cmp(&b[0].span.epoch)
+            })
+        {
+            let (old, new) = match pair {
+                EitherOrBoth::Left(old) => (old, &[][..]),
+                EitherOrBoth::Right(new) => (&[][..], new),
+                EitherOrBoth::Both(old, new) => (old, new),
+            };
+
+            let missing_from = |arenas: &[Arc<Cold>], arena: &Arc<Cold>| {
+                !arenas.iter().any(|a| Arc::ptr_eq(a, arena))
+            };
+            diff.removed
+                .extend(old.iter().filter(|a| missing_from(new, a)).cloned());
+            diff.added
+                .extend(new.iter().filter(|a| missing_from(old, a)).cloned());
+        }
+
+        diff
+    }
+
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    pub fn is_empty(&self) -> bool {
+        self.arenas.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.arenas.len()
+    }
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::content::Timestamp;
+    use crate::content::test_util::make_arena;
+
+    fn arena(epoch: Timestamp) -> Arc<Cold> {
+        make_arena(epoch, 100, 10)
+    }
+
+    fn epochs(arenas: &[Arc<Cold>]) -> Vec<Timestamp> {
+        arenas.iter().map(|a| a.span.epoch).collect()
+    }
+
+    #[test]
+    fn diff_lists_added_and_removed() {
+        let timeline = Timeline::new(vec![arena(0), arena(100), arena(200)]);
+        let before = timeline.capture();
+
+        timeline.add(arena(300));
+        timeline.remove(100);
+        let after = timeline.capture();
+
+        let diff = before.diff(&after);
+        assert_eq!(epochs(&diff.added), [300]);
+        assert_eq!(epochs(&diff.removed), [100]);
+        assert_eq!(epochs(before.arenas()), [0, 100, 200], "unchanged by add");
+
+        let back = after.diff(&before);
+        assert_eq!(epochs(&back.added), [100]);
+        assert_eq!(epochs(&back.removed), [300]);
+
+        assert!(after.diff(&timeline.capture()).is_empty());
+    }
+
+    #[test]
+    fn diff_replaced_epoch_is_both() {
+        let timeline = Timeline::new(vec![arena(0), arena(100)]);
+        let before = timeline.capture();
+
+        timeline.remove(0);
+        timeline.add(arena(0));
+        let diff = before.diff(&timeline.capture());
+
+        assert_eq!(epochs(&diff.added), [0]);
+        assert_eq!(epochs(&diff.removed), [0]);
+        assert!(Arc::ptr_eq(&diff.removed[0], &before.arenas()[0]));
+    }
+
+    #[test]
+    fn diff_matches_arenas_sharing_an_epoch() {
+        let snapshot = |arenas: &[&Arc<Cold>]| TimelineSnapshot {
+            arenas: Arc::new(arenas.iter().map(|&a| Arc::clone(a)).collect()),
+        };
+        let (zero, a, b, c) = (arena(0), arena(100), arena(100), arena(100));
+
+        let before = snapshot(&[&zero, &a, &b]);
+        let after = snapshot(&[&zero, &b, &c]);
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.removed.len(), 1);
+        assert!(Arc::ptr_eq(&diff.removed[0], &a));
+        assert_eq!(diff.added.len(), 1);
+        assert!(Arc::ptr_eq(&diff.added[0], &c));
+
+        assert!(before.diff(&snapshot(&[&zero, &b, &a])).is_empty());
+    }
+}
@@ -0,0 +1,324 @@
+//! DataFusion [`TableProvider`] over a [`Timeline`], behind the `datafusion`
+//! feature.
+
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::catalog::Session;
+use datafusion::common::{DataFusionError, Result, ScalarValue};
+use datafusion::datasource::memory::MemorySourceConfig;
+use datafusion::datasource::{TableProvider, TableType};
+use datafusion::logical_expr::{Between, BinaryExpr, Expr, Operator, TableProviderFilterPushDown};
+use datafusion::physical_plan::ExecutionPlan;
+use tracing::trace;
+
+use super::arena::{self, ALL_COLUMNS};
+use super::{Timeline, Timestamp};
+
+/// Column that filters are pushed down on.
+const TIMESTAMP: &str = "timestamp";
+
+/// Exposes a [`Timeline`] as a table with the export schema `(uid, cid,
+/// timestamp, blob)`. Each arena scans as its own partition; filters on
+/// `timestamp` select rows by binary search, and the blob column is only
+/// materialized when projected.
+pub struct TimelineTable {
+    timeline: Arc<Timeline>,
+}
+
+impl TimelineTable {
+    pub fn new(timeline: Arc<Timeline>) -> Self {
+        Self { timeline }
+    }
+}
+
+impl fmt::Debug for TimelineTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TimelineTable")
+            .field("arenas", &self.timeline.len())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl TableProvider for TimelineTable {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        arena::schema()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        // Inexact: the range is applied per arena, DataFusion still filters
+        // rows so any predicate shape stays correct.
+        Ok(filters
+            .iter()
+            .map(|f| {
+                if TimeRange::default().narrow(f) {
+                    TableProviderFilterPushDown::Inexact
+                } else {
+                    TableProviderFilterPushDown::Unsupported
+                }
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        _limit: Option<usize>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        let mut range = TimeRange::default();
+        for filter in filters {
+            range.narrow(filter);
+        }
+
+        let columns = projection.map_or(ALL_COLUMNS.to_vec(), Clone::clone);
+        let schema = Arc::new(self.schema().project(&columns)?);
+        let batch_size = state.config().batch_size().max(1);
+
+        let mut partitions = Vec::new();
+
+        if range.start < range.end {
+            for (arena, rows) in self.timeline.rows_between(range.start, range.end) {
+                let batches = rows
+                    .clone()
+                    .step_by(batch_size)
+                    .map(|lo| arena.batch_columns(lo..(lo + batch_size).min(rows.end), &columns))
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .map_err(|e| DataFusionError::External(e.into()))?;
+
+                partitions.push(batches);
+            }
+        }
+
+        trace!(
+            start = range.start,
+            end = range.end,
+            partitions = partitions.len(),
+            "scanned timeline"
+        );
+
+        if partitions.is_empty() {
+            partitions.push(Vec::new());
+        }
+
+        let exec: Arc<dyn ExecutionPlan> =
+            MemorySourceConfig::try_new_exec(&partitions, schema, None)?;
+
+        Ok(exec)
+    }
+}
+
+/// Half-open `[start, end)` bound on `timestamp` collected from filters.
+#[derive(Debug, PartialEq, Eq)]
+struct TimeRange {
+    end: Timestamp,
+    start: Timestamp,
+}
+
+impl Default for TimeRange {
+    fn default() -> Self {
+        Self {
+            end: Timestamp::MAX,
+            start: 0,
+        }
+    }
+}
+
+impl TimeRange {
+    /// Narrows the range by `filter`. Returns false if the filter says nothing
+    /// about `timestamp` in a form we understand.
+    fn narrow(&mut self, filter: &Expr) -> bool {
+        match filter {
+            Expr::BinaryExpr(BinaryExpr {
+                left,
+                op: Operator::And,
+                right,
+            }) => {
+                // Either side alone bounds the conjunction.
+                self.narrow(left) | self.narrow(right)
+            }
+            Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
+                match (is_timestamp(left), literal(right)) {
+                    (true, Some(v)) => self.apply(*op, v),
+                    _ => match (literal(left), is_timestamp(right), op.swap()) {
+                        (Some(v), true, Some(op)) => self.apply(op, v),
+                        _ => false,
+                    },
+                }
+            }
+            Expr::Between(Between {
+                expr,
+                negated: false,
+                low,
+                high,
+            }) if is_timestamp(expr) => match (literal(low), literal(high)) {
+                (Some(low), Some(high)) => {
+                    self.apply(Operator::GtEq, low) && self.apply(Operator::LtEq, high)
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Applies `timestamp <op> value`.
+    fn apply(&mut self, op: Operator, value: i128) -> bool {
+        let clamp = |v: i128| v.clamp(0, Timestamp::MAX as i128) as Timestamp;
+
+        match op {
+            Operator::Eq => {
+                self.start = self.start.max(clamp(value));
+                self.end = self.end.min(clamp(value + 1));
+            }
+            Operator::Gt => self.start = self.start.max(clamp(value + 1)),
+            Operator::GtEq => self.start = self.start.max(clamp(value)),
+            Operator::Lt => self.end = self.end.min(clamp(value)),
+            Operator::LtEq => self.end = self.end.min(clamp(value + 1)),
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+fn is_timestamp(expr: &Expr) -> bool {
+    match expr {
+        Expr::Column(column) => column.name == TIMESTAMP,
+        // Widening casts keep the order, so the bound still holds.
+        Expr::Cast(cast) => is_timestamp(&cast.expr),
+        Expr::TryCast(cast) => is_timestamp(&cast.expr),
+        _ => false,
+    }
+}
+
+fn literal(expr: &Expr) -> Option<i128> {
+    let Expr::Literal(value, _) = expr else {
+        return None;
+    };
+
+    match *value {
+        ScalarValue::Int8(Some(v)) => Some(v as i128),
+        ScalarValue::Int16(Some(v)) => Some(v as i128),
+        ScalarValue::Int32(Some(v)) => Some(v as i128),
+        ScalarValue::Int64(Some(v)) => Some(v as i128),
+        ScalarValue::UInt8(Some(v)) => Some(v as i128),
+        ScalarValue::UInt16(Some(v)) => Some(v as i128),
+        ScalarValue::UInt32(Some(v)) => Some(v as i128),
+        ScalarValue::UInt64(Some(v)) => Some(v as i128),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::util::pretty::pretty_format_batches;
+    use datafusion::logical_expr::{col, lit};
+    use datafusion::prelude::SessionContext;
+
+    use super::*;
+    use crate::Uid;
+    use crate::content::Window;
+    use crate::content::arena::Cold;
+    use crate::content::test_util::freeze;
+
+    /// 100 entries one second apart, uids cycling through 0, 1, 2.
+    fn make_arena(epoch: u64) -> Arc<Cold> {
+        freeze(
+            Window::new(epoch, 100),
+            (0..100).map(|i| ((i % 3) as Uid, epoch * 1000 + i, epoch + i, b"blob")),
+        )
+    }
+
+    #[test]
+    fn time_range_from_filters() {
+        let narrowed = |filters: &[Expr]| {
+            let mut range = TimeRange::default();
+            let understood = filters.iter().map(|f| range.narrow(f)).collect::<Vec<_>>();
+            (range.start, range.end, understood)
+        };
+
+        assert_eq!(
+            narrowed(&[col("timestamp").between(lit(1050u64), lit(1149u64))]),
+            (1050, 1150, vec![true])
+        );
+        assert_eq!(
+            narrowed(&[
+                col("timestamp").gt(lit(10i64)),
+                lit(20i64).gt(col("timestamp")),
+                col("uid").eq(lit(1u32)),
+            ]),
+            (11, 20, vec![true, true, false])
+        );
+        assert_eq!(
+            narrowed(&[col("timestamp").lt_eq(lit(-5i64))]),
+            (0, 0, vec![true])
+        );
+    }
+
+    #[tokio::test]
+    async fn sql_over_timeline() -> anyhow::Result<()> {
+        let timeline = Arc::new(Timeline::new(vec![
+            make_arena(1000),
+            make_arena(1100),
+            make_arena(1200),
+        ]));
+
+        let ctx = SessionContext::new();
+        ctx.register_table("timeline", Arc::new(TimelineTable::new(timeline)))?;
+
+        // Rows 50..100 of the first arena and 0..50 of the second.
+        let batches = ctx
+            .sql(
+                "SELECT uid, count(*) AS n FROM timeline \
+                 WHERE timestamp BETWEEN 1050 AND 1149 \
+                 GROUP BY uid ORDER BY uid",
+            )
+            .await?
+            .collect()
+            .await?;
+
+        assert_eq!(
+            pretty_format_batches(&batches)?.to_string(),
+            [
+                "+-----+----+",
+                "| uid | n  |",
+                "+-----+----+",
+                "| 0   | 34 |",
+                "| 1   | 33 |",
+                "| 2   | 33 |",
+                "+-----+----+",
+            ]
+            .join("\n")
+        );
+
+        let blobs = ctx
+            .sql(
+                "SELECT count(*) AS n FROM timeline WHERE blob = X'626c6f62' AND timestamp >= 1290",
+            )
+            .await?
+            .collect()
+            .await?;
+
+        assert_eq!(
+            pretty_format_batches(&blobs)?.to_string(),
+            ["+----+", "| n  |", "+----+", "| 10 |", "+----+"].join("\n")
+        );
+        Ok(())
+    }
+}
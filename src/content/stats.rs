@@ -0,0 +1,162 @@
+//! Per-arena summary statistics of a [`Timeline`], and with the `serde`
+//! feature a JSON sidecar of them for catalogs that index exports.
+
+#[cfg(feature = "serde")]
+use std::fs::{self, File};
+#[cfg(feature = "serde")]
+use std::io::{BufWriter, Write};
+#[cfg(feature = "serde")]
+use std::path::Path;
+
+#[cfg(feature = "serde")]
+use anyhow::{Context, Result};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use tracing::trace;
+
+use super::{Timeline, Timestamp};
+use crate::usage::ReportUsage;
+
+/// File name of the sidecar written by [`Timeline::write_stats_sidecar`].
+pub const STATS_SIDECAR: &str = "_stats.json";
+
+/// Summary of one arena, see [`Timeline::stats`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ArenaStats {
+    /// Size of the arena's blob file; 0 if its blobs are in memory.
+    pub disk_bytes: u64,
+    pub distinct_uids: usize,
+    pub duration: u32,
+    pub epoch: Timestamp,
+    pub first_ts: Option<Timestamp>,
+    pub last_ts: Option<Timestamp>,
+    pub rows: usize,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize, Serialize)]
+struct Sidecar {
+    arenas: Vec<ArenaStats>,
+    format: String,
+}
+
+impl Timeline {
+    /// Statistics of every arena in the current snapshot, oldest first.
+    /// Counting distinct uids reads each arena's uid column.
+    pub fn stats(&self) -> Vec<ArenaStats> {
+        self.snapshot()
+            .iter()
+            .map(|arena| ArenaStats {
+                disk_bytes: arena.usage().disk,
+                distinct_uids: arena.distinct_uids(),
+                duration: arena.span.duration,
+                epoch: arena.span.epoch,
+                first_ts: arena.first_timestamp(),
+                last_ts: arena.last_timestamp(),
+                rows: arena.len(),
+            })
+            .collect()
+    }
+
+    /// Writes [`stats`](Self::stats) as JSON to [`STATS_SIDECAR`] in `dir`,
+    /// e.g. next to the parquet files of `save_dir_async`, so a catalog can
+    /// index the timeline without opening any of them.
+    #[cfg(feature = "serde")]
+    pub fn write_stats_sidecar(&self, dir: impl AsRef<Path>) -> Result<()> {
+        let dir = dir.as_ref();
+        let path = dir.join(STATS_SIDECAR);
+
+        let sidecar = Sidecar {
+            arenas: self.stats(),
+            format: "crimeline-stats-v1".to_string(),
+        };
+
+        fs::create_dir_all(dir).with_context(|| format!("create {}", dir.display()))?;
+        let file = File::create(&path).with_context(|| format!("create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        serde_json::to_writer_pretty(&mut writer, &sidecar)
+            .with_context(|| format!("write {}", path.display()))?;
+        writer
+            .flush()
+            .with_context(|| format!("flush {}", path.display()))?;
+
+        trace!(arenas = sidecar.arenas.len(), path = %path.display(), "wrote stats sidecar");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::Uid;
+    use crate::content::Window;
+    use crate::content::arena::Cold;
+    use crate::content::test_util::freeze;
+
+    fn arena(epoch: Timestamp, n: u64, authors: u64) -> Arc<Cold> {
+        freeze(
+            Window::new(epoch, 3600),
+            (0..n).map(|i| ((i % authors) as Uid, epoch + i, epoch + i * 7, b"blob")),
+        )
+    }
+
+    #[test]
+    fn stats_per_arena() {
+        let timeline = Timeline::new(vec![
+            arena(7200, 30, 3),
+            arena(0, 100, 10),
+            arena(3600, 0, 1),
+        ]);
+
+        let stats = timeline.stats();
+        assert_eq!(
+            stats.iter().map(|s| s.epoch).collect::<Vec<_>>(),
+            [0, 3600, 7200]
+        );
+        assert_eq!(
+            stats[0],
+            ArenaStats {
+                disk_bytes: stats[0].disk_bytes,
+                distinct_uids: 10,
+                duration: 3600,
+                epoch: 0,
+                first_ts: Some(0),
+                last_ts: Some(99 * 7),
+                rows: 100,
+            }
+        );
+        assert_eq!(
+            (stats[1].rows, stats[1].first_ts, stats[1].distinct_uids),
+            (0, None, 0)
+        );
+        assert_eq!(stats[2].distinct_uids, 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sidecar_rows_match_arenas() -> Result<()> {
+        let timeline = Timeline::new(vec![arena(0, 100, 10), arena(3600, 42, 5)]);
+        let dir = tempfile::tempdir()?;
+        let nested = dir.path().join("timeline");
+
+        timeline.write_stats_sidecar(&nested)?;
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&fs::read(nested.join(STATS_SIDECAR))?)?;
+        assert_eq!(json["format"], "crimeline-stats-v1");
+
+        let arenas: Vec<ArenaStats> = serde_json::from_value(json["arenas"].clone())?;
+        let rows: Vec<usize> = arenas.iter().map(|a| a.rows).collect();
+        let lens: Vec<usize> = timeline.snapshot().iter().map(|a| a.len()).collect();
+        assert_eq!(rows, lens);
+        assert_eq!(arenas, timeline.stats());
+        assert_eq!(json["arenas"][1]["distinct_uids"], 5);
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+//! Fixtures shared by the content tests.
+
+use std::sync::Arc;
+
+use super::arena::{Cold, Hot};
+use super::{Cid, Order, Timestamp, Window};
+use crate::Uid;
+
+/// Freezes `(uid, cid, timestamp, blob)` entries into an arena over `span`.
+pub(crate) fn freeze<B: AsRef<[u8]>>(
+    span: Window,
+    entries: impl IntoIterator<Item = (Uid, Cid, Timestamp, B)>,
+) -> Arc<Cold> {
+    let mut hot = Hot::new(span).unwrap();
+    for (uid, cid, ts, blob) in entries {
+        hot.add(uid, cid, ts, blob.as_ref()).unwrap();
+    }
+    hot.try_into().unwrap()
+}
+
+/// `n` entries with uid `i` and blob `"x"`, timestamps cycling through
+/// `[epoch, epoch + duration)`.
+pub(crate) fn make_arena(epoch: Timestamp, duration: u32, n: usize) -> Arc<Cold> {
+    freeze(
+        Window::new(epoch, duration),
+        (0..n).map(|i| {
+            let ts = epoch + (i as u64 % duration as u64);
+            (i as Uid, epoch * 10000 + i as u64, ts, b"x")
+        }),
+    )
+}
+
+/// Every entry of `cold` in ascending order, as `(uid, timestamp, cid, blob)`.
+pub(crate) fn rows(cold: &Cold) -> Vec<(Uid, Timestamp, Cid, Vec<u8>)> {
+    cold.iter(Order::Asc, 0)
+        .map(|e| {
+            let (cid, blob) = e.resolve();
+            (e.uid, e.timestamp(), cid, blob.to_vec())
+        })
+        .collect()
+}
@@ -1,24 +1,239 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet, hash_map};
+use std::fmt;
+use std::io::Write;
+use std::iter;
+use std::num::NonZeroU64;
+use std::ops::Range;
 use std::sync::Arc;
 
+use anyhow::{Context, Result, ensure};
 use arc_swap::ArcSwap;
+use arrow::array::RecordBatch;
+use bytes::Bytes;
+use parking_lot::RwLock;
+use parquet::file::metadata::KeyValue;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use tracing::trace;
 
-use super::arena::{Cold, Entry};
-use super::{Order, Timestamp};
+use crate::par;
+use crate::usage::{
+    BudgetViolation, ReportUsage, ReportUsageTree, Usage, UsageBudget, UsageDelta, UsageTree,
+};
+
+use super::arena::{self, ALL_COLUMNS, Cold, Entry, ExportOptions, OwnedEntry};
+use super::counters::{self, count};
+use super::{Cid, Order, Timestamp, Window};
+use crate::{Relationships, Uid, ViewerSnapshot};
+
+/// Max rows per batch from [`Timeline::record_batches`].
+const RECORD_BATCH_ROWS: usize = 8192;
+
+/// Newest arenas reported individually by [`Timeline::record_metrics`]; older
+/// ones are aggregated to bound label cardinality.
+#[cfg(feature = "metrics")]
+const METRICS_ARENA_LABELS: usize = 16;
+
+/// Returned by [`Timeline::add_checked`] when the new arena's window overlaps
+/// one already in the timeline.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct OverlapError {
+    pub conflicting: Timestamp,
+    pub epoch: Timestamp,
+}
+
+impl fmt::Display for OverlapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "arena at epoch {} overlaps existing arena at epoch {}",
+            self.epoch, self.conflicting
+        )
+    }
+}
+
+impl std::error::Error for OverlapError {}
+
+/// Returned, wrapped in an [`anyhow::Error`], by [`Timeline::from_exports`]
+/// when any buffer fails to import.
+#[derive(Debug)]
+pub struct ImportErrors {
+    /// Index of each failed buffer in the input, ascending, with its error.
+    pub failures: Vec<(usize, anyhow::Error)>,
+    pub total: usize,
+}
+
+impl fmt::Display for ImportErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} exports failed to import",
+            self.failures.len(),
+            self.total
+        )?;
+        if let Some((idx, e)) = self.failures.first() {
+            write!(f, ", first at index {idx}: {e:#}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ImportErrors {}
+
+/// Why a [`Slice`] stopped yielding entries.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub enum StopReason {
+    /// No entries left in the snapshot.
+    Exhausted,
+    /// Reached the [`Slice::limit`].
+    LimitReached,
+    /// The next entry is in an arena beyond [`Slice::max_arenas`].
+    ArenaBudget,
+    /// The next entry is at or after [`Slice::until`].
+    PastEnd,
+}
+
+/// Returned by [`Slice::drain_owned`].
+#[derive(Debug)]
+pub struct DrainResult {
+    pub entries: Vec<OwnedEntry>,
+    pub stopped: StopReason,
+}
 
 pub struct Slice {
     arena_pos: usize,
+    /// Arenas that produced an entry, counted against `max_arenas`.
+    arenas_read: usize,
+    /// `arena_pos` last counted in `arenas_read`.
+    charged: Option<usize>,
+    /// Exclusive upper bound on timestamps.
+    end: Option<Timestamp>,
     entry_pos: usize,
     first: usize,
+    limit: Option<usize>,
+    max_arenas: Option<usize>,
     n_relevant: usize,
     order: Order,
+    /// Lookahead buffered by `peek`, as `(arena_idx, entry_idx)` since an
+    /// `Entry` cannot be stored alongside the snapshot it borrows from.
+    peeked: Option<Option<(usize, usize)>>,
     skip: usize,
     snapshot: Arc<Vec<Arc<Cold>>>,
     start: Timestamp,
+    stopped: Option<StopReason>,
+    /// Entries yielded so far, across arenas.
+    yielded: usize,
+}
+
+/// A [`Slice`] narrowed to the authors one viewer sees, see
+/// [`Slice::visible_to`].
+pub struct VisibleSlice {
+    slice: Slice,
+    viewer: ViewerSnapshot,
 }
 
 pub struct Timeline {
     arenas: ArcSwap<Vec<Arc<Cold>>>,
+    /// Generation bumped on every change, see [`Timeline::subscribe`].
+    #[cfg(feature = "tokio")]
+    pub(super) changes: tokio::sync::watch::Sender<u64>,
+    cid_index: Option<RwLock<CidIndex>>,
+    /// Arena count kept by adds, see [`Timeline::rolling`].
+    max_windows: Option<usize>,
+}
+
+/// Maps each cid to the newest arena holding it.
+#[derive(Default)]
+struct CidIndex(HashMap<Cid, Holder>);
+
+/// The newest arena holding a cid, and how many entries hold it across all
+/// arenas, so dropping one holder keeps the cid while others remain.
+#[derive(Clone, Copy)]
+struct Holder {
+    /// Address of the arena, to tell apart arenas sharing an epoch.
+    arena: usize,
+    count: u32,
+    epoch: Timestamp,
+}
+
+impl Holder {
+    fn of(arena: &Arc<Cold>) -> Self {
+        Holder {
+            arena: Arc::as_ptr(arena) as usize,
+            count: 1,
+            epoch: arena.span.epoch,
+        }
+    }
+
+    fn is(&self, arena: &Arc<Cold>) -> bool {
+        self.arena == Arc::as_ptr(arena) as usize
+    }
+}
+
+impl CidIndex {
+    fn insert(&mut self, arena: &Arc<Cold>) {
+        let holder = Holder::of(arena);
+
+        for &cid in arena.cids() {
+            self.0
+                .entry(cid)
+                .and_modify(|h| {
+                    h.count += 1;
+                    if holder.epoch >= h.epoch {
+                        (h.arena, h.epoch) = (holder.arena, holder.epoch);
+                    }
+                })
+                .or_insert(holder);
+        }
+    }
+
+    /// Drops `arena`'s holds. A cid it was the newest holder of, but that is
+    /// still held, moves to the newest arena in `remaining` holding it.
+    fn remove(&mut self, arena: &Arc<Cold>, remaining: &[Arc<Cold>]) {
+        for &cid in arena.cids() {
+            let hash_map::Entry::Occupied(mut entry) = self.0.entry(cid) else {
+                continue;
+            };
+
+            let holder = entry.get_mut();
+            holder.count -= 1;
+
+            if holder.count == 0 {
+                entry.remove();
+            } else if holder.is(arena)
+                && let Some(next) = remaining.iter().rev().find(|a| a.get(cid).is_some())
+            {
+                (holder.arena, holder.epoch) = (Arc::as_ptr(next) as usize, next.span.epoch);
+            }
+        }
+    }
+
+    /// The arena in `arenas` that `cid` is indexed to.
+    fn lookup(&self, cid: Cid, arenas: &[Arc<Cold>]) -> Option<Arc<Cold>> {
+        let holder = self.0.get(&cid)?;
+        let start = arenas.partition_point(|a| a.span.epoch < holder.epoch);
+
+        arenas[start..]
+            .iter()
+            .take_while(|a| a.span.epoch == holder.epoch)
+            .find(|a| holder.is(a))
+            .cloned()
+    }
+}
+
+impl ReportUsage for CidIndex {
+    fn usage(&self) -> Usage {
+        let mut u = Usage::default();
+
+        // HashMap stores (hash, key, value) per bucket.
+        let bucket_bytes = size_of::<(Cid, Holder)>() + size_of::<u64>();
+        u.add_collection(self.0.len(), self.0.capacity(), bucket_bytes);
+
+        u
+    }
 }
 
 impl Timeline {
@@ -26,88 +241,949 @@ impl Timeline {
         arenas.sort_unstable_by_key(|a| a.span.epoch);
         Timeline {
             arenas: ArcSwap::new(Arc::new(arenas)),
+            #[cfg(feature = "tokio")]
+            changes: tokio::sync::watch::Sender::new(0),
+            cid_index: None,
+            max_windows: None,
+        }
+    }
+
+    /// Imports each parquet buffer with [`Cold::import`], in parallel with
+    /// the `rayon` feature, into a timeline sorted by epoch. Fails with
+    /// [`ImportErrors`] listing every buffer that did not import.
+    pub fn from_exports(exports: impl IntoIterator<Item = Bytes>) -> Result<Timeline> {
+        let exports: Vec<Bytes> = exports.into_iter().collect();
+        let results = par::flat_map(&exports, |i, data| [(i, Cold::import(data.clone()))]);
+
+        let mut arenas = Vec::with_capacity(exports.len());
+        let mut failures = Vec::new();
+        for (i, result) in results {
+            match result {
+                Ok(arena) => arenas.push(arena),
+                Err(e) => failures.push((i, e)),
+            }
         }
+
+        if !failures.is_empty() {
+            return Err(ImportErrors {
+                failures,
+                total: exports.len(),
+            }
+            .into());
+        }
+
+        trace!(arenas = arenas.len(), "loaded timeline from exports");
+
+        Ok(Timeline::new(arenas))
     }
 
-    pub fn add(&self, arena: Arc<Cold>) {
-        self.arenas.rcu(|current| {
-            let mut next = (**current).clone();
-            let pos = next
-                .binary_search_by_key(&arena.span.epoch, |a| a.span.epoch)
-                .unwrap_or_else(|i| i);
-            next.insert(pos, Arc::clone(&arena));
-            next
-        });
+    /// An empty timeline holding at most `max_windows` arenas: every add
+    /// that goes past it drops the oldest arenas in the same swap. Adding an
+    /// arena older than all held ones when full drops it right away.
+    pub fn rolling(max_windows: usize) -> Self {
+        Timeline {
+            max_windows: Some(max_windows),
+            ..Timeline::new(Vec::new())
+        }
+    }
+
+    /// `arenas` with `arena` inserted by epoch, minus the oldest ones past
+    /// `max_windows`, which are moved to `evicted`.
+    fn with_inserted(
+        &self,
+        arenas: &[Arc<Cold>],
+        arena: &Arc<Cold>,
+        evicted: &mut Vec<Arc<Cold>>,
+    ) -> Vec<Arc<Cold>> {
+        let mut next = arenas.to_vec();
+        let pos = next
+            .binary_search_by_key(&arena.span.epoch, |a| a.span.epoch)
+            .unwrap_or_else(|i| i);
+        next.insert(pos, Arc::clone(arena));
+
+        let excess = self
+            .max_windows
+            .map_or(0, |max| next.len().saturating_sub(max));
+        *evicted = next.drain(..excess).collect();
+
+        next
+    }
+
+    /// The current arena list, sorted by epoch.
+    pub(super) fn snapshot(&self) -> Arc<Vec<Arc<Cold>>> {
+        self.arenas.load_full()
+    }
+
+    fn notify(&self) {
+        #[cfg(feature = "tokio")]
+        self.changes.send_modify(|generation| *generation += 1);
+    }
+
+    /// Maintains a timeline-wide cid index on [`add`](Self::add) and
+    /// [`remove`](Self::remove), enabling [`arena_for_cid`](Self::arena_for_cid).
+    pub fn with_cid_index(mut self) -> Self {
+        let mut index = CidIndex::default();
+        for arena in self.arenas.load().iter() {
+            index.insert(arena);
+        }
+
+        self.cid_index = Some(RwLock::new(index));
+        self
+    }
+
+    /// Returns the newest arena holding `cid`, or `None` if it is unknown or
+    /// the timeline has no cid index.
+    pub fn arena_for_cid(&self, cid: Cid) -> Option<Arc<Cold>> {
+        // Writers swap arenas under the index lock, so both agree here.
+        let index = self.cid_index.as_ref()?.read();
+
+        index.lookup(cid, &self.arenas.load())
+    }
+
+    pub fn add(&self, arena: Arc<Cold>) {
+        // Held across the swap so index and arena set change together.
+        let mut index = self.cid_index.as_ref().map(|i| i.write());
+
+        let mut evicted = Vec::new();
+
+        self.arenas
+            .rcu(|current| self.with_inserted(current, &arena, &mut evicted));
+
+        if let Some(index) = index.as_mut() {
+            index.insert(&arena);
+        }
+        self.forget(index.as_deref_mut(), &evicted);
+        self.notify();
+        count!(counters::TIMELINE_ARENAS_ADDED);
+        trace!(len = self.len(), "added arena to timeline");
+    }
+
+    /// Swaps every arena at `arena`'s epoch for `arena` in one step, so
+    /// readers see either the old window or the new one, never both.
+    pub(super) fn replace(&self, arena: Arc<Cold>) {
+        let mut index = self.cid_index.as_ref().map(|i| i.write());
+        let mut dropped = Vec::new();
+
+        self.arenas.rcu(|current| {
+            let (replaced, kept): (Vec<_>, Vec<_>) = current
+                .iter()
+                .cloned()
+                .partition(|a| a.span.epoch == arena.span.epoch);
+            let mut evicted = Vec::new();
+            let next = self.with_inserted(&kept, &arena, &mut evicted);
+            dropped = replaced.into_iter().chain(evicted).collect();
+            next
+        });
+
+        if let Some(index) = index.as_mut() {
+            index.insert(&arena);
+        }
+        self.forget(index.as_deref_mut(), &dropped);
+        self.notify();
+        count!(counters::TIMELINE_ARENAS_ADDED);
+        trace!(len = self.len(), "replaced arena in timeline");
+    }
+
+    /// Drops arenas no longer in the timeline from the cid index, if any,
+    /// and counts them. Called after the swap, with the index lock held.
+    fn forget(&self, index: Option<&mut CidIndex>, dropped: &[Arc<Cold>]) {
+        if let Some(index) = index {
+            let remaining = self.snapshot();
+            for arena in dropped {
+                index.remove(arena, &remaining);
+            }
+        }
+
+        if !dropped.is_empty() {
+            count!(counters::TIMELINE_ARENAS_REMOVED, dropped.len());
+            trace!(dropped = dropped.len(), "dropped arenas");
+        }
+    }
+
+    /// Exports `uid`'s rows from all arenas into a single parquet whose
+    /// window spans the timeline, merged into `(timestamp, cid)` order so
+    /// overlapping arenas still import. See [`Cold::export_uid`].
+    pub fn export_uid<T: Write + Send>(
+        &self,
+        uid: Uid,
+        writer: T,
+        options: &ExportOptions,
+    ) -> Result<u64> {
+        let arenas = self.arenas.load();
+
+        let span = match (
+            arenas.first(),
+            arenas.iter().map(|a| a.span.end_exclusive()).max(),
+        ) {
+            (Some(first), Some(end)) => {
+                let duration = u32::try_from(end - first.span.epoch)
+                    .context("timeline span exceeds a single arena window")?;
+                Window::new(first.span.epoch, duration)
+            }
+            _ => Window::new(0, 0),
+        };
+
+        let flag = KeyValue::new("crimeline.uid".into(), Some(uid.to_string()));
+        let mut pq = Cold::parquet_writer(writer, &span, vec![flag], options)?;
+
+        let n = arena::write_merged(
+            &mut pq,
+            arenas.iter().map(|a| (&**a, a.rows_of(uid))),
+            options.batch_rows,
+        )?;
+
+        pq.close().context("close parquet writer")?;
+
+        trace!(
+            uid,
+            len = n,
+            arenas = arenas.len(),
+            "exported uid from timeline"
+        );
+
+        Ok(n as u64)
+    }
+
+    /// Streams entries with timestamps in `[start, end)` as record batches,
+    /// oldest arena first. Batches never span arenas. See
+    /// [`Cold::record_batches`].
+    pub fn record_batches(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> impl Iterator<Item = Result<RecordBatch>> {
+        let mut chunks = Vec::new();
+
+        for (arena, rows) in self.rows_between(start, end) {
+            for lo in rows.clone().step_by(RECORD_BATCH_ROWS) {
+                let hi = (lo + RECORD_BATCH_ROWS).min(rows.end);
+                chunks.push((Arc::clone(&arena), lo..hi));
+            }
+        }
+
+        chunks
+            .into_iter()
+            .map(|(arena, rows)| arena.batch_columns(rows, &ALL_COLUMNS))
+    }
+
+    /// Each arena with entries in `[start, end)` and the range of those rows,
+    /// oldest first, from a single snapshot.
+    pub(super) fn rows_between(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+    ) -> Vec<(Arc<Cold>, Range<usize>)> {
+        self.snapshot()
+            .iter()
+            .filter(|a| a.span.end_exclusive() > start && a.span.epoch < end)
+            .map(|a| (Arc::clone(a), a.rows_between(start, end)))
+            .filter(|(_, rows)| !rows.is_empty())
+            .collect()
+    }
+
+    /// Splits into `(older, newer)` timelines at `cutoff`. Arenas straddling
+    /// the cutoff are repartitioned so each side gets its entries.
+    pub fn split_at(&self, cutoff: Timestamp) -> Result<(Timeline, Timeline)> {
+        let mut older = Vec::new();
+        let mut newer = Vec::new();
+
+        for arena in self.arenas.load().iter() {
+            if arena.span.end_exclusive() <= cutoff {
+                older.push(Arc::clone(arena));
+            } else if arena.span.epoch >= cutoff {
+                newer.push(Arc::clone(arena));
+            } else {
+                let (before, after) = arena.repartition(cutoff)?;
+                older.push(before);
+                newer.push(after);
+            }
+        }
+
+        trace!(
+            cutoff,
+            older = older.len(),
+            newer = newer.len(),
+            "split timeline"
+        );
+
+        Ok((Timeline::new(older), Timeline::new(newer)))
+    }
+
+    /// Emits the timeline's total usage under `prefix` and per-arena usage
+    /// under `{prefix}_arena`, labeled by `epoch`. Only the newest arenas get
+    /// their own label, older ones are summed under `epoch="older"`.
+    #[cfg(feature = "metrics")]
+    pub fn record_metrics(&self, prefix: &str) {
+        use crate::usage::{record_metrics, record_metrics_labeled};
+
+        let arenas = self.arenas.load();
+        let mut total = snapshot_usage(&arenas);
+        let mut older = Usage::default();
+
+        if let Some(index) = &self.cid_index {
+            total += index.read().usage();
+        }
+
+        let arena_prefix = format!("{prefix}_arena");
+        let split = arenas.len().saturating_sub(METRICS_ARENA_LABELS);
+
+        for (i, arena) in arenas.iter().enumerate() {
+            let u = arena.usage();
+            total.observe(&u);
+
+            if i < split {
+                older.observe(&u);
+            } else {
+                let epoch = metrics::Label::new("epoch", arena.span.epoch.to_string());
+                record_metrics_labeled(&arena_prefix, &u, vec![epoch]);
+            }
+        }
+
+        if split > 0 {
+            let epoch = metrics::Label::new("epoch", "older");
+            record_metrics_labeled(&arena_prefix, &older, vec![epoch]);
+        }
+
+        record_metrics(prefix, &total);
+    }
+
+    /// Shorthand for `self.usage().diff(prior)`.
+    pub fn usage_delta_since(&self, prior: &Usage) -> UsageDelta {
+        self.usage().diff(prior)
+    }
+
+    /// The time covered by arenas as sorted `[start, end)` intervals, merging
+    /// windows that overlap or touch. Gaps show up between intervals.
+    pub fn coverage(&self) -> Vec<(Timestamp, Timestamp)> {
+        let mut merged: Vec<(Timestamp, Timestamp)> = Vec::new();
+
+        for span in self.arenas.load().iter().map(|a| &a.span) {
+            let (start, end) = (span.epoch, span.end_exclusive());
+            if start == end {
+                continue;
+            }
+
+            match merged.last_mut() {
+                Some((_, last)) if start <= *last => *last = (*last).max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        merged
+    }
+
+    /// Each arena's epoch and usage, oldest first, from a single snapshot.
+    pub fn usage_per_arena(&self) -> Vec<(Timestamp, Usage)> {
+        self.arenas
+            .load()
+            .iter()
+            .map(|arena| (arena.span.epoch, arena.usage()))
+            .collect()
+    }
+
+    /// Checks the timeline's total usage against `budget`, see [`Usage::check`].
+    pub fn check_budget(&self, budget: &UsageBudget) -> Vec<BudgetViolation> {
+        self.usage().check(budget)
+    }
+
+    /// Like [`add`](Self::add), but rejects an arena whose window overlaps
+    /// any arena already in the timeline.
+    pub fn add_checked(&self, arena: Arc<Cold>) -> Result<(), OverlapError> {
+        let mut conflict = None;
+        let mut evicted = Vec::new();
+        let mut index = self.cid_index.as_ref().map(|i| i.write());
+
+        self.arenas.rcu(|current| {
+            conflict = current
+                .iter()
+                .find(|a| a.span.overlaps(&arena.span))
+                .map(|a| a.span.epoch);
+
+            if conflict.is_some() {
+                return Arc::clone(current);
+            }
+
+            Arc::new(self.with_inserted(current, &arena, &mut evicted))
+        });
+
+        if let Some(conflicting) = conflict {
+            return Err(OverlapError {
+                conflicting,
+                epoch: arena.span.epoch,
+            });
+        }
+
+        if let Some(index) = index.as_mut() {
+            index.insert(&arena);
+        }
+        self.forget(index.as_deref_mut(), &evicted);
+        self.notify();
+        count!(counters::TIMELINE_ARENAS_ADDED);
+        trace!(len = self.len(), "added arena to timeline");
+        Ok(())
+    }
+
+    /// Checks that arenas are sorted by epoch and that no two windows overlap,
+    /// which [`add_checked`](Self::add_checked) guarantees but unchecked
+    /// [`add`](Self::add) does not. An overlap is reported as an
+    /// [`OverlapError`] naming both epochs.
+    pub fn validate(&self) -> Result<()> {
+        let arenas = self.snapshot();
+
+        for pair in arenas.windows(2) {
+            ensure!(
+                pair[0].span.epoch <= pair[1].span.epoch,
+                "arena at epoch {} sorts after arena at epoch {}",
+                pair[0].span.epoch,
+                pair[1].span.epoch
+            );
+        }
+
+        // With sorted epochs, anything overlapping an earlier arena also
+        // overlaps the one reaching furthest so far.
+        let mut furthest: Option<&Window> = None;
+
+        for span in arenas.iter().map(|a| &a.span) {
+            if let Some(prev) = furthest
+                && prev.overlaps(span)
+            {
+                return Err(OverlapError {
+                    conflicting: prev.epoch,
+                    epoch: span.epoch,
+                }
+                .into());
+            }
+
+            if span.duration > 0
+                && furthest.is_none_or(|f| span.end_exclusive() > f.end_exclusive())
+            {
+                furthest = Some(span);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&self, epoch: Timestamp) {
+        let mut index = self.cid_index.as_ref().map(|i| i.write());
+        let mut removed = Vec::new();
+
+        self.arenas.rcu(|current| {
+            let (gone, kept): (Vec<_>, Vec<_>) =
+                current.iter().cloned().partition(|a| a.span.epoch == epoch);
+            removed = gone;
+            kept
+        });
+
+        self.forget(index.as_deref_mut(), &removed);
+        self.notify();
+        trace!(epoch, len = self.len(), "removed arena from timeline");
+    }
+
+    /// Drops the oldest arenas until the `disk` usage of the blob stores
+    /// they resolve through is at most `max_disk_bytes`. A store shared by
+    /// several arenas, from [`Cold::remap_uids`] or a [`BlobPool`](super::BlobPool),
+    /// counts once and is only freed with the last of them. Usage is taken
+    /// from the same snapshot the arenas are removed from, in one swap.
+    /// Returns the number evicted.
+    pub fn enforce_budget(&self, max_disk_bytes: u64) -> usize {
+        let mut index = self.cid_index.as_ref().map(|i| i.write());
+        let mut evicted = Vec::new();
+
+        self.arenas.rcu(|current| {
+            // Arenas still resolving through each store, and its size.
+            let mut stores: HashMap<*const (), (usize, u64)> = HashMap::new();
+            for arena in current.iter() {
+                let (store, disk) = arena.blob_backing();
+                stores.entry(store).or_insert((0, disk)).0 += 1;
+            }
+
+            let mut total: u64 = stores.values().map(|&(_, disk)| disk).sum();
+            let n = current
+                .iter()
+                .take_while(|a| {
+                    let over = total > max_disk_bytes;
+                    let (users, disk) = stores.get_mut(&a.blob_backing().0).unwrap();
+                    *users -= 1;
+                    if *users == 0 {
+                        total -= *disk;
+                    }
+                    over
+                })
+                .count();
+
+            evicted = current[..n].to_vec();
+            current[n..].to_vec()
+        });
+
+        self.forget(index.as_deref_mut(), &evicted);
+        if !evicted.is_empty() {
+            self.notify();
+        }
+        trace!(
+            evicted = evicted.len(),
+            len = self.len(),
+            max_disk_bytes,
+            "enforced timeline disk budget"
+        );
+
+        evicted.len()
+    }
+
+    /// Counts entries at or after `start` in buckets of `bucket_secs`, aligned
+    /// to multiples of the bucket width, as `(bucket start, count)`. Buckets
+    /// are emitted in ascending order from the first to the last populated
+    /// bucket, including empty ones in between; filter on `count > 0` to
+    /// drop those.
+    pub fn bucketed(
+        &self,
+        start: Timestamp,
+        bucket_secs: NonZeroU64,
+    ) -> impl Iterator<Item = (Timestamp, usize)> + use<> {
+        let bucket_secs = bucket_secs.get();
+        let snapshot = self.arenas.load();
+        let mut counts: BTreeMap<Timestamp, usize> = BTreeMap::new();
+
+        for arena in snapshot.iter().filter(|a| a.span.end_exclusive() > start) {
+            let skip = Self::compute_skip(arena, start);
+
+            for &rel in &arena.timestamps[skip..] {
+                let ts = arena.span.convert_to_absolute(rel);
+                *counts.entry(ts - ts % bucket_secs).or_default() += 1;
+            }
+        }
+
+        trace!(buckets = counts.len(), "bucketed timeline");
+
+        let last = counts.last_key_value().map(|(&bucket, _)| bucket);
+        let first = counts.first_key_value().map(|(&bucket, _)| bucket);
+
+        iter::successors(first, move |bucket| bucket.checked_add(bucket_secs))
+            .take_while(move |&bucket| Some(bucket) <= last)
+            .map(move |bucket| (bucket, counts.get(&bucket).copied().unwrap_or(0)))
+    }
+
+    /// Iterates every entry in `order`, without a lower bound.
+    pub fn iter_all(&self, order: Order) -> Slice {
+        let snapshot = self.snapshot();
+        count!(counters::SLICES_OPENED);
+
+        Slice {
+            n_relevant: snapshot.len(),
+            snapshot,
+            first: 0,
+            arena_pos: 0,
+            arenas_read: 0,
+            charged: None,
+            end: None,
+            entry_pos: 0,
+            limit: None,
+            max_arenas: None,
+            order,
+            peeked: None,
+            skip: 0,
+            start: 0,
+            stopped: None,
+            yielded: 0,
+        }
+    }
+
+    /// Iterates entries at or after `start` in `order`. See
+    /// [`iter_all`](Self::iter_all) for an unbounded iteration.
+    pub fn iter(&self, start: Timestamp, order: Order) -> Slice {
+        count!(counters::SLICES_OPENED);
+
+        let mut slice = Slice {
+            snapshot: self.snapshot(),
+            first: 0,
+            n_relevant: 0,
+            arena_pos: 0,
+            arenas_read: 0,
+            charged: None,
+            end: None,
+            entry_pos: 0,
+            limit: None,
+            max_arenas: None,
+            order,
+            peeked: None,
+            skip: 0,
+            start,
+            stopped: None,
+            yielded: 0,
+        };
+
+        slice.seek(start);
+        slice
+    }
+
+    /// Arenas with entries at or after `start`, oldest first, each with the
+    /// number of its entries before `start`. Arenas are independent, so each
+    /// can go to its own worker to read with [`Cold::iter`]`(Order::Asc,
+    /// start)`.
+    pub fn chunks(&self, start: Timestamp) -> impl Iterator<Item = (Arc<Cold>, usize)> {
+        let snapshot = self.snapshot();
+
+        (0..snapshot.len())
+            .map(move |i| Arc::clone(&snapshot[i]))
+            .filter(move |arena| arena.span.end_exclusive() > start)
+            .map(move |arena| {
+                let skip = Timeline::compute_skip(&arena, start);
+                (arena, skip)
+            })
+    }
+
+    /// The `top_k` entries at or after `start` with the highest `score`,
+    /// best first; ties go to the newer entry. `score` sees metadata only and
+    /// a heap of `top_k` candidates is kept, so blobs are resolved for the
+    /// winners alone and nothing is sorted beyond them.
+    pub fn iter_scored(
+        &self,
+        start: Timestamp,
+        score: impl Fn(Uid, Cid, Timestamp) -> i64,
+        top_k: usize,
+    ) -> Vec<OwnedEntry> {
+        if top_k == 0 {
+            return Vec::new();
+        }
+
+        let arenas: Vec<(Arc<Cold>, usize)> = self.chunks(start).collect();
+
+        // Min-heap on (score, arena, row): later positions are newer.
+        let mut heap = BinaryHeap::with_capacity(top_k);
+        for (pos, (arena, skip)) in arenas.iter().enumerate() {
+            for (idx, &cid) in arena.cids().iter().enumerate().skip(*skip) {
+                let entry = Entry::new(arena, idx);
+                let key = (score(entry.uid, cid, entry.timestamp()), pos, idx);
+
+                if heap.len() < top_k {
+                    heap.push(Reverse(key));
+                } else if let Some(mut min) = heap.peek_mut()
+                    && key > min.0
+                {
+                    *min = Reverse(key);
+                }
+            }
+        }
+
+        trace!(top_k, kept = heap.len(), "scored timeline entries");
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((_, pos, idx))| OwnedEntry::from(&Entry::new(&arenas[pos].0, idx)))
+            .collect()
+    }
+
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    pub fn is_empty(&self) -> bool {
+        self.arenas.load().is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.arenas.load().len()
+    }
+
+    /// Resolves the blob for `cid` from whichever arena holds it, scanning
+    /// arenas newest-first and returning the first hit.
+    pub fn resolve(&self, cid: Cid) -> Option<(Timestamp, Vec<u8>)> {
+        let snapshot = self.arenas.load();
+
+        let found = snapshot.iter().rev().find_map(|arena| {
+            arena.get(cid).map(|entry| {
+                let (_, blob) = entry.resolve();
+                (entry.timestamp(), blob.to_vec())
+            })
+        });
+
+        trace!(cid, found = found.is_some(), "resolved cid across timeline");
+
+        found
+    }
+
+    /// Cids stored more than once across (or within) arenas, with their
+    /// occurrence counts, in ascending cid order.
+    pub fn duplicate_cids(&self) -> Vec<(Cid, usize)> {
+        let mut counts: HashMap<Cid, usize> = HashMap::new();
+
+        for arena in self.snapshot().iter() {
+            for &cid in arena.cids() {
+                *counts.entry(cid).or_default() += 1;
+            }
+        }
+
+        let mut dups: Vec<_> = counts.into_iter().filter(|&(_, n)| n > 1).collect();
+        dups.sort_unstable();
+
+        trace!(dups = dups.len(), "counted duplicate cids");
+        dups
+    }
+
+    /// Cids held here but absent from `peer`, each once, in arena order.
+    /// Streams over the arenas' cid columns; only the difference is
+    /// collected.
+    pub fn cids_not_in(&self, peer: &HashSet<Cid>) -> Vec<Cid> {
+        let mut seen = HashSet::new();
+
+        let out: Vec<Cid> = self
+            .snapshot()
+            .iter()
+            .flat_map(|arena| arena.cids().iter().copied())
+            .filter(|cid| !peer.contains(cid) && seen.insert(*cid))
+            .collect();
+
+        trace!(
+            peer = peer.len(),
+            len = out.len(),
+            "collected cids not in peer"
+        );
+
+        out
+    }
+
+    /// Cids in `peer` held by no arena here, sorted. Uses the cid index if
+    /// present, otherwise one pass over the arenas that only remembers hits,
+    /// so memory is bounded by `peer`.
+    pub fn cids_missing(&self, peer: &HashSet<Cid>) -> Vec<Cid> {
+        let mut out: Vec<Cid> = match &self.cid_index {
+            Some(index) => {
+                let index = index.read();
+                peer.iter()
+                    .copied()
+                    .filter(|cid| !index.0.contains_key(cid))
+                    .collect()
+            }
+            None => {
+                let found: HashSet<Cid> = self
+                    .snapshot()
+                    .iter()
+                    .flat_map(|arena| arena.cids().iter().copied())
+                    .filter(|cid| peer.contains(cid))
+                    .collect();
+                peer.difference(&found).copied().collect()
+            }
+        };
+        out.sort_unstable();
+
+        trace!(
+            peer = peer.len(),
+            len = out.len(),
+            "collected cids missing from timeline"
+        );
+
+        out
+    }
+
+    fn compute_skip(arena: &Cold, start: Timestamp) -> usize {
+        let rel = arena.span.relative_bound(start);
+
+        arena.timestamps.partition_point(|&ts| ts < rel)
+    }
+}
+
+/// Overhead of the snapshot itself: the `ArcSwap`, the shared list and the
+/// `Arc` allocation of every arena in it.
+fn snapshot_usage(arenas: &Vec<Arc<Cold>>) -> Usage {
+    let mut u = Usage::new("snapshot", arenas.capacity() * size_of::<Arc<Cold>>());
+    u.add_arcswap_overhead::<Vec<Arc<Cold>>>();
+    u.add_arc_overhead::<Cold>(arenas.len());
+    u
+}
+
+impl ReportUsage for Timeline {
+    fn usage(&self) -> Usage {
+        let arenas = self.arenas.load();
+        let mut u = snapshot_usage(&arenas);
+
+        if let Some(index) = &self.cid_index {
+            u += index.read().usage();
+        }
+
+        for arena in arenas.iter() {
+            u.observe_named(arena.span.epoch.to_string(), &**arena);
+        }
+
+        u
+    }
+}
+
+impl ReportUsageTree for Timeline {
+    fn usage_tree(&self) -> UsageTree {
+        let arenas = self.arenas.load();
+        let mut tree = UsageTree::new("timeline", snapshot_usage(&arenas));
+
+        if let Some(index) = &self.cid_index {
+            let u = index.read().usage();
+            tree.usage += u.clone();
+            tree = tree.with_child(UsageTree::new("cid index", u));
+        }
+
+        for arena in arenas.iter() {
+            tree.observe_child(format!("arena {}", arena.span.epoch), &**arena);
+        }
+
+        tree
+    }
+}
+
+impl VisibleSlice {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Entry<'_>> {
+        let viewer = &self.viewer;
+        self.slice.next_where(|uid| viewer.shows(uid))
+    }
+}
+
+impl Slice {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Entry<'_>> {
+        self.next_where(|_| true)
+    }
+
+    /// Stops after `n` entries.
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Excludes entries at or after `end`. Ascending slices stop at the first
+    /// such entry; descending ones skip past them.
+    pub fn until(mut self, end: Timestamp) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Stops before reading entries from more than `n` arenas.
+    pub fn max_arenas(mut self, n: usize) -> Self {
+        self.max_arenas = Some(n);
+        self
+    }
+
+    /// Why the slice last returned `None`, or `None` if it has not.
+    pub fn stopped(&self) -> Option<StopReason> {
+        self.stopped
+    }
+
+    /// Collects the remaining entries, with why iteration stopped. A client
+    /// paginating with [`limit`](Self::limit) should fetch more unless the
+    /// reason is [`StopReason::Exhausted`] or [`StopReason::PastEnd`].
+    pub fn drain_owned(&mut self) -> DrainResult {
+        let mut entries = Vec::new();
+
+        while let Some(entry) = self.next() {
+            entries.push(OwnedEntry::from(&entry));
+        }
+
+        DrainResult {
+            entries,
+            stopped: self.stopped.unwrap_or(StopReason::Exhausted),
+        }
+    }
+
+    /// Yields only entries whose author `viewer` follows and does not block.
+    /// Both lists are copied up front, so iterating takes no locks and
+    /// skipped entries never touch their blobs.
+    pub fn visible_to(self, rels: Arc<Relationships>, viewer: Uid) -> VisibleSlice {
+        VisibleSlice {
+            slice: self,
+            viewer: rels.viewer_snapshot(viewer),
+        }
+    }
+
+    /// Next entry whose uid passes `keep`.
+    fn next_where(&mut self, mut keep: impl FnMut(Uid) -> bool) -> Option<Entry<'_>> {
+        loop {
+            let (arena_idx, idx) = match self.peeked.take() {
+                Some(peeked) => peeked?,
+                None => self.advance()?,
+            };
+
+            if keep(Entry::new(&self.snapshot[arena_idx], idx).uid) {
+                self.yielded += 1;
+                count!(counters::SLICE_ENTRIES_YIELDED);
+
+                return Some(Entry::new(&self.snapshot[arena_idx], idx));
+            }
+        }
+    }
+
+    /// Like [`next`](Self::next), but also returns the entry's position in
+    /// this slice, counting from 0 across arena boundaries.
+    pub fn next_ranked(&mut self) -> Option<(usize, Entry<'_>)> {
+        let rank = self.yielded;
+
+        self.next().map(|entry| (rank, entry))
+    }
+
+    /// Folds the remaining entries without collecting them.
+    pub fn fold_entries<B>(&mut self, init: B, mut f: impl FnMut(B, Entry<'_>) -> B) -> B {
+        let mut acc = init;
+
+        while let Some(entry) = self.next() {
+            acc = f(acc, entry);
+        }
 
-        trace!(len = self.len(), "added arena to timeline");
+        acc
     }
 
-    pub fn remove(&self, epoch: Timestamp) {
-        self.arenas.rcu(|current| {
-            let mut next = (**current).clone();
-            next.retain(|a| a.span.epoch != epoch);
-            next
-        });
+    /// Returns the entry the following `next` will yield, without consuming it.
+    pub fn peek(&mut self) -> Option<Entry<'_>> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.advance());
+        }
 
-        trace!(epoch, len = self.len(), "removed arena from timeline");
+        let (arena_idx, idx) = self.peeked.flatten()?;
+
+        Some(Entry::new(&self.snapshot[arena_idx], idx))
     }
 
-    pub fn iter(&self, start: Timestamp, order: Order) -> Slice {
-        let snapshot = self.arenas.load_full();
+    /// Repositions the slice as if freshly created by
+    /// [`Timeline::iter`]`(ts, order)`, against the same snapshot. Works in
+    /// either direction; ranks from [`next_ranked`](Self::next_ranked)
+    /// restart at 0.
+    pub fn seek(&mut self, ts: Timestamp) {
+        let snapshot = &self.snapshot;
 
-        // Arenas are kept sorted by epoch. Find first with entries past `start`.
+        // Arenas are kept sorted by epoch. Find first with entries past `ts`.
         let first = (0..snapshot.len())
-            .find(|&i| snapshot[i].span.end_exclusive() > start)
+            .find(|&i| snapshot[i].span.end_exclusive() > ts)
             .unwrap_or(snapshot.len());
         let n_relevant = snapshot.len() - first;
 
         let skip = if n_relevant > 0 {
-            let initial = match order {
+            let initial = match self.order {
                 Order::Asc => first,
                 Order::Desc => snapshot.len() - 1,
             };
-            Self::compute_skip(&snapshot[initial], start)
+            Timeline::compute_skip(&snapshot[initial], ts)
         } else {
             0
         };
 
-        Slice {
-            snapshot,
-            first,
-            n_relevant,
-            arena_pos: 0,
-            entry_pos: 0,
-            order,
-            skip,
-            start,
-        }
-    }
-
-    #[cfg_attr(coverage_nightly, coverage(off))]
-    pub fn is_empty(&self) -> bool {
-        self.arenas.load().is_empty()
+        self.first = first;
+        self.n_relevant = n_relevant;
+        self.skip = skip;
+        self.start = ts;
+        self.arena_pos = 0;
+        self.arenas_read = 0;
+        self.charged = None;
+        self.entry_pos = 0;
+        self.peeked = None;
+        self.stopped = None;
+        self.yielded = 0;
     }
 
-    pub fn len(&self) -> usize {
-        self.arenas.load().len()
+    fn stop(&mut self, reason: StopReason) -> Option<(usize, usize)> {
+        self.stopped = Some(reason);
+        None
     }
 
-    fn compute_skip(arena: &Cold, start: Timestamp) -> usize {
-        if start <= arena.span.epoch {
-            0
-        } else {
-            let rel = (start - arena.span.epoch) as u32;
-            arena.timestamps.partition_point(|&ts| ts < rel)
+    fn advance(&mut self) -> Option<(usize, usize)> {
+        if self.limit.is_some_and(|limit| self.yielded >= limit) {
+            return self.stop(StopReason::LimitReached);
         }
-    }
-}
 
-impl Slice {
-    #[allow(clippy::should_implement_trait)]
-    pub fn next(&mut self) -> Option<Entry<'_>> {
         loop {
             if self.arena_pos >= self.n_relevant {
-                return None;
+                return self.stop(StopReason::Exhausted);
             }
 
             let arena_idx = match self.order {
@@ -119,6 +1195,14 @@ impl Slice {
             let len = arena.len();
             let effective = len - skip;
 
+            // Descending: jump over the arena's entries at or after `end`.
+            if self.order == Order::Desc
+                && self.entry_pos == 0
+                && let Some(end) = self.end
+            {
+                self.entry_pos = len - Timeline::compute_skip(arena, end);
+            }
+
             if self.entry_pos >= effective {
                 self.arena_pos += 1;
                 self.entry_pos = 0;
@@ -139,9 +1223,25 @@ impl Slice {
                 Order::Desc => len - 1 - self.entry_pos,
             };
 
+            if self.order == Order::Asc
+                && self
+                    .end
+                    .is_some_and(|end| arena.span.convert_to_absolute(arena.timestamps[idx]) >= end)
+            {
+                return self.stop(StopReason::PastEnd);
+            }
+
+            if self.charged != Some(self.arena_pos) {
+                if self.max_arenas.is_some_and(|max| self.arenas_read >= max) {
+                    return self.stop(StopReason::ArenaBudget);
+                }
+                self.arenas_read += 1;
+                self.charged = Some(self.arena_pos);
+            }
+
             self.entry_pos += 1;
 
-            return Some(Entry::new(arena, idx));
+            return Some((arena_idx, idx));
         }
     }
 }
@@ -149,18 +1249,18 @@ impl Slice {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::content::Window;
     use crate::content::arena::Hot;
+    use crate::content::test_util::{self, make_arena};
+    use crate::content::{BlobPool, Window};
+    use bytes::Bytes;
+    use itertools::Itertools;
     use proptest::prelude::*;
+    use std::thread;
 
-    fn make_arena(epoch: u64, duration: u32, n: usize) -> Arc<Cold> {
-        let mut hot = Hot::new(Window::new(epoch, duration)).unwrap();
-        for i in 0..n {
-            let ts = epoch + (i as u64 % duration as u64);
-            hot.add(i as u32, epoch * 10000 + i as u64, ts, b"x")
-                .unwrap();
-        }
-        hot.try_into().unwrap()
+    fn drain_n(slice: &mut Slice, n: usize) -> Vec<Timestamp> {
+        (0..n)
+            .map_while(|_| slice.next().map(|e| e.timestamp()))
+            .collect()
     }
 
     fn drain(slice: &mut Slice) -> Vec<Timestamp> {
@@ -171,6 +1271,1016 @@ mod tests {
         out
     }
 
+    #[test]
+    fn add_checked_rejects_overlap() -> anyhow::Result<()> {
+        let timeline = Timeline::new(vec![make_arena(1000, 100, 5)]);
+
+        timeline.add_checked(make_arena(1100, 100, 5))?;
+        assert_eq!(timeline.len(), 2, "adjacent windows are disjoint");
+
+        let err = timeline.add_checked(make_arena(1150, 100, 5)).unwrap_err();
+        assert_eq!(
+            err,
+            OverlapError {
+                conflicting: 1100,
+                epoch: 1150,
+            }
+        );
+        assert_eq!(timeline.len(), 2, "rejected arena is not inserted");
+
+        timeline.add(make_arena(1150, 100, 5));
+        assert_eq!(timeline.len(), 3, "unchecked add still permits overlap");
+        Ok(())
+    }
+
+    /// The filtering loop from `examples/timeline.rs`, against the adaptor.
+    #[test]
+    fn visible_to_matches_manual_filter() {
+        const VIEWER: Uid = 1;
+        const TROLL: Uid = 99;
+
+        let rels = Arc::new(Relationships::new(crate::Sharding::S16));
+        for uid in [10, 20, 30] {
+            rels.follows.add(VIEWER, uid);
+        }
+        rels.follows.add(VIEWER, TROLL);
+        rels.blocks.add(VIEWER, TROLL);
+        rels.follows.add(20, 50);
+
+        let freeze = |epoch: u64, entries: &[(Uid, u64, &str)]| -> Arc<Cold> {
+            test_util::freeze(
+                Window::new(epoch, 100),
+                (0..)
+                    .zip(entries)
+                    .map(|(i, &(uid, ts, text))| (uid, epoch + i, ts, text)),
+            )
+        };
+
+        let timeline = Timeline::new(vec![
+            freeze(
+                1000,
+                &[
+                    (10, 1010, "gm from alice"),
+                    (20, 1020, "bob's hot take"),
+                    (TROLL, 1030, "blocked troll"),
+                    (50, 1040, "stranger waves"),
+                ],
+            ),
+            freeze(
+                2000,
+                &[(30, 2010, "carol ships code"), (20, 2050, "bob again")],
+            ),
+        ]);
+
+        for order in [Order::Asc, Order::Desc] {
+            let mut manual = Vec::new();
+            let mut slice = timeline.iter_all(order);
+            while let Some(e) = slice.next() {
+                if !rels.follows.contains(VIEWER, e.uid) || rels.blocks.contains(VIEWER, e.uid) {
+                    continue;
+                }
+                manual.push((e.timestamp(), e.resolve().1.to_vec()));
+            }
+
+            let mut adapted = Vec::new();
+            let mut visible = timeline
+                .iter_all(order)
+                .visible_to(Arc::clone(&rels), VIEWER);
+            while let Some(e) = visible.next() {
+                adapted.push((e.timestamp(), e.resolve().1.to_vec()));
+            }
+
+            assert_eq!(adapted, manual, "{order:?}");
+            assert_eq!(adapted.len(), 4);
+        }
+
+        // Captured once: edges added later do not show up.
+        let mut visible = timeline
+            .iter(2000, Order::Asc)
+            .visible_to(Arc::clone(&rels), VIEWER);
+        rels.blocks.add(VIEWER, 30);
+        assert_eq!(visible.next().map(|e| e.uid), Some(30));
+    }
+
+    #[test]
+    fn validate_finds_overlap_from_unchecked_add() -> anyhow::Result<()> {
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 500, 5),
+            make_arena(1500, 0, 0),
+            make_arena(1500, 100, 5),
+        ]);
+        timeline.validate()?;
+
+        // Overlaps the long first arena, not its neighbour at 1500.
+        timeline.add(make_arena(1200, 10, 5));
+        timeline.add(make_arena(1300, 10, 5));
+
+        let err = timeline.validate().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<OverlapError>(),
+            Some(&OverlapError {
+                conflicting: 1000,
+                epoch: 1200,
+            })
+        );
+
+        timeline.remove(1000);
+        timeline.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn bucketed_counts() -> anyhow::Result<()> {
+        let mut first = Hot::new(Window::new(1000, 100))?;
+        for (cid, ts) in [(1, 1000), (2, 1005), (3, 1009), (4, 1010), (5, 1035)] {
+            first.add(0, cid, ts, b"x")?;
+        }
+
+        let mut second = Hot::new(Window::new(1100, 100))?;
+        for (cid, ts) in [(6, 1100), (7, 1119)] {
+            second.add(0, cid, ts, b"x")?;
+        }
+
+        let timeline = Timeline::new(vec![first.try_into()?, second.try_into()?]);
+
+        let ten = NonZeroU64::new(10).unwrap();
+        let all: Vec<_> = timeline.bucketed(0, ten).collect();
+        assert_eq!(all.len(), 12, "1000..=1110 in steps of 10");
+        assert_eq!(all[0], (1000, 3));
+        assert_eq!(all[1], (1010, 1));
+        assert_eq!(all[2], (1020, 0));
+        assert_eq!(all[3], (1030, 1));
+        assert_eq!(all[10], (1100, 1));
+        assert_eq!(all[11], (1110, 1));
+        assert_eq!(all.iter().map(|&(_, n)| n).sum::<usize>(), 7);
+
+        let populated: Vec<_> = timeline
+            .bucketed(1005, ten)
+            .filter(|&(_, n)| n > 0)
+            .collect();
+        assert_eq!(
+            populated,
+            vec![(1000, 2), (1010, 1), (1030, 1), (1100, 1), (1110, 1)],
+            "start skips the entry at 1000",
+        );
+
+        assert_eq!(Timeline::new(vec![]).bucketed(0, ten).count(), 0);
+
+        let widest: Vec<_> = timeline.bucketed(0, NonZeroU64::MAX).collect();
+        assert_eq!(widest, [(0, 7)]);
+        Ok(())
+    }
+
+    #[test]
+    fn fold_entries_sums_blob_lengths() -> anyhow::Result<()> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..20u64 {
+            hot.add(i as crate::Uid, i, 1000 + i, &vec![0u8; i as usize])?;
+        }
+
+        let timeline = Timeline::new(vec![hot.try_into()?, make_arena(1100, 10, 5)]);
+
+        let mut expected = 0;
+        let mut slice = timeline.iter(1005, Order::Desc);
+        while let Some(e) = slice.next() {
+            expected += e.resolve().1.len();
+        }
+
+        let folded = timeline
+            .iter(1005, Order::Desc)
+            .fold_entries(0, |acc, e| acc + e.resolve().1.len());
+
+        assert_eq!(folded, expected);
+        assert_eq!(
+            folded,
+            (5..20).sum::<usize>() + 5,
+            "make_arena blobs are 1 byte"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn next_ranked_counts_across_arenas() {
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 100, 4),
+            make_arena(1100, 100, 0),
+            make_arena(1200, 100, 3),
+        ]);
+
+        for order in [Order::Asc, Order::Desc] {
+            let mut slice = timeline.iter_all(order);
+            assert!(slice.peek().is_some());
+
+            let mut ranks = Vec::new();
+            while let Some((rank, _)) = slice.next_ranked() {
+                ranks.push(rank);
+            }
+
+            assert_eq!(ranks, (0..7).collect::<Vec<_>>(), "{order:?}");
+        }
+
+        let mut slice = timeline.iter(1202, Order::Asc);
+        assert_eq!(
+            slice.next_ranked().map(|(r, e)| (r, e.timestamp())),
+            Some((0, 1202))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn overlap_error_serde_roundtrip() -> serde_json::Result<()> {
+        let e = OverlapError {
+            conflicting: 1000,
+            epoch: 1050,
+        };
+        let json = serde_json::to_value(&e)?;
+        assert_eq!(
+            json,
+            serde_json::json!({"conflicting": 1000, "epoch": 1050})
+        );
+        assert_eq!(serde_json::from_value::<OverlapError>(json)?, e);
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_merges_touching_and_overlapping() {
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 100, 1),
+            make_arena(1100, 50, 1),
+            make_arena(1120, 100, 1),
+            make_arena(1130, 10, 1),
+            make_arena(1500, 100, 1),
+            make_arena(1700, 0, 0),
+        ]);
+
+        assert_eq!(timeline.coverage(), [(1000, 1220), (1500, 1600)]);
+        assert!(Timeline::new(vec![]).coverage().is_empty());
+    }
+
+    #[test]
+    fn record_batches_match_iteration() -> anyhow::Result<()> {
+        use arrow::array::{AsArray, UInt64Array};
+        use arrow::util::pretty::pretty_format_batches;
+
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 100, 100),
+            make_arena(1100, 100, 100),
+            make_arena(1300, 100, 100),
+        ]);
+
+        let batches: Vec<RecordBatch> =
+            timeline.record_batches(1050, 1320).collect::<Result<_>>()?;
+
+        let mut rows = Vec::new();
+        for batch in &batches {
+            let cids: &UInt64Array = batch.column(1).as_primitive();
+            let timestamps: &UInt64Array = batch.column(2).as_primitive();
+            let blobs = batch.column(3).as_binary::<i32>();
+            for i in 0..batch.num_rows() {
+                rows.push((cids.value(i), timestamps.value(i), blobs.value(i).to_vec()));
+            }
+        }
+
+        let mut expected = Vec::new();
+        let mut slice = timeline.iter(1050, Order::Asc);
+        while let Some(e) = slice.next().filter(|e| e.timestamp() < 1320) {
+            let (cid, blob) = e.resolve();
+            expected.push((cid, e.timestamp(), blob.to_vec()));
+        }
+        assert_eq!(expected.len(), 50 + 100 + 20);
+        assert_eq!(rows, expected);
+
+        let table = pretty_format_batches(&batches)?.to_string();
+        assert!(table.contains("| uid | cid"), "{table}");
+        // Header, its borders and the closing border are not rows.
+        assert_eq!(table.lines().count() - 4, expected.len());
+
+        let per_arena: Vec<usize> = timeline
+            .snapshot()
+            .iter()
+            .flat_map(|a| a.record_batches(30))
+            .map(|b| b.map(|b| b.num_rows()))
+            .collect::<Result<_>>()?;
+        assert_eq!(per_arena, [30, 30, 30, 10, 30, 30, 30, 10, 30, 30, 30, 10]);
+        assert_eq!(timeline.record_batches(1200, 1300).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn drain_owned_reports_stop_reason() {
+        let timeline = Timeline::new(vec![
+            make_arena(0, 100, 50),
+            make_arena(100, 100, 50),
+            make_arena(200, 100, 50),
+        ]);
+        let timestamps = |r: &DrainResult| r.entries.iter().map(|e| e.timestamp).collect_vec();
+
+        let all = timeline.iter_all(Order::Asc).drain_owned();
+        assert_eq!(all.stopped, StopReason::Exhausted);
+        assert_eq!(all.entries.len(), 150);
+        assert_eq!(
+            all.entries[51],
+            OwnedEntry {
+                blob: Bytes::from_static(b"x"),
+                cid: 100 * 10000 + 1,
+                timestamp: 101,
+                uid: 1,
+            }
+        );
+
+        let mut page = timeline.iter(0, Order::Asc).limit(10);
+        let first = page.drain_owned();
+        assert_eq!(first.stopped, StopReason::LimitReached);
+        assert_eq!(timestamps(&first), (0..10).collect_vec());
+        assert_eq!(page.stopped(), Some(StopReason::LimitReached));
+        page.seek(200);
+        assert_eq!(page.stopped(), None);
+        assert_eq!(page.drain_owned().entries[0].timestamp, 200);
+
+        let budget = timeline.iter_all(Order::Asc).max_arenas(2).drain_owned();
+        assert_eq!(budget.stopped, StopReason::ArenaBudget);
+        assert_eq!(budget.entries.len(), 100);
+        let enough = timeline.iter_all(Order::Asc).max_arenas(3).drain_owned();
+        assert_eq!(enough.stopped, StopReason::Exhausted);
+
+        let range = timeline.iter(20, Order::Asc).until(120).drain_owned();
+        assert_eq!(range.stopped, StopReason::PastEnd);
+        assert_eq!(timestamps(&range), (20..50).chain(100..120).collect_vec());
+
+        // Descending skips entries past the end without spending budget on
+        // the newest arena.
+        let desc = timeline
+            .iter(0, Order::Desc)
+            .until(120)
+            .max_arenas(2)
+            .drain_owned();
+        assert_eq!(desc.stopped, StopReason::Exhausted);
+        assert_eq!(
+            timestamps(&desc),
+            (100..120).rev().chain((0..50).rev()).collect_vec()
+        );
+        let desc = timeline
+            .iter(0, Order::Desc)
+            .until(120)
+            .max_arenas(1)
+            .drain_owned();
+        assert_eq!(desc.stopped, StopReason::ArenaBudget);
+        assert_eq!(desc.entries.len(), 20);
+
+        // The tightest bound wins.
+        let both = timeline
+            .iter_all(Order::Asc)
+            .limit(60)
+            .until(110)
+            .drain_owned();
+        assert_eq!(both.stopped, StopReason::LimitReached);
+        let both = timeline
+            .iter_all(Order::Asc)
+            .limit(61)
+            .until(110)
+            .drain_owned();
+        assert_eq!(both.stopped, StopReason::PastEnd);
+    }
+
+    #[test]
+    fn iter_scored_keeps_top_k() {
+        let timeline = Timeline::new(vec![make_arena(0, 100, 100), make_arena(100, 100, 100)]);
+        let score =
+            |uid: crate::Uid, _: Cid, ts: Timestamp| (uid == 3) as i64 * 1_000_000 + ts as i64;
+
+        let top = timeline.iter_scored(0, score, 4);
+        let got: Vec<_> = top.iter().map(|e| (e.uid, e.cid, e.timestamp)).collect();
+        assert_eq!(
+            got,
+            [
+                (3, 1_000_003, 103),
+                (3, 3, 3),
+                (99, 1_000_099, 199),
+                (98, 1_000_098, 198)
+            ]
+        );
+        assert!(top.iter().all(|e| e.blob == b"x"[..]));
+
+        let late: Vec<_> = timeline
+            .iter_scored(50, score, 3)
+            .iter()
+            .map(|e| e.timestamp)
+            .collect();
+        assert_eq!(late, [103, 199, 198], "respects start");
+
+        let all = timeline.iter_scored(0, |_, _, _| 0, 1000);
+        assert_eq!(all.len(), 200);
+        assert_eq!(all[0].timestamp, 199, "ties favor newer");
+        assert_eq!(all[199].timestamp, 0);
+        assert!(timeline.iter_scored(0, score, 0).is_empty());
+    }
+
+    #[test]
+    fn from_exports_round_trips() -> Result<()> {
+        let timeline = Timeline::new(vec![
+            make_arena(0, 100, 50),
+            make_arena(200, 100, 30),
+            make_arena(100, 100, 80),
+        ]);
+
+        let exports: Vec<Bytes> = timeline
+            .snapshot()
+            .iter()
+            .rev()
+            .map(|arena| {
+                let mut buf = Vec::new();
+                arena.export(&mut buf)?;
+                Ok(Bytes::from(buf))
+            })
+            .collect::<Result<_>>()?;
+
+        let loaded = Timeline::from_exports(exports.clone())?;
+        assert_eq!(
+            drain(&mut loaded.iter_all(Order::Asc)),
+            drain(&mut timeline.iter_all(Order::Asc))
+        );
+        assert_eq!(
+            loaded
+                .snapshot()
+                .iter()
+                .map(|a| a.fingerprint())
+                .collect_vec(),
+            timeline
+                .snapshot()
+                .iter()
+                .map(|a| a.fingerprint())
+                .collect_vec()
+        );
+
+        let mut broken = exports;
+        broken.insert(1, Bytes::from_static(b"not parquet"));
+        broken.push(Bytes::new());
+        let err = Timeline::from_exports(broken).err().unwrap();
+        let errors = err.downcast_ref::<ImportErrors>().unwrap();
+        assert_eq!(errors.total, 5);
+        assert_eq!(
+            errors.failures.iter().map(|(i, _)| *i).collect_vec(),
+            [1, 4]
+        );
+        assert!(err.to_string().contains("2 of 5 exports"), "{err}");
+
+        assert!(Timeline::from_exports([])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_cover_iter() {
+        let timeline = Timeline::new(vec![
+            make_arena(0, 100, 50),
+            make_arena(100, 100, 80),
+            make_arena(200, 100, 0),
+            make_arena(300, 100, 30),
+        ]);
+
+        for start in [0, 42, 100, 150, 250, 310, 400] {
+            let expected = drain(&mut timeline.iter(start, Order::Asc));
+
+            let chunks: Vec<_> = timeline.chunks(start).collect();
+            let got: Vec<Timestamp> = thread::scope(|s| {
+                let workers: Vec<_> = chunks
+                    .iter()
+                    .map(|(arena, skip)| {
+                        s.spawn(move || {
+                            let ts: Vec<_> = arena
+                                .iter(Order::Asc, start)
+                                .map(|e| e.timestamp())
+                                .collect();
+                            assert_eq!(ts.len(), arena.len() - skip);
+                            ts
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().unwrap())
+                    .collect()
+            });
+
+            assert_eq!(got, expected, "start {start}");
+            assert!(chunks.iter().all(|(a, _)| a.span.end_exclusive() > start));
+        }
+    }
+
+    #[test]
+    fn seek_reuses_snapshot() {
+        let timeline = Timeline::new(vec![make_arena(1000, 100, 10), make_arena(1100, 100, 10)]);
+
+        let mut slice = timeline.iter(1005, Order::Asc);
+        assert_eq!(drain_n(&mut slice, 3), [1005, 1006, 1007]);
+
+        timeline.remove(1000);
+
+        slice.seek(1002);
+        assert_eq!(
+            slice.next_ranked().map(|(r, e)| (r, e.timestamp())),
+            Some((0, 1002))
+        );
+        slice.seek(1108);
+        assert_eq!(drain(&mut slice), [1108, 1109]);
+
+        let mut slice = timeline.iter(0, Order::Desc);
+        assert_eq!(drain_n(&mut slice, 2), [1109, 1108]);
+        slice.seek(1105);
+        assert_eq!(drain(&mut slice), [1109, 1108, 1107, 1106, 1105]);
+        slice.seek(0);
+        assert_eq!(drain(&mut slice).len(), 10, "removed arena is gone");
+    }
+
+    #[test]
+    fn iter_all_matches_unbounded_iter() {
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 100, 20),
+            make_arena(1100, 100, 5),
+            make_arena(1200, 100, 0),
+            make_arena(1300, 100, 7),
+        ]);
+
+        let asc = drain(&mut timeline.iter_all(Order::Asc));
+        assert_eq!(asc, drain(&mut timeline.iter(0, Order::Asc)));
+        assert_eq!(asc.len(), 32);
+        assert!(asc.is_sorted());
+
+        let desc = drain(&mut timeline.iter_all(Order::Desc));
+        assert_eq!(desc, drain(&mut timeline.iter(0, Order::Desc)));
+        assert_eq!(desc, asc.iter().rev().copied().collect::<Vec<_>>());
+
+        assert!(Timeline::new(vec![]).iter_all(Order::Desc).next().is_none());
+    }
+
+    #[test]
+    fn peek_matches_next() {
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 10, 3),
+            make_arena(1010, 10, 0),
+            make_arena(1020, 10, 4),
+        ]);
+
+        for order in [Order::Asc, Order::Desc] {
+            let mut slice = timeline.iter(1001, order);
+            let mut count = 0;
+
+            loop {
+                let peeked = slice.peek().map(|e| (e.uid, e.timestamp(), e.resolve().0));
+                assert_eq!(
+                    peeked,
+                    slice.peek().map(|e| (e.uid, e.timestamp(), e.resolve().0)),
+                    "repeated peek is stable",
+                );
+
+                let next = slice.next().map(|e| (e.uid, e.timestamp(), e.resolve().0));
+                assert_eq!(peeked, next, "{order:?}: peek must match next");
+
+                if next.is_none() {
+                    break;
+                }
+                count += 1;
+            }
+
+            assert_eq!(count, 6, "{order:?}: entry at 1000 is before start");
+            assert!(slice.peek().is_none());
+        }
+    }
+
+    #[test]
+    fn split_at_straddling_arena() -> anyhow::Result<()> {
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 100, 50),
+            make_arena(1100, 100, 50),
+            make_arena(1200, 100, 50),
+        ]);
+
+        let (older, newer) = timeline.split_at(1125)?;
+        assert_eq!((older.len(), newer.len()), (2, 2));
+
+        let cids = |t: &Timeline| -> Vec<(Timestamp, Cid)> {
+            let mut slice = t.iter(0, Order::Asc);
+            let mut out = Vec::new();
+            while let Some(e) = slice.next() {
+                out.push((e.timestamp(), e.resolve().0));
+            }
+            out
+        };
+
+        let (old, new) = (cids(&older), cids(&newer));
+        assert!(old.iter().all(|&(ts, _)| ts < 1125));
+        assert!(new.iter().all(|&(ts, _)| ts >= 1125));
+
+        let mut rejoined = old;
+        rejoined.extend(new);
+        assert_eq!(rejoined, cids(&timeline), "no entries lost or duplicated");
+        Ok(())
+    }
+
+    #[test]
+    fn export_uid_across_arenas() -> anyhow::Result<()> {
+        let timeline = Timeline::new(vec![make_arena(1100, 100, 10), make_arena(1000, 100, 10)]);
+
+        let mut buf = Vec::new();
+        let written = timeline.export_uid(3, &mut buf, &ExportOptions::default())?;
+        assert_eq!(written, 2, "one row per arena");
+
+        let imported = Cold::import(bytes::Bytes::from(buf))?;
+        assert_eq!(imported.span, Window::new(1000, 200));
+
+        let rows: Vec<(crate::Uid, Timestamp)> = imported
+            .iter(Order::Asc, 0)
+            .map(|e| (e.uid, e.timestamp()))
+            .collect();
+        assert_eq!(rows, [(3, 1003), (3, 1103)]);
+        Ok(())
+    }
+
+    #[test]
+    fn export_uid_merges_overlapping_arenas() -> anyhow::Result<()> {
+        let arena = |epoch, rows: &[(Timestamp, Cid)]| -> Arc<Cold> {
+            test_util::freeze(
+                Window::new(epoch, 100),
+                rows.iter().map(|&(ts, cid)| (7, cid, ts, b"x")),
+            )
+        };
+        // Two arenas share an epoch and a third overlaps both.
+        let timeline = Timeline::new(vec![
+            arena(1000, &[(1010, 9), (1090, 5)]),
+            arena(1050, &[(1060, 2), (1090, 1)]),
+            arena(1000, &[(1090, 3)]),
+        ]);
+
+        let mut buf = Vec::new();
+        let written = timeline.export_uid(7, &mut buf, &ExportOptions::default())?;
+        assert_eq!(written, 5);
+
+        let imported = Cold::import(bytes::Bytes::from(buf))?;
+        assert_eq!(imported.span, Window::new(1000, 150));
+        assert_eq!(
+            imported.cids(),
+            [9, 2, 1, 3, 5],
+            "(1010, 9) (1060, 2) (1090, 1) (1090, 3) (1090, 5)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn usage_per_arena_ordered_by_epoch() {
+        let timeline = Timeline::new(vec![
+            make_arena(1200, 100, 30),
+            make_arena(1000, 100, 5),
+            make_arena(1100, 100, 15),
+        ]);
+
+        let per_arena = timeline.usage_per_arena();
+        let epochs: Vec<_> = per_arena.iter().map(|(epoch, _)| *epoch).collect();
+        assert_eq!(epochs, [1000, 1100, 1200]);
+
+        let items: Vec<_> = per_arena.iter().map(|(_, u)| u.items).collect();
+        assert_eq!(items, [5, 15, 30]);
+        assert!(per_arena.windows(2).all(|w| w[0].1.heap < w[1].1.heap));
+
+        let heap: usize = per_arena.iter().map(|(_, u)| u.heap).sum();
+        assert_eq!(heap, timeline.usage().heap);
+    }
+
+    #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
+    fn usage_tree_per_arena() {
+        let timeline = Timeline::new(vec![make_arena(1100, 100, 10), make_arena(1000, 100, 5)]);
+
+        let tree = timeline.usage_tree();
+        let usage = timeline.usage();
+        assert_eq!(tree.label, "timeline");
+        assert_eq!(
+            (tree.usage.heap, tree.usage.mapped),
+            (usage.heap, usage.mapped)
+        );
+
+        let labels: Vec<_> = tree.children.iter().map(|c| c.label.as_ref()).collect();
+        assert_eq!(labels, ["arena 1000", "arena 1100"]);
+
+        for arena in &tree.children {
+            let parts: Vec<_> = arena.children.iter().map(|c| c.label.as_ref()).collect();
+            assert_eq!(parts, ["blobs", "timestamps", "uids"]);
+
+            let heap: usize = arena.children.iter().map(|c| c.usage.heap).sum();
+            assert_eq!(heap, arena.usage.heap, "children add up");
+        }
+
+        let top: Vec<_> = usage
+            .top_contributors()
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(top, ["1100", "1000"], "largest arena first");
+        assert_eq!(tree.usage.top_contributors()[0].0, "arena 1100");
+
+        let budget = UsageBudget {
+            mapped: Some(usage.mapped - 1),
+            ..Default::default()
+        };
+        assert_eq!(timeline.check_budget(&budget).len(), 1);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn record_metrics_caps_arena_labels() {
+        let arenas = (0..20)
+            .map(|i| make_arena(1000 + i * 100, 100, 5))
+            .collect();
+        let timeline = Timeline::new(arenas);
+
+        let gauges = crate::usage::tests::capture_gauges(|| timeline.record_metrics("tl"));
+
+        let epochs: std::collections::BTreeSet<String> = gauges
+            .keys()
+            .filter(|(name, _)| name == "tl_arena_count")
+            .map(|(_, labels)| labels[0].1.clone())
+            .collect();
+        assert_eq!(epochs.len(), METRICS_ARENA_LABELS + 1);
+        assert!(epochs.contains("older"));
+        assert!(epochs.contains("2900"), "newest arena is labeled");
+        assert!(!epochs.contains("1000"), "oldest arena is aggregated");
+
+        let label = |epoch: &str| vec![("epoch".to_string(), epoch.to_string())];
+        assert_eq!(gauges[&("tl_arena_count".to_string(), label("older"))], 4.0);
+        assert_eq!(gauges[&("tl_count".to_string(), vec![])], 20.0);
+        assert_eq!(
+            gauges[&("tl_heap_bytes".to_string(), vec![])],
+            timeline.usage().heap as f64
+        );
+    }
+
+    #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
+    fn usage_delta_since_tracks_added_arena() {
+        let timeline = Timeline::new(vec![make_arena(1000, 100, 10)]);
+        let before = timeline.usage();
+
+        timeline.add(make_arena(1100, 100, 10));
+
+        let delta = timeline.usage_delta_since(&before);
+        assert_eq!(delta.count, 1);
+        assert_eq!(delta.active, 1);
+        assert!(delta.heap > 0, "{delta}");
+        assert!(delta.mapped > 0, "{delta}");
+    }
+
+    #[test]
+    fn rolling_keeps_newest_windows() -> anyhow::Result<()> {
+        const MAX: usize = 4;
+        let timeline = Timeline::rolling(MAX).with_cid_index();
+
+        for i in 0..MAX as u64 + 3 {
+            timeline.add(make_arena(i * 100, 50, 5));
+            assert!(timeline.len() <= MAX);
+        }
+        let epochs = |t: &Timeline| t.snapshot().iter().map(|a| a.span.epoch).collect_vec();
+        assert_eq!(epochs(&timeline), [300, 400, 500, 600]);
+        assert!(timeline.arena_for_cid(200 * 10000).is_none());
+        assert!(timeline.arena_for_cid(300 * 10000).is_some());
+
+        // Older than everything held: dropped at once.
+        timeline.add(make_arena(0, 50, 5));
+        assert_eq!(epochs(&timeline), [300, 400, 500, 600]);
+        assert!(timeline.arena_for_cid(0).is_none());
+
+        // Out of order but newer than the oldest: the oldest goes.
+        timeline.add_checked(make_arena(450, 50, 5))?;
+        assert_eq!(epochs(&timeline), [400, 450, 500, 600]);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg_attr(
+        target_arch = "wasm32",
+        ignore = "in-memory blobs use no disk or mapping"
+    )]
+    fn enforce_budget_evicts_oldest() {
+        let timeline = Timeline::new(vec![]).with_cid_index();
+        for epoch in [400, 0, 300, 100, 200] {
+            timeline.add(make_arena(epoch, 100, 50 + epoch as usize));
+        }
+        let disk = |epoch| {
+            let snapshot = timeline.snapshot();
+            snapshot
+                .iter()
+                .find(|a| a.span.epoch == epoch)
+                .unwrap()
+                .usage()
+                .disk
+        };
+        let newest_two = disk(300) + disk(400);
+        assert!(newest_two > 0);
+
+        assert_eq!(timeline.enforce_budget(u64::MAX), 0);
+        assert_eq!(timeline.enforce_budget(newest_two), 3);
+        assert_eq!(timeline.coverage(), [(300, 500)]);
+        assert!(timeline.usage().disk <= newest_two);
+        assert!(
+            timeline.arena_for_cid(0).is_none(),
+            "evicted from cid index"
+        );
+        assert!(timeline.arena_for_cid(300 * 10000).is_some());
+
+        assert_eq!(
+            timeline.enforce_budget(newest_two),
+            0,
+            "already under budget"
+        );
+        assert_eq!(timeline.enforce_budget(newest_two - 1), 1);
+        assert_eq!(timeline.enforce_budget(0), 1);
+        assert!(timeline.is_empty());
+    }
+
+    #[test]
+    fn enforce_budget_counts_shared_stores_once() -> Result<()> {
+        let own = make_arena(200, 100, 50);
+        let own_disk = own.usage().disk;
+
+        let remapped = Timeline::new(vec![own.clone(), own.remap_uids(|uid| uid + 1)?]);
+        assert_eq!(
+            remapped.enforce_budget(own_disk),
+            0,
+            "one store, counted once"
+        );
+        assert_eq!(remapped.enforce_budget(own_disk - 1), 2);
+
+        let (a, b) = (make_arena(0, 100, 50), make_arena(100, 100, 50));
+        let pool = Arc::new(BlobPool::from_arenas([&a, &b])?);
+        let pool_disk = pool.usage().disk;
+        assert!(pool_disk > 0);
+
+        let timeline = Timeline::new(vec![
+            a.with_external_blobs(Arc::clone(&pool))?,
+            b.with_external_blobs(Arc::clone(&pool))?,
+            own,
+        ]);
+        assert_eq!(timeline.enforce_budget(pool_disk + own_disk), 0);
+        assert_eq!(
+            timeline.enforce_budget(own_disk),
+            2,
+            "pool freed with its last arena"
+        );
+        assert_eq!(timeline.coverage(), [(200, 300)]);
+        Ok(())
+    }
+
+    #[test]
+    fn arena_for_cid_follows_add_and_remove() {
+        let timeline = Timeline::new(vec![make_arena(1000, 100, 5)]).with_cid_index();
+        timeline.add(make_arena(1100, 100, 5));
+        assert!(timeline.add_checked(make_arena(1200, 100, 5)).is_ok());
+
+        let epoch_of = |cid| timeline.arena_for_cid(cid).map(|a| a.span.epoch);
+        assert_eq!(epoch_of(1000 * 10000 + 2), Some(1000));
+        assert_eq!(epoch_of(1100 * 10000 + 4), Some(1100));
+        assert_eq!(epoch_of(1200 * 10000), Some(1200));
+        assert_eq!(epoch_of(42), None);
+
+        let before = timeline.usage().heap;
+        timeline.remove(1100);
+
+        assert!((0..5).all(|i| epoch_of(1100 * 10000 + i).is_none()));
+        assert_eq!(epoch_of(1000 * 10000 + 2), Some(1000));
+        assert_eq!(timeline.usage().items, 10);
+        assert!(timeline.usage().heap <= before);
+
+        let tree = timeline.usage_tree();
+        assert_eq!(tree.children[0].label, "cid index");
+        assert!(tree.children[0].usage.heap > 0);
+        assert!(Timeline::new(vec![]).arena_for_cid(0).is_none(), "no index");
+    }
+
+    #[test]
+    fn cid_index_survives_dropping_one_holder() -> anyhow::Result<()> {
+        let arena = |epoch: Timestamp, cids: &[Cid]| -> anyhow::Result<Arc<Cold>> {
+            let mut hot = Hot::without_dedup(Window::new(epoch, 100))?;
+            for (i, &cid) in cids.iter().enumerate() {
+                hot.add(1, cid, epoch + i as u64, b"x")?;
+            }
+            hot.try_into()
+        };
+
+        let timeline = Timeline::rolling(3).with_cid_index();
+        let old = arena(0, &[42, 1])?;
+        timeline.add(Arc::clone(&old));
+        timeline.add(arena(100, &[42, 2])?);
+
+        let epoch_of = |cid| timeline.arena_for_cid(cid).map(|a| a.span.epoch);
+        assert_eq!(epoch_of(42), Some(100));
+
+        // The newest holder goes; the older arena still holds the cid.
+        timeline.remove(100);
+        assert!(Arc::ptr_eq(&timeline.arena_for_cid(42).unwrap(), &old));
+        assert_eq!(epoch_of(2), None);
+
+        // Two arenas at one epoch, as a `Rotator` may produce.
+        let first = arena(200, &[7, 8])?;
+        let second = arena(200, &[7, 9])?;
+        timeline.add(Arc::clone(&first));
+        timeline.add(Arc::clone(&second));
+        assert!(Arc::ptr_eq(&timeline.arena_for_cid(7).unwrap(), &second));
+        assert!(Arc::ptr_eq(&timeline.arena_for_cid(8).unwrap(), &first));
+        assert!(Arc::ptr_eq(&timeline.arena_for_cid(9).unwrap(), &second));
+
+        // Rolling out the oldest arena leaves the newer holder in place.
+        timeline.add(arena(300, &[42, 9, 9])?);
+        assert_eq!(epoch_of(1), None);
+        assert_eq!(epoch_of(42), Some(300));
+        assert_eq!(epoch_of(9), Some(300));
+
+        timeline.remove(200);
+        assert_eq!(epoch_of(7), None);
+        assert_eq!(epoch_of(8), None);
+        assert_eq!(epoch_of(9), Some(300));
+
+        timeline.remove(300);
+        assert!(timeline.cid_index.as_ref().unwrap().read().0.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_cids_counted_across_arenas() -> anyhow::Result<()> {
+        let arena = |epoch: Timestamp, cids: &[Cid]| -> anyhow::Result<Arc<Cold>> {
+            let mut hot = Hot::without_dedup(Window::new(epoch, 100))?;
+            for (i, &cid) in cids.iter().enumerate() {
+                hot.add(1, cid, epoch + i as u64, b"x")?;
+            }
+            hot.try_into()
+        };
+
+        let timeline = Timeline::new(vec![
+            arena(0, &[42, 1, 2])?,
+            arena(100, &[3, 42, 2])?,
+            arena(200, &[42, 7, 7])?,
+        ]);
+        assert_eq!(timeline.duplicate_cids(), [(2, 2), (7, 2), (42, 3)]);
+
+        assert!(
+            Timeline::new(vec![make_arena(0, 100, 10), make_arena(100, 100, 10)])
+                .duplicate_cids()
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn cid_differences_with_partial_overlap() {
+        let arenas = || vec![make_arena(1000, 100, 4), make_arena(1100, 100, 3)];
+        // Ours: 10_000_000..=10_000_003 and 11_000_000..=11_000_002.
+        let peer: HashSet<Cid> = [10_000_001, 10_000_003, 11_000_000, 7, 8].into();
+
+        for timeline in [
+            Timeline::new(arenas()),
+            Timeline::new(arenas()).with_cid_index(),
+        ] {
+            assert_eq!(
+                timeline.cids_not_in(&peer),
+                [10_000_000, 10_000_002, 11_000_001, 11_000_002]
+            );
+            assert_eq!(timeline.cids_missing(&peer), [7, 8]);
+
+            let ours: HashSet<Cid> = timeline.cids_not_in(&HashSet::new()).into_iter().collect();
+            assert_eq!(ours.len(), 7);
+            assert!(timeline.cids_not_in(&ours).is_empty());
+            assert!(timeline.cids_missing(&ours).is_empty());
+        }
+
+        // A cid in two overlapping arenas is reported once.
+        let doubled = Timeline::new(vec![make_arena(1000, 100, 2), make_arena(1000, 100, 2)]);
+        assert_eq!(
+            doubled.cids_not_in(&HashSet::new()),
+            [10_000_000, 10_000_001]
+        );
+        assert!(Timeline::new(vec![]).cids_not_in(&peer).is_empty());
+        assert_eq!(Timeline::new(vec![]).cids_missing(&peer).len(), peer.len());
+    }
+
+    #[test]
+    fn resolve_cid_from_middle_arena() -> anyhow::Result<()> {
+        let mut middle = Hot::new(Window::new(1100, 100))?;
+        middle.add(7, 42, 1150, b"deep link")?;
+        middle.add(8, 43, 1160, b"other")?;
+
+        let timeline = Timeline::new(vec![
+            make_arena(1000, 100, 5),
+            middle.try_into()?,
+            make_arena(1200, 100, 5),
+        ]);
+
+        assert_eq!(timeline.resolve(42), Some((1150, b"deep link".to_vec())));
+        assert_eq!(timeline.resolve(4242), None);
+        Ok(())
+    }
+
     proptest! {
         #[test]
         fn fuzz_iter(
@@ -1,6 +1,14 @@
+use anyhow::{Result, ensure};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use super::Timestamp;
 
+/// Half-open time range `[epoch, epoch + duration)`. With the `serde` feature,
+/// serializes as `{"duration", "epoch"}`. The end saturates at `u64::MAX`, so
+/// windows near it are cut short and never contain `u64::MAX` itself.
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Window {
     pub duration: u32,
     pub epoch: Timestamp,
@@ -11,6 +19,16 @@ impl Window {
         Self { epoch, duration }
     }
 
+    /// The window of `duration` containing `ts`, with its epoch floored to a
+    /// multiple of `duration`. Producers bucketing with the same duration get
+    /// identical or disjoint windows. Fails for a zero duration, which
+    /// contains nothing.
+    pub fn aligned_for(ts: Timestamp, duration: u32) -> Result<Self> {
+        ensure!(duration > 0, "cannot align {ts} to a zero-length window");
+
+        Ok(Self::new(ts - ts % duration as u64, duration))
+    }
+
     pub fn contains(&self, ts: Timestamp) -> bool {
         ts >= self.epoch && ts < self.end_exclusive()
     }
@@ -30,7 +48,22 @@ impl Window {
     }
 
     pub fn end_exclusive(&self) -> Timestamp {
-        self.epoch + self.duration as u64
+        self.epoch.saturating_add(self.duration as u64)
+    }
+
+    /// Relative offset to binary search stored offsets for `ts` with: 0 at
+    /// or below the epoch, and `u32::MAX`, past every offset, for `ts` too
+    /// far after it to fit.
+    pub(super) fn relative_bound(&self, ts: Timestamp) -> u32 {
+        ts.saturating_sub(self.epoch).min(u32::MAX as u64) as u32
+    }
+
+    /// Whether the two half-open windows share at least one timestamp.
+    pub fn overlaps(&self, other: &Window) -> bool {
+        self.duration > 0
+            && other.duration > 0
+            && self.epoch < other.end_exclusive()
+            && other.epoch < self.end_exclusive()
     }
 }
 
@@ -39,6 +72,16 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() -> serde_json::Result<()> {
+        let w = Window::new(1000, 60);
+        let json = serde_json::to_value(&w)?;
+        assert_eq!(json, serde_json::json!({"duration": 60, "epoch": 1000}));
+        assert_eq!(serde_json::from_value::<Window>(json)?, w);
+        Ok(())
+    }
+
     proptest! {
         #[test]
         fn fuzz_window(
@@ -65,6 +108,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn aligned_for_floors_to_duration() -> Result<()> {
+        assert_eq!(Window::aligned_for(3600, 3600)?, Window::new(3600, 3600));
+        assert_eq!(Window::aligned_for(7199, 3600)?, Window::new(3600, 3600));
+        assert_eq!(Window::aligned_for(59, 60)?, Window::new(0, 60));
+        assert_eq!(Window::aligned_for(0, 1)?, Window::new(0, 1));
+
+        let err = Window::aligned_for(1000, 0).unwrap_err();
+        assert!(err.to_string().contains("zero-length"), "{err}");
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn fuzz_aligned_for(ts in 0..u64::MAX / 2, duration in 1..u32::MAX, other in 0..u64::MAX / 2) {
+            let span = Window::aligned_for(ts, duration).unwrap();
+
+            prop_assert!(span.contains(ts));
+            prop_assert_eq!(span.epoch % duration as u64, 0);
+
+            // Same-duration buckets never partially overlap.
+            let other = Window::aligned_for(other, duration).unwrap();
+            prop_assert_eq!(span.overlaps(&other), span == other);
+        }
+    }
+
+    #[test]
+    fn overlaps_is_half_open() {
+        let span = Window::new(1000, 100);
+
+        assert!(span.overlaps(&span));
+        assert!(span.overlaps(&Window::new(1099, 10)));
+        assert!(span.overlaps(&Window::new(900, 101)));
+        assert!(span.overlaps(&Window::new(1010, 10)), "contained");
+        assert!(!span.overlaps(&Window::new(1100, 100)), "adjacent after");
+        assert!(!span.overlaps(&Window::new(900, 100)), "adjacent before");
+        assert!(!span.overlaps(&Window::new(1050, 0)), "empty window");
+    }
+
     #[test]
     #[should_panic(expected = "outside")]
     fn relative_outside_panics_in_debug() {
@@ -79,4 +161,36 @@ mod tests {
         assert!(!span.contains(999));
         assert_eq!(span.end_exclusive(), 1000);
     }
+
+    #[test]
+    fn end_saturates_near_max() -> Result<()> {
+        let span = Window::new(u64::MAX - 10, 3600);
+        assert_eq!(span.end_exclusive(), u64::MAX);
+        assert!(span.contains(u64::MAX - 10));
+        assert!(span.contains(u64::MAX - 1));
+        assert!(!span.contains(u64::MAX));
+        assert!(!span.contains(u64::MAX - 11));
+        assert_eq!(span.convert_to_relative(u64::MAX - 1), 9);
+
+        assert!(span.overlaps(&Window::new(u64::MAX - 1, u32::MAX)));
+        assert!(!span.overlaps(&Window::new(0, u32::MAX)));
+
+        let aligned = Window::aligned_for(u64::MAX - 1, 1 << 30)?;
+        assert!(aligned.contains(u64::MAX - 1));
+        assert_eq!(aligned.end_exclusive(), u64::MAX);
+        Ok(())
+    }
+
+    #[test]
+    fn relative_bound_clamps() {
+        let span = Window::new(1000, 100);
+        assert_eq!(span.relative_bound(0), 0);
+        assert_eq!(span.relative_bound(1042), 42);
+        assert_eq!(
+            span.relative_bound(1000 + (1 << 32) + 5),
+            u32::MAX,
+            "no truncation"
+        );
+        assert_eq!(span.relative_bound(u64::MAX), u32::MAX);
+    }
 }
@@ -0,0 +1,423 @@
+//! Diagnostics for exported arenas, for operators debugging bad exports.
+//! [`describe`] reads only the parquet footer; [`validate`] decodes every row.
+//! `examples/inspect.rs` prints both for a file.
+
+use std::collections::{BTreeSet, HashSet};
+
+use anyhow::{Context, Result, ensure};
+use arrow::array::{Array, PrimitiveArray, UInt64Array};
+use bytes::Bytes;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::metadata::{KeyValue, ParquetMetaData, ParquetMetaDataReader};
+use parquet::file::statistics::Statistics;
+use tracing::trace;
+
+use crate::content::arena::{Cold, FINGERPRINT_KEY};
+use crate::content::{Cid, Timestamp, Window};
+use crate::users::UidType;
+
+/// Max row indices kept per kind of violation.
+const SAMPLES: usize = 8;
+
+/// Index of the timestamp column in the export schema.
+const TIMESTAMP_COLUMN: usize = 2;
+
+/// What an export's footer says about it.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ArenaReport {
+    /// Export fingerprint from the footer, if the writer stamped one.
+    pub checksum: Option<u64>,
+    /// Distinct codecs across column chunks. Levels are not recorded in the
+    /// footer and read back as the codec's default.
+    pub compression: BTreeSet<String>,
+    pub created_by: Option<String>,
+    /// `None` if any row group lacks timestamp statistics.
+    pub max_timestamp: Option<Timestamp>,
+    pub min_timestamp: Option<Timestamp>,
+    pub row_groups: usize,
+    pub rows: u64,
+    /// Parquet format version.
+    pub version: i32,
+    pub window: Window,
+}
+
+/// Rows breaking one invariant: how many, and the first few indices.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Violations {
+    pub count: u64,
+    pub samples: Vec<usize>,
+}
+
+/// Outcome of a full decode of an export.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Second and later occurrences of a cid.
+    pub duplicate_cids: Violations,
+    /// Fingerprint of the imported arena; `None` if rows were invalid and the
+    /// import was skipped.
+    pub fingerprint: Option<u64>,
+    /// Whether `fingerprint` matches the footer's, if both are known.
+    pub fingerprint_matches: Option<bool>,
+    /// Rows with a timestamp outside the window.
+    pub out_of_window: Violations,
+    pub rows: u64,
+    /// Rows ordered before their predecessor by `(timestamp, cid)`.
+    pub unsorted: Violations,
+    pub window: Window,
+}
+
+impl Violations {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn push(&mut self, row: usize) {
+        self.count += 1;
+        if self.samples.len() < SAMPLES {
+            self.samples.push(row);
+        }
+    }
+}
+
+impl ValidationReport {
+    /// Whether every row is valid and the fingerprint, if stamped, matches.
+    pub fn is_valid(&self) -> bool {
+        self.duplicate_cids.is_empty()
+            && self.out_of_window.is_empty()
+            && self.unsorted.is_empty()
+            && self.fingerprint_matches != Some(false)
+    }
+}
+
+/// Summarizes an export from its footer alone, without decoding rows.
+pub fn describe(data: &Bytes) -> Result<ArenaReport> {
+    let metadata = ParquetMetaDataReader::new()
+        .parse_and_finish(data)
+        .context("read parquet footer")?;
+
+    let file = metadata.file_metadata();
+    let key_values = file
+        .key_value_metadata()
+        .context("missing parquet metadata")?;
+
+    let mut compression = BTreeSet::new();
+    let mut bounds = Some((Timestamp::MAX, Timestamp::MIN));
+
+    for group in metadata.row_groups() {
+        for column in group.columns() {
+            compression.insert(column.compression().to_string());
+        }
+
+        let stats = group
+            .columns()
+            .get(TIMESTAMP_COLUMN)
+            .and_then(|c| c.statistics());
+        // u64 columns are stored as INT64 with unsigned ordering.
+        let group_bounds = match stats {
+            Some(Statistics::Int64(s)) => s.min_opt().zip(s.max_opt()),
+            _ => None,
+        };
+
+        bounds = match (bounds, group_bounds) {
+            (Some((lo, hi)), Some((&min, &max))) => Some((lo.min(min as u64), hi.max(max as u64))),
+            _ => None,
+        };
+    }
+
+    let bounds = bounds.filter(|_| metadata.num_row_groups() > 0);
+
+    Ok(ArenaReport {
+        checksum: checksum(key_values)?,
+        compression,
+        created_by: file.created_by().map(str::to_string),
+        max_timestamp: bounds.map(|(_, hi)| hi),
+        min_timestamp: bounds.map(|(lo, _)| lo),
+        row_groups: metadata.num_row_groups(),
+        rows: file.num_rows() as u64,
+        version: file.version(),
+        window: window(&metadata)?,
+    })
+}
+
+/// Decodes every row, checking `(timestamp, cid)` order, that timestamps fall
+/// in the window and that cids are unique. If they do, also imports the arena
+/// and checks its fingerprint against the footer's.
+pub fn validate(data: Bytes) -> Result<ValidationReport> {
+    let builder =
+        ParquetRecordBatchReaderBuilder::try_new(data.clone()).context("open parquet reader")?;
+
+    let window = window(builder.metadata())?;
+    let checksum = checksum(
+        builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .context("missing parquet metadata")?,
+    )?;
+
+    let mut report = ValidationReport {
+        duplicate_cids: Violations::default(),
+        fingerprint: None,
+        fingerprint_matches: None,
+        out_of_window: Violations::default(),
+        rows: 0,
+        unsorted: Violations::default(),
+        window,
+    };
+
+    let mut seen: HashSet<Cid> = HashSet::new();
+    let mut prev: Option<(Timestamp, Cid)> = None;
+    let mut row = 0;
+
+    for batch_result in builder.build().context("build parquet reader")? {
+        let batch = batch_result.context("read parquet batch")?;
+        ensure!(
+            batch.num_columns() == 4,
+            "expected 4 columns, found {}",
+            batch.num_columns()
+        );
+
+        // Checked for the type only; any uid is valid.
+        batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<PrimitiveArray<UidType>>()
+            .context("downcast uid column")?;
+
+        let cid_col = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .context("downcast cid column")?;
+
+        let ts_col = batch
+            .column(TIMESTAMP_COLUMN)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .context("downcast timestamp column")?;
+
+        for (&cid, &ts) in cid_col.values().iter().zip(ts_col.values()) {
+            if !report.window.contains(ts) {
+                report.out_of_window.push(row);
+            }
+            if prev.is_some_and(|p| (ts, cid) < p) {
+                report.unsorted.push(row);
+            }
+            if !seen.insert(cid) {
+                report.duplicate_cids.push(row);
+            }
+
+            prev = Some((ts, cid));
+            row += 1;
+        }
+    }
+
+    report.rows = row as u64;
+
+    if report.is_valid() {
+        let fingerprint = Cold::import(data)?.fingerprint();
+
+        report.fingerprint = Some(fingerprint);
+        report.fingerprint_matches = checksum.map(|c| c == fingerprint);
+    }
+
+    trace!(
+        rows = report.rows,
+        valid = report.is_valid(),
+        "validated export"
+    );
+
+    Ok(report)
+}
+
+fn window(metadata: &ParquetMetaData) -> Result<Window> {
+    let key_values = metadata
+        .file_metadata()
+        .key_value_metadata()
+        .context("missing parquet metadata")?;
+
+    let epoch = value(key_values, "crimeline.epoch")
+        .context("missing crimeline.epoch")?
+        .parse()
+        .context("parse epoch")?;
+    let duration = value(key_values, "crimeline.duration")
+        .context("missing crimeline.duration")?
+        .parse()
+        .context("parse duration")?;
+
+    Ok(Window::new(epoch, duration))
+}
+
+fn checksum(key_values: &[KeyValue]) -> Result<Option<u64>> {
+    value(key_values, FINGERPRINT_KEY)
+        .map(|v| u64::from_str_radix(v, 16).context("parse fingerprint"))
+        .transpose()
+}
+
+fn value<'a>(key_values: &'a [KeyValue], key: &str) -> Option<&'a str> {
+    key_values
+        .iter()
+        .find(|e| e.key == key)
+        .and_then(|e| e.value.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{BinaryArray, RecordBatch};
+    use arrow::datatypes::{ArrowPrimitiveType, DataType, Field, Schema};
+    use parquet::arrow::ArrowWriter;
+    use parquet::file::properties::WriterProperties;
+
+    use super::*;
+    use crate::Uid;
+    use crate::content::arena::Hot;
+
+    fn good() -> Result<Bytes> {
+        let mut hot = Hot::new(Window::new(1000, 100))?;
+        for i in 0..50u64 {
+            hot.add(
+                (i % 3) as Uid,
+                100 + i,
+                1000 + i * 2,
+                format!("post {i}").as_bytes(),
+            )?;
+        }
+        let cold: Arc<Cold> = hot.try_into()?;
+
+        let mut buf = Vec::new();
+        cold.export(&mut buf)?;
+        Ok(buf.into())
+    }
+
+    /// Writes `(cid, timestamp)` rows as-is over `[1000, 1100)`, bypassing
+    /// the arena's sorting and checks.
+    fn raw(rows: &[(Cid, Timestamp)], extra: Vec<KeyValue>) -> Result<Bytes> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("uid", UidType::DATA_TYPE, false),
+            Field::new("cid", DataType::UInt64, false),
+            Field::new("timestamp", DataType::UInt64, false),
+            Field::new("blob", DataType::Binary, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(PrimitiveArray::<UidType>::from_iter_values(
+                    rows.iter().map(|_| 1),
+                )),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.0))),
+                Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.1))),
+                Arc::new(BinaryArray::from_iter_values(rows.iter().map(|_| b"x"))),
+            ],
+        )?;
+
+        let mut metadata = vec![
+            KeyValue::new("crimeline.epoch".into(), "1000".to_string()),
+            KeyValue::new("crimeline.duration".into(), "100".to_string()),
+        ];
+        metadata.extend(extra);
+
+        let props = WriterProperties::builder()
+            .set_key_value_metadata(Some(metadata))
+            .build();
+        let mut buf = Vec::new();
+        let mut writer = ArrowWriter::try_new(&mut buf, schema, Some(props))?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(buf.into())
+    }
+
+    #[test]
+    fn describe_reads_footer() -> Result<()> {
+        let report = describe(&good()?)?;
+
+        assert_eq!(report.window, Window::new(1000, 100));
+        assert_eq!(report.rows, 50);
+        assert_eq!(report.row_groups, 1);
+        assert_eq!(report.min_timestamp, Some(1000));
+        assert_eq!(report.max_timestamp, Some(1098));
+        assert!(report.checksum.is_some());
+        assert!(report.compression.iter().all(|c| c.starts_with("ZSTD")));
+        assert!(report.created_by.is_some_and(|c| c.contains("parquet")));
+
+        let raw = describe(&raw(&[(1, 1010)], vec![])?)?;
+        assert_eq!(raw.checksum, None);
+        assert_eq!(
+            raw.compression,
+            BTreeSet::from(["UNCOMPRESSED".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn describe_rejects_damaged_footer() -> Result<()> {
+        let data = good()?;
+
+        assert!(describe(&data.slice(..data.len() - 1)).is_err());
+        assert!(describe(&Bytes::from_static(b"not parquet")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_accepts_export() -> Result<()> {
+        let data = good()?;
+        let report = validate(data.clone())?;
+
+        assert!(report.is_valid(), "{report:?}");
+        assert_eq!(report.rows, 50);
+        assert_eq!(report.fingerprint, describe(&data)?.checksum);
+        assert_eq!(report.fingerprint_matches, Some(true));
+        Ok(())
+    }
+
+    #[test]
+    fn validate_samples_violations() -> Result<()> {
+        let report = validate(raw(
+            &[
+                (1, 1010),
+                (2, 1005), // unsorted
+                (3, 1200), // out of window
+                (1, 1300), // out of window, duplicate
+                (4, 1020), // unsorted
+            ],
+            vec![],
+        )?)?;
+
+        assert!(!report.is_valid());
+        assert_eq!(report.rows, 5);
+        assert_eq!(report.unsorted.samples, [1, 4]);
+        assert_eq!(report.out_of_window.samples, [2, 3]);
+        assert_eq!(report.duplicate_cids.samples, [3]);
+        assert_eq!(report.fingerprint, None);
+        Ok(())
+    }
+
+    #[test]
+    fn validate_caps_samples() -> Result<()> {
+        let rows: Vec<_> = (0..20).map(|i| (7, 1000 + i)).collect();
+        let report = validate(raw(&rows, vec![])?)?;
+
+        assert_eq!(report.duplicate_cids.count, 19);
+        assert_eq!(
+            report.duplicate_cids.samples,
+            (1..=SAMPLES).collect::<Vec<_>>()
+        );
+        assert!(report.unsorted.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_detects_wrong_fingerprint() -> Result<()> {
+        let stamp = KeyValue::new(FINGERPRINT_KEY.into(), "00000000deadbeef".to_string());
+        let report = validate(raw(&[(1, 1010), (2, 1020)], vec![stamp])?)?;
+
+        assert!(report.fingerprint.is_some());
+        assert_eq!(report.fingerprint_matches, Some(false));
+        assert!(!report.is_valid());
+
+        let garbled = KeyValue::new(FINGERPRINT_KEY.into(), "zz".to_string());
+        assert!(validate(raw(&[(1, 1010)], vec![garbled])?).is_err());
+        Ok(())
+    }
+}
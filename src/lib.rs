@@ -1,9 +1,34 @@
 #![cfg_attr(coverage_nightly, feature(coverage_attribute))]
 
 mod content;
+pub mod inspect;
+mod par;
 mod usage;
 mod users;
 
-pub use content::{Cid, Order, Slice, Timeline, Timestamp, Window, arena, blobs};
-pub use usage::{ReportUsage, Usage};
-pub use users::{Relationships, Sharding, Uid, UserMap};
+#[cfg(all(test, feature = "alloc-stats"))]
+#[global_allocator]
+static ALLOC: usage::CountingAllocator = usage::CountingAllocator;
+
+#[cfg(feature = "datafusion")]
+pub use content::TimelineTable;
+pub use content::{
+    ArenaStats, BlobPool, Cid, DrainResult, ExportReport, FreezeHook, ImportErrors, MANIFEST,
+    Order, OverlapError, Partition, PartitionScheme, Rotator, RotatorOptions, STATS_SIDECAR, Slice,
+    SnapshotDiff, StopReason, Stray, StrayError, Timeline, TimelineSnapshot, Timestamp,
+    VisibleSlice, Window, arena, blobs,
+};
+#[cfg(feature = "rayon")]
+pub use par::{set_parallelism, set_thread_pool};
+#[cfg(feature = "metrics")]
+pub use usage::record_metrics;
+#[cfg(feature = "alloc-stats")]
+pub use usage::{AllocDelta, CountingAllocator, measure};
+pub use usage::{
+    BudgetCategory, BudgetViolation, ReportUsage, ReportUsageTree, Usage, UsageBudget, UsageDelta,
+    UsageTree,
+};
+pub use users::{
+    ApplyReport, EdgeOp, Kind, KindReport, RelationshipCounts, RelationshipEvent, Relationships,
+    Routing, Sharding, Uid, UserMap, ViewerSnapshot, Visibility,
+};
@@ -0,0 +1,145 @@
+//! Parallel building blocks with one signature per operation. With the
+//! `rayon` feature they fan out on rayon, on the pool set by
+//! [`set_thread_pool`] or [`set_parallelism`] if any and the global pool
+//! otherwise. Without it they run in order on the calling thread.
+
+#[cfg(feature = "rayon")]
+mod imp {
+    use std::sync::Arc;
+
+    use anyhow::{Context, Result};
+    use arc_swap::ArcSwapOption;
+    use rayon::prelude::*;
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+    use tracing::trace;
+
+    static POOL: ArcSwapOption<ThreadPool> = ArcSwapOption::const_empty();
+
+    /// Runs crimeline's parallel work on `pool` instead of rayon's global
+    /// pool, or back on the global pool with `None`.
+    pub fn set_thread_pool(pool: Option<Arc<ThreadPool>>) {
+        POOL.store(pool);
+    }
+
+    /// Runs crimeline's parallel work on a dedicated pool of `threads`
+    /// threads. See [`set_thread_pool`] to share an existing pool.
+    pub fn set_parallelism(threads: usize) -> Result<()> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("crimeline-{i}"))
+            .build()
+            .context("build thread pool")?;
+
+        trace!(threads, "set parallelism");
+
+        set_thread_pool(Some(Arc::new(pool)));
+        Ok(())
+    }
+
+    fn install<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+        match POOL.load_full() {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+
+    /// `items.iter().enumerate().flat_map(f).collect()`, in input order.
+    pub(crate) fn flat_map<T, I, F>(items: &[T], f: F) -> Vec<I::Item>
+    where
+        T: Sync,
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(usize, &T) -> I + Send + Sync,
+    {
+        install(|| {
+            items
+                .par_iter()
+                .enumerate()
+                .flat_map_iter(|(i, item)| f(i, item))
+                .collect()
+        })
+    }
+
+    pub(crate) fn sort_unstable_by_key<T, K, F>(items: &mut [T], f: F)
+    where
+        T: Send,
+        K: Ord,
+        F: Fn(&T) -> K + Send + Sync,
+    {
+        install(|| items.par_sort_unstable_by_key(f));
+    }
+}
+
+#[cfg(not(feature = "rayon"))]
+mod imp {
+    pub(crate) fn flat_map<T, I, F>(items: &[T], f: F) -> Vec<I::Item>
+    where
+        T: Sync,
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(usize, &T) -> I + Send + Sync,
+    {
+        items
+            .iter()
+            .enumerate()
+            .flat_map(|(i, item)| f(i, item))
+            .collect()
+    }
+
+    pub(crate) fn sort_unstable_by_key<T, K, F>(items: &mut [T], f: F)
+    where
+        T: Send,
+        K: Ord,
+        F: Fn(&T) -> K + Send + Sync,
+    {
+        items.sort_unstable_by_key(f);
+    }
+}
+
+pub(crate) use imp::{flat_map, sort_unstable_by_key};
+#[cfg(feature = "rayon")]
+pub use imp::{set_parallelism, set_thread_pool};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_map_keeps_input_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let out = flat_map(&items, |i, &x| {
+            assert_eq!(i as u32, x);
+            (0..x % 3).map(move |k| (x, k))
+        });
+
+        let expected: Vec<(u32, u32)> = items
+            .iter()
+            .flat_map(|&x| (0..x % 3).map(move |k| (x, k)))
+            .collect();
+        assert_eq!(out, expected);
+    }
+
+    #[test]
+    fn sort_matches_std() {
+        let mut items: Vec<(u32, u32)> = (0..10_000u32).map(|i| (i * 7919 % 101, i)).collect();
+        let mut expected = items.clone();
+
+        sort_unstable_by_key(&mut items, |&(k, i)| (k, i));
+        expected.sort_unstable();
+        assert_eq!(items, expected);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn runs_on_configured_pool() -> anyhow::Result<()> {
+        let items = [(); 4];
+        let threads = || flat_map(&items, |_, _| [rayon::current_num_threads()]);
+
+        set_parallelism(3)?;
+        assert_eq!(threads(), [3; 4]);
+
+        set_thread_pool(None);
+        assert_eq!(threads(), [rayon::current_num_threads(); 4]);
+        Ok(())
+    }
+}
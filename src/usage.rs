@@ -1,18 +1,64 @@
+use std::alloc::Layout;
+use std::borrow::Cow;
 use std::fmt;
 use std::ops::AddAssign;
+use std::sync::atomic::AtomicUsize;
 
+use arc_swap::ArcSwap;
 use humansize::{BINARY, SizeFormatter};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tracing::warn;
 
-#[derive(Default)]
+/// Memory accounting for a structure or a group of observed items.
+///
+/// With the `serde` feature, serializes with the field names below, which are
+/// part of the compatibility surface:
+///
+/// - `active`: observed items with any heap usage
+/// - `count`: observed items
+/// - `histogram`: heap sizes of observed items as `[bucket, count]` pairs,
+///   bucket `k` holding `2^(k-1)..2^k` bytes, omitted when empty
+/// - `items`: logical elements held, e.g. arena entries or edges
+/// - `label`: what `overhead` accounts for, e.g. `"locks"`
+/// - `max` / `min`: largest and smallest heap usage of an observed item
+/// - `overhead`: fixed bytes outside the observed items
+/// - `top`: largest named items as `[name, bytes]`, omitted when empty
+/// - `blob_bytes`, `disk`, `heap`, `mapped`, `waste`: totals in bytes
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
 pub struct Usage {
     active: usize,
     count: usize,
-    label: &'static str,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Histogram::is_empty")
+    )]
+    histogram: Histogram,
+    label: Cow<'static, str>,
     max: usize,
     min: usize,
     overhead: usize,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    top: Vec<(String, usize)>,
+    /// Bound on `top`, `DEFAULT_TOP_N` if unset.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    top_n: Option<usize>,
+    /// Logical blob payload, without file framing or padding. Compare with
+    /// `disk` or `mapped` for per-arena overhead.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub blob_bytes: u64,
     pub disk: u64,
     pub heap: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub items: usize,
+    /// Memory-mapped file bytes, resident on access and counted towards RSS
+    /// but not the heap.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub mapped: u64,
     pub waste: usize,
 }
 
@@ -20,10 +66,138 @@ pub trait ReportUsage {
     fn usage(&self) -> Usage;
 }
 
+/// Implemented by structures that can break their [`Usage`] down further.
+pub trait ReportUsageTree: ReportUsage {
+    fn usage_tree(&self) -> UsageTree;
+}
+
+/// A labeled [`Usage`] with an optional breakdown into children.
+///
+/// A node's usage is its total; children describe where it comes from and
+/// are not added on top.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct UsageTree {
+    pub children: Vec<UsageTree>,
+    pub label: Cow<'static, str>,
+    pub usage: Usage,
+}
+
+/// Signed change between two [`Usage`] snapshots, see [`Usage::diff`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct UsageDelta {
+    pub active: isize,
+    pub count: isize,
+    pub disk: i64,
+    pub heap: isize,
+    pub mapped: i64,
+    pub waste: isize,
+}
+
+/// Named contributors kept by [`Usage::observe_named`] unless
+/// [`Usage::with_top_n`] says otherwise.
+const DEFAULT_TOP_N: usize = 5;
+
+/// Limits checked by [`Usage::check`]. Unset limits are not checked.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct UsageBudget {
+    pub disk: Option<u64>,
+    pub heap: Option<usize>,
+    pub mapped: Option<u64>,
+    /// Also emit each violation as a `tracing` warning.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub warn: bool,
+}
+
+/// What a [`BudgetViolation`] exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BudgetCategory {
+    Disk,
+    Heap,
+    Mapped,
+}
+
+/// A limit in a [`UsageBudget`] and the value that exceeded it, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct BudgetViolation {
+    pub actual: u64,
+    pub category: BudgetCategory,
+    pub limit: u64,
+}
+
+/// Counts of observed heap sizes in power-of-two buckets: bucket 0 holds
+/// zero, bucket `k` holds `2^(k-1)..2^k`.
+#[derive(Clone)]
+struct Histogram([u32; usize::BITS as usize + 1]);
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram([0; usize::BITS as usize + 1])
+    }
+}
+
+impl Histogram {
+    fn bucket(bytes: usize) -> usize {
+        (usize::BITS - bytes.leading_zeros()) as usize
+    }
+
+    fn lower_bound(bucket: usize) -> usize {
+        match bucket {
+            0 => 0,
+            k => 1 << (k - 1),
+        }
+    }
+
+    fn record(&mut self, bytes: usize) {
+        let b = &mut self.0[Self::bucket(bytes)];
+        *b = b.saturating_add(1);
+    }
+
+    #[cfg(feature = "serde")]
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|&n| n == 0)
+    }
+}
+
+/// Only non-empty buckets, as `[bucket, count]` pairs.
+#[cfg(feature = "serde")]
+impl Serialize for Histogram {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.0
+                .iter()
+                .enumerate()
+                .filter(|&(_, &n)| n > 0)
+                .map(|(bucket, &n)| (bucket, n)),
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Histogram {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut histogram = Histogram::default();
+
+        for (bucket, n) in Vec::<(usize, u32)>::deserialize(deserializer)? {
+            let slot = histogram.0.get_mut(bucket).ok_or_else(|| {
+                serde::de::Error::custom(format!("histogram bucket {bucket} out of range"))
+            })?;
+            *slot = n;
+        }
+
+        Ok(histogram)
+    }
+}
+
 impl Usage {
     pub fn new(label: &'static str, overhead: usize) -> Self {
         Usage {
-            label,
+            label: Cow::Borrowed(label),
             overhead,
             ..Default::default()
         }
@@ -45,18 +219,134 @@ impl Usage {
         self.disk += bytes;
     }
 
+    /// Adds exported archive bytes, e.g. from [`Cold::export`], to `disk`.
+    ///
+    /// [`Cold::export`]: crate::arena::Cold::export
+    pub fn add_export_bytes(&mut self, bytes: u64) {
+        self.add_disk_usage(bytes);
+    }
+
+    pub fn add_blob_bytes(&mut self, bytes: u64) {
+        self.blob_bytes += bytes;
+    }
+
+    pub fn add_items(&mut self, n: usize) {
+        self.items += n;
+    }
+
+    pub fn add_mapped_usage(&mut self, bytes: u64) {
+        self.mapped += bytes;
+    }
+
+    /// Heap plus mapped bytes per item, `None` if there are no items.
+    pub fn bytes_per_item(&self) -> Option<f64> {
+        (self.items > 0).then(|| (self.heap as u64 + self.mapped) as f64 / self.items as f64)
+    }
+
     #[allow(clippy::ptr_arg)]
     pub fn add_vec<T>(&mut self, v: &Vec<T>) {
-        self.heap += v.capacity() * size_of::<T>();
-        self.waste += (v.capacity() - v.len()) * size_of::<T>();
+        self.add_collection(v.len(), v.capacity(), size_of::<T>());
+    }
+
+    /// Adds a collection holding `len` of `capacity` slots of `elem_size`
+    /// bytes. Unused slots count as waste; a `len` above `capacity` is an
+    /// accounting bug and saturates to zero waste in release builds.
+    pub fn add_collection(&mut self, len: usize, capacity: usize, elem_size: usize) {
+        debug_assert!(len <= capacity, "len {len} exceeds capacity {capacity}");
+
+        self.heap += capacity * elem_size;
+        self.waste += capacity.saturating_sub(len) * elem_size;
+    }
+
+    /// Adds `count` locks of type `L` to the overhead. `L` is the full lock
+    /// type, e.g. `RwLock<Shard>`, so the guarded value stored inline counts
+    /// too but anything it points to does not.
+    pub fn add_lock_overhead<L>(&mut self, count: usize) {
+        self.overhead += count * size_of::<L>();
+    }
+
+    /// Adds `count` `Arc<T>` allocations to the overhead: the strong and weak
+    /// counters plus the inline `T`, padded to `T`'s alignment. The pointers
+    /// themselves belong to whoever holds them.
+    pub fn add_arc_overhead<T>(&mut self, count: usize) {
+        let inner = Layout::new::<[AtomicUsize; 2]>()
+            .extend(Layout::new::<T>())
+            .expect("arc layout")
+            .0
+            .pad_to_align();
+
+        self.overhead += count * inner.size();
+    }
+
+    /// Adds an `ArcSwap<T>` and the `Arc<T>` allocation it currently points to
+    /// to the overhead. What `T` owns on the heap is not included.
+    pub fn add_arcswap_overhead<T>(&mut self) {
+        self.overhead += size_of::<ArcSwap<T>>();
+        self.add_arc_overhead::<T>(1);
+    }
+
+    /// Fixed bytes for locks, headers and the like, including those reported
+    /// by observed items.
+    pub fn overhead(&self) -> usize {
+        self.overhead
+    }
+
+    /// Returns what changed since `earlier`, e.g. between two scrapes.
+    pub fn diff(&self, earlier: &Usage) -> UsageDelta {
+        UsageDelta {
+            active: self.active as isize - earlier.active as isize,
+            count: self.count as isize - earlier.count as isize,
+            disk: self.disk as i64 - earlier.disk as i64,
+            heap: self.heap as isize - earlier.heap as isize,
+            mapped: self.mapped as i64 - earlier.mapped as i64,
+            waste: self.waste as isize - earlier.waste as isize,
+        }
     }
 
     pub fn observe<T: ReportUsage>(&mut self, item: &T) {
+        self.observe_usage(&item.usage());
+    }
+
+    /// Like [`observe`](Self::observe), but also keeps `name` among the top
+    /// contributors if the item is one of the largest by heap plus mapped
+    /// bytes. At most [`with_top_n`](Self::with_top_n) names are kept.
+    pub fn observe_named<T: ReportUsage>(&mut self, name: impl Into<String>, item: &T) {
         let u = item.usage();
+        self.observe_usage(&u);
 
+        let n = self.top_n.unwrap_or(DEFAULT_TOP_N);
+        let bytes = u.heap + u.mapped as usize;
+
+        if n == 0 || (self.top.len() >= n && self.top.last().is_some_and(|&(_, b)| b >= bytes)) {
+            return;
+        }
+
+        let pos = self.top.partition_point(|&(_, b)| b >= bytes);
+        self.top.insert(pos, (name.into(), bytes));
+        self.top.truncate(n);
+    }
+
+    /// Keeps at most `n` contributors in [`top_contributors`](Self::top_contributors).
+    pub fn with_top_n(mut self, n: usize) -> Self {
+        self.top_n = Some(n);
+        self.top.truncate(n);
+        self
+    }
+
+    /// The largest items seen by [`observe_named`](Self::observe_named) as
+    /// `(name, heap + mapped bytes)`, largest first.
+    pub fn top_contributors(&self) -> &[(String, usize)] {
+        &self.top
+    }
+
+    fn observe_usage(&mut self, u: &Usage) {
+        self.overhead += u.overhead;
         self.heap += u.heap;
         self.waste += u.waste;
+        self.blob_bytes += u.blob_bytes;
         self.disk += u.disk;
+        self.items += u.items;
+        self.mapped += u.mapped;
         self.count += 1;
 
         if u.heap > 0 {
@@ -70,54 +360,493 @@ impl Usage {
             self.min = self.min.min(u.heap);
             self.max = self.max.max(u.heap);
         }
+
+        self.histogram.record(u.heap);
+    }
+
+    /// Estimates the `p`th percentile (`0.0..=100.0`) of observed heap sizes.
+    ///
+    /// Sizes are kept in power-of-two buckets, so the result is the lower
+    /// bound of the matching bucket (exact for powers of two), clamped to the
+    /// observed min and max. Returns 0 if nothing was observed.
+    pub fn percentile(&self, p: f64) -> usize {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = ((p.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as usize).max(1);
+
+        if rank >= self.count {
+            return self.max;
+        }
+
+        let mut seen = 0;
+
+        for (bucket, &n) in self.histogram.0.iter().enumerate() {
+            seen += n as usize;
+
+            if seen >= rank {
+                return Histogram::lower_bound(bucket).clamp(self.min, self.max);
+            }
+        }
+
+        self.max
     }
 }
 
 impl AddAssign for Usage {
     fn add_assign(&mut self, rhs: Self) {
+        self.overhead += rhs.overhead;
         self.heap += rhs.heap;
         self.waste += rhs.waste;
+        self.blob_bytes += rhs.blob_bytes;
         self.disk += rhs.disk;
+        self.items += rhs.items;
+        self.mapped += rhs.mapped;
     }
 }
 
 impl fmt::Display for Usage {
+    /// Single line by default; `{:#}` gives the multi-line breakdown,
+    /// including heap percentiles of observed items.
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return self.fmt_verbose(f);
+        }
+
         let min = if self.count == 0 { 0 } else { self.min };
 
         write!(
             f,
-            "{} ({} {} + {} data, {} wasted",
+            "{} ({}{}{} + {} data, {} wasted",
             SizeFormatter::new(self.overhead + self.heap, BINARY),
             SizeFormatter::new(self.overhead, BINARY),
+            self.label_separator(),
             self.label,
             SizeFormatter::new(self.heap, BINARY),
             SizeFormatter::new(self.waste, BINARY),
         )?;
 
+        if self.items > 0 {
+            write!(f, ", {} items", self.items)?;
+        }
+
         if self.disk > 0 {
             write!(f, ", {} on disk", SizeFormatter::new(self.disk, BINARY),)?;
         }
 
-        if self.count > 0 {
-            write!(
-                f,
-                ") across {} ({} active, {}..{})",
-                self.count,
-                self.active,
-                SizeFormatter::new(min, BINARY),
-                SizeFormatter::new(self.max, BINARY),
-            )
+        if self.mapped > 0 {
+            write!(f, ", {} mapped", SizeFormatter::new(self.mapped, BINARY))?;
+        }
+
+        if self.count == 0 {
+            return write!(f, ")");
+        }
+
+        write!(
+            f,
+            ") across {} ({} active, {}..{}",
+            self.count,
+            self.active,
+            SizeFormatter::new(min, BINARY),
+            SizeFormatter::new(self.max, BINARY),
+        )?;
+
+        write!(f, ")")
+    }
+}
+
+impl Usage {
+    /// Space between the overhead size and its label, none if unlabeled.
+    fn label_separator(&self) -> &'static str {
+        if self.label.is_empty() { "" } else { " " }
+    }
+
+    /// The multi-line breakdown behind `{:#}`, one aligned field per line.
+    fn fmt_verbose(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = |bytes: u64| SizeFormatter::new(bytes, BINARY);
+        let min = if self.count == 0 { 0 } else { self.min };
+
+        writeln!(
+            f,
+            "{:<9}{}",
+            "total",
+            size((self.overhead + self.heap) as u64)
+        )?;
+        writeln!(
+            f,
+            "{:<9}{}{}{}",
+            "overhead",
+            size(self.overhead as u64),
+            self.label_separator(),
+            self.label
+        )?;
+        writeln!(f, "{:<9}{}", "data", size(self.heap as u64))?;
+        writeln!(f, "{:<9}{}", "waste", size(self.waste as u64))?;
+        writeln!(f, "{:<9}{}", "disk", size(self.disk))?;
+        writeln!(f, "{:<9}{}", "mapped", size(self.mapped))?;
+        writeln!(f, "{:<9}{}", "items", self.items)?;
+        writeln!(f, "{:<9}{} ({} active)", "count", self.count, self.active)?;
+        write!(
+            f,
+            "{:<9}min {}, p50 {}, p95 {}, max {}",
+            "heap",
+            size(min as u64),
+            size(self.percentile(50.0) as u64),
+            size(self.percentile(95.0) as u64),
+            size(self.max as u64),
+        )?;
+
+        for (i, (name, bytes)) in self.top.iter().enumerate() {
+            let key = if i == 0 { "top" } else { "" };
+            write!(f, "\n{key:<9}{name} {}", size(*bytes as u64))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl UsageTree {
+    pub fn new(label: impl Into<Cow<'static, str>>, usage: Usage) -> Self {
+        UsageTree {
+            children: Vec::new(),
+            label: label.into(),
+            usage,
+        }
+    }
+
+    /// Observes `item` into this node's usage and adds its breakdown as a
+    /// child labeled `label`.
+    pub fn observe_child<T: ReportUsageTree>(
+        &mut self,
+        label: impl Into<Cow<'static, str>>,
+        item: &T,
+    ) -> &mut Self {
+        let mut child = item.usage_tree();
+        child.label = label.into();
+
+        self.usage.observe_named(child.label.as_ref(), &child.usage);
+        self.children.push(child);
+        self
+    }
+
+    /// Adds `child` as-is, without changing this node's usage.
+    pub fn with_child(mut self, child: UsageTree) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    fn fmt_depth(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let indent = depth * 2;
+
+        if f.alternate() {
+            write!(f, "{:indent$}{}:", "", self.label)?;
+
+            for line in format!("{:#}", self.usage).lines() {
+                write!(f, "\n{:indent$}{line}", "", indent = indent + 2)?;
+            }
         } else {
-            write!(f, ")")
+            write!(f, "{:indent$}{}: {}", "", self.label, self.usage)?;
         }
+
+        for child in &self.children {
+            writeln!(f)?;
+            child.fmt_depth(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reports an already computed usage, so trees can observe their children.
+impl ReportUsage for Usage {
+    fn usage(&self) -> Usage {
+        self.clone()
+    }
+}
+
+/// One line per node, children indented by two spaces.
+impl fmt::Display for UsageTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_depth(f, 0)
     }
 }
 
+/// Emits the usage as gauges named `{prefix}_heap_bytes`,
+/// `{prefix}_waste_bytes`, `{prefix}_disk_bytes`, `{prefix}_mapped_bytes`,
+/// `{prefix}_count` and `{prefix}_active` via the `metrics` facade.
+#[cfg(feature = "metrics")]
+pub fn record_metrics(prefix: &str, u: &Usage) {
+    record_metrics_labeled(prefix, u, Vec::new());
+}
+
+#[cfg(feature = "metrics")]
+pub(crate) fn record_metrics_labeled(prefix: &str, u: &Usage, labels: Vec<metrics::Label>) {
+    let gauges = [
+        ("heap_bytes", u.heap as f64),
+        ("waste_bytes", u.waste as f64),
+        ("disk_bytes", u.disk as f64),
+        ("mapped_bytes", u.mapped as f64),
+        ("count", u.count as f64),
+        ("active", u.active as f64),
+    ];
+
+    for (name, value) in gauges {
+        metrics::gauge!(format!("{prefix}_{name}"), labels.clone()).set(value);
+    }
+}
+
+fn signed_size(f: &mut fmt::Formatter<'_>, bytes: i64, what: &str) -> fmt::Result {
+    let sign = if bytes < 0 { '-' } else { '+' };
+    write!(
+        f,
+        "{sign}{} {what}",
+        SizeFormatter::new(bytes.unsigned_abs(), BINARY)
+    )
+}
+
+impl Usage {
+    /// Returns every limit in `budget` that this usage exceeds, in the order
+    /// heap, disk, mapped.
+    pub fn check(&self, budget: &UsageBudget) -> Vec<BudgetViolation> {
+        let checks = [
+            (
+                BudgetCategory::Heap,
+                budget.heap.map(|l| l as u64),
+                self.heap as u64,
+            ),
+            (BudgetCategory::Disk, budget.disk, self.disk),
+            (BudgetCategory::Mapped, budget.mapped, self.mapped),
+        ];
+
+        let violations: Vec<_> = checks
+            .into_iter()
+            .filter_map(|(category, limit, actual)| {
+                let limit = limit?;
+                (actual > limit).then_some(BudgetViolation {
+                    actual,
+                    category,
+                    limit,
+                })
+            })
+            .collect();
+
+        if budget.warn {
+            for v in &violations {
+                warn!(label = %self.label, "{v}");
+            }
+        }
+
+        violations
+    }
+}
+
+impl fmt::Display for BudgetCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BudgetCategory::Disk => "disk",
+            BudgetCategory::Heap => "heap",
+            BudgetCategory::Mapped => "mapped",
+        })
+    }
+}
+
+impl fmt::Display for BudgetViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} usage {} exceeds budget of {}",
+            self.category,
+            SizeFormatter::new(self.actual, BINARY),
+            SizeFormatter::new(self.limit, BINARY),
+        )
+    }
+}
+
+impl fmt::Display for UsageDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        signed_size(f, self.heap as i64, "heap")?;
+        f.write_str(", ")?;
+        signed_size(f, self.waste as i64, "waste")?;
+        f.write_str(", ")?;
+        signed_size(f, self.disk, "disk")?;
+        f.write_str(", ")?;
+        signed_size(f, self.mapped, "mapped")?;
+
+        if self.count != 0 {
+            write!(f, ", count {:+}", self.count)?;
+        }
+
+        if self.active != 0 {
+            write!(f, ", active {:+}", self.active)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Heap allocations made on the current thread while [`measure`] ran.
+#[cfg(feature = "alloc-stats")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct AllocDelta {
+    pub allocated: usize,
+    pub allocations: usize,
+    pub freed: usize,
+}
+
+#[cfg(feature = "alloc-stats")]
+impl AllocDelta {
+    /// Bytes still allocated when `f` returned, i.e. retained by its result.
+    pub fn net(&self) -> isize {
+        self.allocated as isize - self.freed as isize
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+thread_local! {
+    static ALLOC_COUNTERS: std::cell::Cell<AllocDelta> = const {
+        std::cell::Cell::new(AllocDelta { allocated: 0, allocations: 0, freed: 0 })
+    };
+}
+
+/// Wraps the system allocator to count allocations per thread for
+/// [`measure`]. Install it as the `#[global_allocator]` to use it.
+#[cfg(feature = "alloc-stats")]
+pub struct CountingAllocator;
+
+#[cfg(feature = "alloc-stats")]
+impl CountingAllocator {
+    fn record(allocated: usize, freed: usize) {
+        // Fails only during thread teardown, when nothing is measured anyway.
+        let _ = ALLOC_COUNTERS.try_with(|c| {
+            let mut d = c.get();
+            d.allocated += allocated;
+            d.allocations += usize::from(allocated > 0);
+            d.freed += freed;
+            c.set(d);
+        });
+    }
+}
+
+#[cfg(feature = "alloc-stats")]
+unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { std::alloc::System.alloc(layout) };
+        if !ptr.is_null() {
+            Self::record(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { std::alloc::System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            Self::record(layout.size(), 0);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { std::alloc::System.dealloc(ptr, layout) };
+        Self::record(0, layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new = unsafe { std::alloc::System.realloc(ptr, layout, new_size) };
+        if !new.is_null() {
+            Self::record(new_size, layout.size());
+        }
+        new
+    }
+}
+
+/// Runs `f` and returns its result with the allocations it made on this
+/// thread. Reports zeros unless [`CountingAllocator`] is the global allocator.
+#[cfg(feature = "alloc-stats")]
+pub fn measure<T, F: FnOnce() -> T>(f: F) -> (T, AllocDelta) {
+    let before = ALLOC_COUNTERS.with(std::cell::Cell::get);
+    let out = f();
+    let after = ALLOC_COUNTERS.with(std::cell::Cell::get);
+
+    let delta = AllocDelta {
+        allocated: after.allocated - before.allocated,
+        allocations: after.allocations - before.allocations,
+        freed: after.freed - before.freed,
+    };
+
+    (out, delta)
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
+    /// Builds a structure under [`measure`] and asserts its reported heap
+    /// plus overhead is within `tolerance` (a fraction) of the bytes it
+    /// actually retained.
+    #[cfg(feature = "alloc-stats")]
+    pub(crate) fn assert_usage_within<T: ReportUsage>(
+        tolerance: f64,
+        build: impl FnOnce() -> T,
+    ) -> T {
+        let (built, delta) = measure(build);
+        let usage = built.usage();
+        let reported = (usage.heap + usage.overhead) as f64;
+        let actual = delta.net() as f64;
+
+        assert!(
+            (reported - actual).abs() <= tolerance * actual.max(reported),
+            "reported heap {reported} B, allocator retained {actual} B ({delta:?})"
+        );
+
+        built
+    }
+
+    /// Runs `f` against a local debugging recorder and returns the gauges it
+    /// set, keyed by name and sorted labels.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn capture_gauges(
+        f: impl FnOnce(),
+    ) -> std::collections::BTreeMap<(String, Vec<(String, String)>), f64> {
+        use metrics_util::debugging::DebugValue;
+
+        capture_metrics(f, |value| match value {
+            DebugValue::Gauge(v) => Some(v.0),
+            _ => None,
+        })
+    }
+
+    /// Runs `f` against a local debugging recorder and returns what `pick`
+    /// keeps of each metric it recorded, keyed by name and sorted labels.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn capture_metrics<T>(
+        f: impl FnOnce(),
+        pick: impl Fn(metrics_util::debugging::DebugValue) -> Option<T>,
+    ) -> std::collections::BTreeMap<(String, Vec<(String, String)>), T> {
+        use metrics_util::debugging::DebuggingRecorder;
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, f);
+
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, _, _, value)| {
+                let value = pick(value)?;
+                let key = key.key();
+                let mut labels: Vec<_> = key
+                    .labels()
+                    .map(|l| (l.key().to_string(), l.value().to_string()))
+                    .collect();
+                labels.sort();
+                Some(((key.name().to_string(), labels), value))
+            })
+            .collect()
+    }
+
     struct FakeItem {
         heap: usize,
         waste: usize,
@@ -134,6 +863,16 @@ mod tests {
         }
     }
 
+    struct MappedItem(u64);
+
+    impl ReportUsage for MappedItem {
+        fn usage(&self) -> Usage {
+            let mut u = Usage::default();
+            u.add_mapped_usage(self.0);
+            u
+        }
+    }
+
     #[test]
     fn add_assign_nests_totals_only() {
         let mut parent = Usage::new("test", 0);
@@ -153,6 +892,11 @@ mod tests {
         assert_eq!(parent.heap, 150);
         assert_eq!(parent.waste, 15);
         assert_eq!(parent.disk, 1000);
+        let mut mapped = Usage::default();
+        mapped.add_mapped_usage(2048);
+        parent += mapped;
+        assert_eq!(parent.mapped, 2048);
+
         // count/active/min/max unchanged by +=
         assert_eq!(parent.count, 1);
         assert_eq!(parent.active, 1);
@@ -183,6 +927,361 @@ mod tests {
         assert_eq!(u.waste, 7 * size_of::<u32>());
     }
 
+    fn observe_heaps(heaps: impl IntoIterator<Item = usize>) -> Usage {
+        let mut usage = Usage::new("arenas", 0);
+        for heap in heaps {
+            usage.observe(&FakeItem {
+                heap,
+                waste: 0,
+                disk: 0,
+            });
+        }
+        usage
+    }
+
+    #[test]
+    fn percentile_uniform() {
+        let usage = observe_heaps([1024; 20]);
+        assert_eq!(usage.percentile(0.0), 1024);
+        assert_eq!(usage.percentile(50.0), 1024);
+        assert_eq!(usage.percentile(100.0), 1024);
+    }
+
+    #[test]
+    fn percentile_skewed() {
+        // one 2 GiB arena among fifty 64 MiB ones
+        let usage = observe_heaps((0..50).map(|_| 64 << 20).chain([2 << 30]));
+
+        assert_eq!(usage.percentile(50.0), 64 << 20);
+        assert_eq!(usage.percentile(95.0), 64 << 20);
+        assert_eq!(usage.percentile(98.0), 64 << 20);
+        assert_eq!(usage.percentile(99.0), 2 << 30, "rank 51 of 51");
+        assert_eq!(usage.percentile(100.0), 2 << 30);
+    }
+
+    #[test]
+    fn percentile_power_of_two_steps() {
+        // 1, 2, 4, ..., 512 bytes: each in its own bucket
+        let usage = observe_heaps((0..10).map(|k| 1 << k));
+
+        assert_eq!(usage.percentile(10.0), 1);
+        assert_eq!(usage.percentile(50.0), 16);
+        assert_eq!(usage.percentile(90.0), 256);
+        assert_eq!(usage.percentile(95.0), 512);
+        assert_eq!(observe_heaps([]).percentile(50.0), 0);
+    }
+
+    #[test]
+    fn percentile_within_factor_of_two() {
+        let usage = observe_heaps([0, 0, 300, 700, 1500]);
+
+        assert_eq!(usage.percentile(20.0), 0);
+        assert_eq!(usage.percentile(60.0), 256, "300 falls in 256..512");
+        assert_eq!(usage.percentile(80.0), 512, "700 falls in 512..1024");
+        assert_eq!(usage.percentile(100.0), 1500);
+    }
+
+    #[test]
+    fn display_alternate_reports_percentiles() {
+        let usage = observe_heaps((0..50).map(|_| 64 << 20).chain([2 << 30]));
+
+        let plain = format!("{usage}");
+        assert!(plain.ends_with("(51 active, 64 MiB..2 GiB)"), "{plain}");
+        assert!(
+            format!("{usage:#}").ends_with(
+                "count    51 (51 active)
+heap     min 64 MiB, p50 64 MiB, p95 64 MiB, max 2 GiB"
+            ),
+            "{usage:#}"
+        );
+    }
+
+    #[test]
+    fn add_collection_counts_unused_slots() {
+        let mut u = Usage::default();
+        u.add_collection(3, 8, 4);
+        assert_eq!((u.heap, u.waste), (32, 20));
+
+        let mut v = Vec::<u64>::with_capacity(4);
+        v.push(1);
+        u.add_vec(&v);
+        assert_eq!((u.heap, u.waste), (64, 44));
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[test]
+    fn add_collection_saturates_waste() {
+        let mut u = Usage::default();
+        u.add_collection(10, 4, 8);
+        assert_eq!((u.heap, u.waste), (32, 0));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "len 10 exceeds capacity 4")]
+    fn add_collection_asserts_len_within_capacity() {
+        Usage::default().add_collection(10, 4, 8);
+    }
+
+    #[test]
+    fn observe_named_keeps_largest() {
+        let mut usage = Usage::new("locks", 0).with_top_n(2);
+        for (name, heap) in [("a", 10), ("b", 300), ("c", 0), ("d", 20), ("e", 300)] {
+            usage.observe_named(
+                name,
+                &FakeItem {
+                    heap,
+                    waste: 0,
+                    disk: 0,
+                },
+            );
+        }
+        usage.observe_named("mapped", &MappedItem(200));
+
+        assert_eq!(usage.count, 6);
+        assert_eq!(
+            usage.top_contributors(),
+            [("b".to_string(), 300), ("e".to_string(), 300)]
+        );
+        assert!(format!("{usage:#}").ends_with("\ntop      b 300 B\n         e 300 B"));
+
+        let usage = usage.with_top_n(1);
+        assert_eq!(usage.top_contributors().len(), 1);
+
+        let mut none = Usage::default().with_top_n(0);
+        none.observe_named("x", &MappedItem(1));
+        assert!(none.top_contributors().is_empty());
+    }
+
+    #[test]
+    fn check_reports_each_category() {
+        let mut usage = Usage::default();
+        usage.add_heap_usage(2048);
+        usage.add_disk_usage(4096);
+        usage.add_mapped_usage(8192);
+
+        let under = UsageBudget {
+            disk: Some(4096),
+            heap: Some(2048),
+            mapped: Some(8192),
+            warn: true,
+        };
+        assert!(usage.check(&under).is_empty(), "limits are inclusive");
+        assert!(usage.check(&UsageBudget::default()).is_empty());
+
+        let over = |category, limit| BudgetViolation {
+            actual: match category {
+                BudgetCategory::Disk => 4096,
+                BudgetCategory::Heap => 2048,
+                BudgetCategory::Mapped => 8192,
+            },
+            category,
+            limit,
+        };
+
+        let budget = UsageBudget {
+            heap: Some(1024),
+            ..Default::default()
+        };
+        assert_eq!(usage.check(&budget), [over(BudgetCategory::Heap, 1024)]);
+
+        let budget = UsageBudget {
+            disk: Some(1024),
+            ..Default::default()
+        };
+        assert_eq!(usage.check(&budget), [over(BudgetCategory::Disk, 1024)]);
+
+        let budget = UsageBudget {
+            mapped: Some(1024),
+            ..Default::default()
+        };
+        assert_eq!(usage.check(&budget), [over(BudgetCategory::Mapped, 1024)]);
+
+        let budget = UsageBudget {
+            disk: Some(0),
+            heap: Some(0),
+            mapped: Some(0),
+            warn: true,
+        };
+        let all = usage.check(&budget);
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].to_string(), "heap usage 2 KiB exceeds budget of 0 B");
+    }
+
+    #[test]
+    fn sync_overhead_sizes() {
+        use parking_lot::{Mutex, RwLock};
+        use std::sync::Arc;
+
+        let mut u = Usage::new("sync", 0);
+        u.add_lock_overhead::<RwLock<Vec<u32>>>(3);
+        assert_eq!(u.overhead, 3 * size_of::<RwLock<Vec<u32>>>());
+
+        let mut u = Usage::new("sync", 0);
+        u.add_lock_overhead::<Mutex<Option<u64>>>(2);
+        assert_eq!(u.overhead, 2 * size_of::<Mutex<Option<u64>>>());
+
+        let mut u = Usage::new("sync", 0);
+        u.add_arc_overhead::<u64>(4);
+        assert_eq!(u.overhead, 4 * (2 * size_of::<usize>() + size_of::<u64>()));
+
+        // Counters come first, so a small `T` is padded to their alignment.
+        let mut u = Usage::new("sync", 0);
+        u.add_arc_overhead::<u8>(1);
+        assert_eq!(u.overhead, 3 * size_of::<usize>());
+
+        let mut u = Usage::new("sync", 0);
+        u.add_arcswap_overhead::<Vec<Arc<u32>>>();
+        assert_eq!(
+            u.overhead,
+            size_of::<ArcSwap<Vec<Arc<u32>>>>()
+                + 2 * size_of::<usize>()
+                + size_of::<Vec<Arc<u32>>>()
+        );
+    }
+
+    #[test]
+    fn diff_signed_deltas() {
+        let earlier = observe_heaps([1024, 0]);
+        let mut later = earlier.clone();
+        later.observe(&FakeItem {
+            heap: 3 << 20,
+            waste: 0,
+            disk: 4096,
+        });
+        later.add_heap_waste(10);
+
+        let delta = later.diff(&earlier);
+        assert_eq!(
+            delta,
+            UsageDelta {
+                active: 1,
+                count: 1,
+                disk: 4096,
+                heap: 3 << 20,
+                mapped: 0,
+                waste: 10,
+            }
+        );
+        assert_eq!(
+            delta.to_string(),
+            "+3 MiB heap, +10 B waste, +4 KiB disk, +0 B mapped, count +1, active +1",
+        );
+
+        let back = earlier.diff(&later);
+        assert_eq!(back.heap, -(3 << 20));
+        assert_eq!(
+            back.to_string(),
+            "-3 MiB heap, -10 B waste, -4 KiB disk, +0 B mapped, count -1, active -1",
+        );
+        assert_eq!(
+            earlier.diff(&earlier).to_string(),
+            "+0 B heap, +0 B waste, +0 B disk, +0 B mapped"
+        );
+    }
+
+    struct FakeTree(usize, usize);
+
+    impl ReportUsage for FakeTree {
+        fn usage(&self) -> Usage {
+            let mut u = Usage::default();
+            u.add_heap_usage(self.0 + self.1);
+            u
+        }
+    }
+
+    impl ReportUsageTree for FakeTree {
+        fn usage_tree(&self) -> UsageTree {
+            let (mut a, mut b) = (Usage::default(), Usage::default());
+            a.add_heap_usage(self.0);
+            b.add_heap_usage(self.1);
+
+            UsageTree::new("fake", self.usage())
+                .with_child(UsageTree::new("a", a))
+                .with_child(UsageTree::new("b", b))
+        }
+    }
+
+    #[test]
+    fn tree_observes_children() {
+        let mut tree = UsageTree::new("root", Usage::new("locks", 64));
+        tree.observe_child("first", &FakeTree(100, 28))
+            .observe_child("second", &FakeTree(0, 0));
+
+        assert_eq!(tree.usage.heap, 128);
+        assert_eq!(tree.usage.count, 2);
+        assert_eq!(tree.usage.active, 1);
+        assert_eq!(tree.children[0].label, "first");
+        assert_eq!(tree.children[0].children.len(), 2);
+        assert_eq!(
+            tree.to_string(),
+            "root: 192 B (64 B locks + 128 B data, 0 B wasted) across 2 (1 active, 0 B..128 B)
+  first: 128 B (0 B + 128 B data, 0 B wasted)
+    a: 100 B (0 B + 100 B data, 0 B wasted)
+    b: 28 B (0 B + 28 B data, 0 B wasted)
+  second: 0 B (0 B + 0 B data, 0 B wasted)
+    a: 0 B (0 B + 0 B data, 0 B wasted)
+    b: 0 B (0 B + 0 B data, 0 B wasted)",
+        );
+        assert!(
+            format!("{tree:#}").starts_with("root:\n  total    192 B\n  overhead 64 B locks"),
+            "{tree:#}"
+        );
+        assert!(
+            format!("{tree:#}").contains("\n    a:\n      total    100 B\n      overhead 0 B\n")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn tree_serde_shape() -> serde_json::Result<()> {
+        let tree = FakeTree(100, 28).usage_tree();
+
+        let json = serde_json::to_value(&tree)?;
+        assert_eq!(json["label"], "fake");
+        assert_eq!(json["usage"]["heap"], 128);
+        assert_eq!(json["children"][1]["label"], "b");
+        assert_eq!(json["children"][1]["usage"]["heap"], 28);
+        assert_eq!(json["children"][1]["children"], serde_json::json!([]));
+
+        let back: UsageTree = serde_json::from_value(json)?;
+        assert_eq!(back.to_string(), tree.to_string());
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn record_metrics_gauges() {
+        let mut usage = Usage::new("locks", 64);
+        usage.observe(&FakeItem {
+            heap: 512,
+            waste: 32,
+            disk: 4096,
+        });
+        usage.observe(&MappedItem(2048));
+
+        let gauges = capture_gauges(|| record_metrics("crimeline_test", &usage));
+        let got: Vec<(&str, f64)> = gauges
+            .iter()
+            .map(|((name, labels), &v)| {
+                assert!(labels.is_empty());
+                (name.as_str(), v)
+            })
+            .collect();
+
+        assert_eq!(
+            got,
+            [
+                ("crimeline_test_active", 1.0),
+                ("crimeline_test_count", 2.0),
+                ("crimeline_test_disk_bytes", 4096.0),
+                ("crimeline_test_heap_bytes", 512.0),
+                ("crimeline_test_mapped_bytes", 2048.0),
+                ("crimeline_test_waste_bytes", 32.0),
+            ]
+        );
+    }
+
     #[test]
     fn display_empty() {
         let usage = Usage::new("locks", 2048);
@@ -190,6 +1289,18 @@ mod tests {
             format!("{usage}"),
             "2 KiB (2 KiB locks + 0 B data, 0 B wasted)",
         );
+        assert_eq!(
+            format!("{usage:#}"),
+            "total    2 KiB
+overhead 2 KiB locks
+data     0 B
+waste    0 B
+disk     0 B
+mapped   0 B
+items    0
+count    0 (0 active)
+heap     min 0 B, p50 0 B, p95 0 B, max 0 B",
+        );
     }
 
     #[test]
@@ -218,6 +1329,18 @@ mod tests {
             format!("{usage}"),
             "464 B (64 B locks + 400 B data, 72 B wasted) across 3 (2 active, 0 B..300 B)",
         );
+        assert_eq!(
+            format!("{usage:#}"),
+            "total    464 B
+overhead 64 B locks
+data     400 B
+waste    72 B
+disk     0 B
+mapped   0 B
+items    0
+count    3 (2 active)
+heap     min 0 B, p50 64 B, p95 300 B, max 300 B",
+        );
     }
 
     #[test]
@@ -248,6 +1371,138 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn budget_and_delta_serde_roundtrip() -> serde_json::Result<()> {
+        let delta = UsageDelta {
+            active: -1,
+            count: 2,
+            disk: -4096,
+            heap: 100,
+            mapped: 0,
+            waste: -3,
+        };
+        let json = serde_json::to_string(&delta)?;
+        assert_eq!(serde_json::from_str::<UsageDelta>(&json)?, delta);
+
+        let budget: UsageBudget = serde_json::from_str(r#"{"heap": 1024, "disk": null}"#)?;
+        assert_eq!(
+            (budget.heap, budget.disk, budget.mapped, budget.warn),
+            (Some(1024), None, None, false)
+        );
+
+        let violation = BudgetViolation {
+            actual: 2048,
+            category: BudgetCategory::Mapped,
+            limit: 1024,
+        };
+        let json = serde_json::to_value(violation)?;
+        assert_eq!(
+            json,
+            serde_json::json!({"actual": 2048, "category": "mapped", "limit": 1024})
+        );
+        assert_eq!(serde_json::from_value::<BudgetViolation>(json)?, violation);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_json_shape() -> serde_json::Result<()> {
+        let mut usage = Usage::new("locks", 64);
+        usage.observe(&FakeItem {
+            heap: 512,
+            waste: 32,
+            disk: 4096,
+        });
+
+        let json = serde_json::to_value(&usage)?;
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "active": 1,
+                "count": 1,
+                "histogram": [[10, 1]],
+                "label": "locks",
+                "max": 512,
+                "min": 512,
+                "overhead": 64,
+                "blob_bytes": 0,
+                "disk": 4096,
+                "heap": 512,
+                "items": 0,
+                "mapped": 0,
+                "waste": 32,
+            })
+        );
+
+        let back: Usage = serde_json::from_value(json)?;
+        assert_eq!(back.to_string(), usage.to_string());
+        assert_eq!(back.percentile(50.0), 512);
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_keeps_percentiles() -> serde_json::Result<()> {
+        let usage = observe_heaps([0, 3, 300, 700, 64 << 20]);
+        let back: Usage = serde_json::from_str(&serde_json::to_string(&usage)?)?;
+        for p in [0.0, 20.0, 40.0, 60.0, 80.0, 100.0] {
+            assert_eq!(back.percentile(p), usage.percentile(p), "p{p}");
+        }
+
+        let bad = serde_json::json!({
+            "active": 0, "count": 1, "histogram": [[99, 1]], "label": "", "max": 0,
+            "min": 0, "overhead": 0, "disk": 0, "heap": 0, "waste": 0,
+        });
+        assert!(serde_json::from_value::<Usage>(bad).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn display_with_mapped() {
+        let mut usage = Usage::new("locks", 64);
+        usage.observe(&MappedItem(3 * 1024 * 1024));
+        usage.observe(&MappedItem(1024 * 1024));
+
+        assert_eq!(usage.mapped, 4 * 1024 * 1024);
+        assert_eq!(
+            format!("{usage}"),
+            "64 B (64 B locks + 0 B data, 0 B wasted, 4 MiB mapped) across 2 (0 active, 0 B..0 B)",
+        );
+    }
+
+    #[test]
+    fn display_with_items() {
+        let mut arena = Usage::default();
+        arena.add_heap_usage(1024);
+        arena.add_mapped_usage(3072);
+        arena.add_items(16);
+
+        let mut usage = Usage::new("locks", 64);
+        usage.observe(&arena);
+        usage.observe(&arena);
+
+        assert_eq!(usage.items, 32);
+        assert_eq!(usage.bytes_per_item(), Some(256.0));
+        assert_eq!(Usage::default().bytes_per_item(), None);
+        assert_eq!(
+            format!("{usage}"),
+            "2.06 KiB (64 B locks + 2 KiB data, 0 B wasted, 32 items, 6 KiB mapped) across 2 (2 active, 1 KiB..1 KiB)",
+        );
+        assert_eq!(
+            format!("{usage:#}"),
+            "total    2.06 KiB
+overhead 64 B locks
+data     2 KiB
+waste    0 B
+disk     0 B
+mapped   6 KiB
+items    32
+count    2 (2 active)
+heap     min 1 KiB, p50 1 KiB, p95 1 KiB, max 1 KiB",
+        );
+    }
+
     #[test]
     fn display_with_waste() {
         let mut usage = Usage::new("locks", 64);
@@ -0,0 +1,112 @@
+use parking_lot::Mutex;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::usage::Usage;
+use crate::users::Uid;
+
+/// How a viewer sees content by an author. With the `serde` feature,
+/// serializes as `"followed"`, `"hidden"` or `"unrelated"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Visibility {
+    /// Followed by the viewer and not blocked in either direction.
+    Followed,
+    /// Blocked in either direction.
+    Hidden,
+    /// Neither followed nor blocked.
+    Unrelated,
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    author: Uid,
+    generation: u64,
+    viewer: Uid,
+    visibility: Visibility,
+}
+
+/// Fixed-size, direct-mapped cache of visibility decisions.
+///
+/// Each slot remembers the relationship generation it was computed under. Any
+/// write to the underlying maps bumps that generation, so every cached entry is
+/// lazily invalidated on the next lookup. This trades hit rate under write load
+/// (a single unrelated edge change flushes the whole cache) for the guarantee
+/// that a cached answer never outlives a relevant edge change.
+pub(crate) struct DecisionCache {
+    bits: u32,
+    slots: Box<[Mutex<Option<Slot>>]>,
+}
+
+impl DecisionCache {
+    /// Creates a cache with `capacity` rounded up to a power of two.
+    pub fn new(capacity: usize) -> Self {
+        let count = capacity.max(2).next_power_of_two();
+
+        DecisionCache {
+            bits: count.trailing_zeros(),
+            slots: (0..count).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    pub fn get(&self, viewer: Uid, author: Uid, generation: u64) -> Option<Visibility> {
+        self.slots[self.slot(viewer, author)]
+            .lock()
+            .filter(|s| s.viewer == viewer && s.author == author && s.generation == generation)
+            .map(|s| s.visibility)
+    }
+
+    pub fn insert(&self, viewer: Uid, author: Uid, generation: u64, visibility: Visibility) {
+        *self.slots[self.slot(viewer, author)].lock() = Some(Slot {
+            author,
+            generation,
+            viewer,
+            visibility,
+        });
+    }
+
+    /// Adds the slot table, one lock per slot, to `usage`'s overhead.
+    pub fn add_overhead(&self, usage: &mut Usage) {
+        usage.add_lock_overhead::<Mutex<Option<Slot>>>(self.slots.len());
+    }
+
+    /// Fibonacci hashing of the mixed pair onto the slot table.
+    #[inline]
+    #[allow(clippy::unnecessary_cast)] // `Uid` may already be `u64`
+    fn slot(&self, viewer: Uid, author: Uid) -> usize {
+        let key = (viewer as u64).rotate_left(32) ^ author as u64;
+
+        (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (64 - self.bits)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capacity_rounds_to_power_of_two() {
+        assert_eq!(DecisionCache::new(0).slots.len(), 2);
+        assert_eq!(DecisionCache::new(100).slots.len(), 128);
+        assert_eq!(DecisionCache::new(128).slots.len(), 128);
+    }
+
+    #[test]
+    fn overhead_is_one_lock_per_slot() {
+        let mut u = Usage::default();
+        DecisionCache::new(100).add_overhead(&mut u);
+
+        assert_eq!(u.overhead(), 128 * size_of::<Mutex<Option<Slot>>>());
+    }
+
+    #[test]
+    fn stale_generation_misses() {
+        let cache = DecisionCache::new(16);
+        cache.insert(1, 2, 7, Visibility::Followed);
+
+        assert_eq!(cache.get(1, 2, 7), Some(Visibility::Followed));
+        assert_eq!(cache.get(1, 2, 8), None, "generation moved on");
+        assert_eq!(cache.get(2, 1, 7), None, "key is directional");
+    }
+}
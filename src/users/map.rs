@@ -1,17 +1,46 @@
 use std::fmt;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
-use parking_lot::RwLock;
+use anyhow::Result;
+use itertools::{EitherOrBoth, Itertools};
+use parking_lot::{RwLock, RwLockReadGuard};
 use tracing::{debug, trace};
 
-use crate::usage::Usage;
-use crate::users::{Uid, shard::Shard, sharding::Sharding};
+use crate::par;
+use crate::usage::{ReportUsage, ReportUsageTree, Usage, UsageTree};
+use crate::users::{Uid, routing::Routing, shard::Shard, sharding::Sharding};
 
 pub struct UserMap {
+    compact_on_merge: bool,
+    generation: AtomicU64,
+    inverse: Option<Box<UserMap>>,
     len: AtomicUsize,
+    routing: Routing,
     shard_bits: u32,
-    shard_mask: u32,
+    shard_mask: Uid,
+    sharding: Sharding,
     shards: Box<[RwLock<Shard>]>,
+    subjects: AtomicUsize,
+}
+
+/// Read guards on up to two shards, see [`UserMap::lock_two`].
+struct ShardPair<'a> {
+    first: RwLockReadGuard<'a, Shard>,
+    second: Option<RwLockReadGuard<'a, Shard>>,
+    swapped: bool,
+}
+
+impl ShardPair<'_> {
+    /// Returns the shards in the order they were requested.
+    fn get(&self) -> (&Shard, &Shard) {
+        let second = self.second.as_deref().unwrap_or(&self.first);
+
+        if self.swapped {
+            (second, &self.first)
+        } else {
+            (&self.first, second)
+        }
+    }
 }
 
 #[cfg_attr(coverage_nightly, coverage(off))]
@@ -22,33 +51,111 @@ impl Default for UserMap {
 }
 
 impl UserMap {
+    /// # Panics
+    ///
+    /// If `sharding` is too wide for the configured uid width, see
+    /// [`Sharding::validate`]. Use [`try_new`](Self::try_new) to handle this.
     pub fn new(sharding: Sharding) -> Self {
+        Self::new_with_routing(sharding, Routing::LowBits)
+    }
+
+    /// Like [`new`](Self::new), but routes with `routing`.
+    pub fn new_with_routing(sharding: Sharding, routing: Routing) -> Self {
+        Self::try_new_with_routing(sharding, routing).unwrap_or_else(|e| panic!("{e:#}"))
+    }
+
+    pub fn try_new(sharding: Sharding) -> Result<Self> {
+        Self::try_new_with_routing(sharding, Routing::LowBits)
+    }
+
+    pub fn try_new_with_routing(sharding: Sharding, routing: Routing) -> Result<Self> {
+        sharding.validate()?;
+
         let count = sharding.count();
 
         let shards: Vec<_> = (0..count).map(|_| RwLock::new(Shard::new())).collect();
 
-        debug!(shards = count, "created user map with sharding");
+        debug!(shards = count, ?routing, "created user map with sharding");
 
-        UserMap {
+        Ok(UserMap {
+            compact_on_merge: false,
+            generation: AtomicU64::new(0),
+            inverse: None,
             len: AtomicUsize::new(0),
+            routing,
             shard_bits: sharding.bits(),
-            shard_mask: sharding.mask(),
+            shard_mask: sharding.mask() as Uid,
+            sharding,
             shards: shards.into_boxed_slice(),
+            subjects: AtomicUsize::new(0),
+        })
+    }
+
+    /// Creates a map that additionally maintains a `target -> subjects`
+    /// inverse index, doubling edge memory in exchange for fast [`sources`].
+    ///
+    /// [`sources`]: UserMap::sources
+    pub fn new_with_inverse(sharding: Sharding) -> Self {
+        UserMap {
+            inverse: Some(Box::new(UserMap::new(sharding))),
+            ..UserMap::new(sharding)
+        }
+    }
+
+    /// Controls whether every bulk merge grows the list exactly and shrinks
+    /// it to fit, trading a reallocation per merge for zero slack. Off by
+    /// default, where lists grow amortized and shrink only once half empty:
+    /// a bulk load is better served by one
+    /// [`shrink_to_fit`](Self::shrink_to_fit) at the end.
+    pub fn with_compact_on_merge(mut self, compact: bool) -> Self {
+        self.compact_on_merge = compact;
+        self
+    }
+
+    /// Drops spare capacity from every adjacency list, e.g. once a bulk load
+    /// is done. Takes each shard's write lock in turn.
+    pub fn shrink_to_fit(&self) {
+        for shard in &self.shards {
+            shard.write().shrink_to_fit();
+        }
+
+        if let Some(inverse) = &self.inverse {
+            inverse.shrink_to_fit();
         }
+
+        trace!(len = self.len(), "shrank user map");
     }
 
-    pub fn add(&self, subject: Uid, target: Uid) {
+    /// Adds `target` to `subject`'s list. Returns whether the edge was new.
+    pub fn add(&self, subject: Uid, target: Uid) -> bool {
         let (s, idx) = self.find(subject);
         let mut shard = self.shards[s].write();
 
-        if shard.insert(idx, target) {
+        let was_empty = shard.get(idx).is_none_or(<[Uid]>::is_empty);
+        let added = shard.insert(idx, target);
+
+        if added {
             self.len.fetch_add(1, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+
+            if was_empty {
+                self.subjects.fetch_add(1, Ordering::Relaxed);
+            }
+
+            // The forward shard lock is held while updating the inverse so
+            // that concurrent add/remove of the same edge cannot interleave.
+            if let Some(inverse) = &self.inverse {
+                inverse.add(target, subject);
+            }
 
             trace!(subject, target, len = self.len(), "added target to subject");
         }
+
+        added
     }
 
-    pub fn add_bulk<T: IntoIterator<Item = Uid>>(&self, subject: Uid, targets: T) {
+    /// Adds all `targets` to `subject`'s list. Returns the number of new edges.
+    pub fn add_bulk<T: IntoIterator<Item = Uid>>(&self, subject: Uid, targets: T) -> usize {
         let mut incoming: Vec<Uid> = targets.into_iter().collect();
         incoming.sort_unstable();
         incoming.dedup();
@@ -56,10 +163,22 @@ impl UserMap {
         let (s, idx) = self.find(subject);
         let mut shard = self.shards[s].write();
 
-        let added = shard.merge(idx, &incoming);
+        let was_empty = shard.get(idx).is_none_or(<[Uid]>::is_empty);
+        let added = shard.merge(idx, &incoming, self.compact_on_merge);
 
         if added > 0 {
             self.len.fetch_add(added, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+
+            if was_empty {
+                self.subjects.fetch_add(1, Ordering::Relaxed);
+            }
+
+            if let Some(inverse) = &self.inverse {
+                for &target in &incoming {
+                    inverse.add(target, subject);
+                }
+            }
 
             trace!(
                 subject,
@@ -68,6 +187,8 @@ impl UserMap {
                 "added multiple targets to subject"
             );
         }
+
+        added
     }
 
     pub fn contains(&self, subject: Uid, target: Uid) -> bool {
@@ -84,12 +205,105 @@ impl UserMap {
         matched
     }
 
+    /// Whether `subject`'s list holds every one of `targets`, checked under
+    /// a single read lock and stopping at the first miss. Sorted `targets`
+    /// are merged against the list in one pass; others are binary searched
+    /// one by one. True for no targets.
+    pub fn contains_all(&self, subject: Uid, targets: &[Uid]) -> bool {
+        let (s, idx) = self.find(subject);
+        let shard = self.shards[s].read();
+        let list = shard.get(idx).unwrap_or_default();
+
+        if targets.is_sorted() {
+            let mut pos = 0;
+            targets.iter().all(|&t| {
+                while pos < list.len() && list[pos] < t {
+                    pos += 1;
+                }
+                list.get(pos) == Some(&t)
+            })
+        } else {
+            targets.iter().all(|t| list.binary_search(t).is_ok())
+        }
+    }
+
+    /// Returns the targets `a` and `b` have in common, sorted. Both lists are
+    /// read under their shard locks at the same time.
+    pub fn intersect(&self, a: Uid, b: Uid) -> Vec<Uid> {
+        let (sa, ia) = self.find(a);
+        let (sb, ib) = self.find(b);
+
+        let pair = self.lock_two(sa, sb);
+        let (shard_a, shard_b) = pair.get();
+
+        let (Some(ta), Some(tb)) = (shard_a.get(ia), shard_b.get(ib)) else {
+            return Vec::new();
+        };
+
+        ta.iter()
+            .merge_join_by(tb, |x, y| x.cmp(y))
+            .filter_map(|e| match e {
+                EitherOrBoth::Both(&t, _) => Some(t),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Jaccard similarity of the target sets of `a` and `b`: shared targets
+    /// over targets of either, counted in one merge of both lists under
+    /// their shard locks. Two empty sets have a similarity of 0.0.
+    pub fn jaccard(&self, a: Uid, b: Uid) -> f64 {
+        let (sa, ia) = self.find(a);
+        let (sb, ib) = self.find(b);
+
+        let pair = self.lock_two(sa, sb);
+        let (shard_a, shard_b) = pair.get();
+
+        let ta = shard_a.get(ia).unwrap_or_default();
+        let tb = shard_b.get(ib).unwrap_or_default();
+
+        let (both, either) = ta
+            .iter()
+            .merge_join_by(tb, |x, y| x.cmp(y))
+            .fold((0usize, 0usize), |(both, either), e| {
+                (both + usize::from(e.is_both()), either + 1)
+            });
+
+        if either == 0 {
+            0.0
+        } else {
+            both as f64 / either as f64
+        }
+    }
+
+    /// Read-locks shards `a` and `b`, always in ascending index order so that
+    /// two-shard operations cannot deadlock against each other. A single
+    /// guard is taken if both are the same shard, since re-entrant read locks
+    /// can deadlock behind a queued writer.
+    fn lock_two(&self, a: usize, b: usize) -> ShardPair<'_> {
+        let (lo, hi) = (a.min(b), a.max(b));
+
+        let first = self.shards[lo].read();
+        let second = (lo != hi).then(|| self.shards[hi].read());
+
+        ShardPair {
+            first,
+            second,
+            swapped: a > b,
+        }
+    }
+
     #[inline]
     fn find(&self, user: Uid) -> (usize, usize) {
-        (
-            (user & self.shard_mask) as usize,
-            (user >> self.shard_bits) as usize,
-        )
+        route(user, self.shard_bits, self.shard_mask, self.routing)
+    }
+
+    /// Inverse of [`find`](Self::find): the subject stored at `idx` in shard `s`.
+    fn subject_at(&self, s: usize, idx: usize) -> Uid {
+        let idx = idx as Uid;
+        let high = idx.checked_shl(self.shard_bits).unwrap_or(0);
+        let low = (s as Uid ^ self.routing.salt(idx)) & self.shard_mask;
+        high | low
     }
 
     #[cfg_attr(coverage_nightly, coverage(off))]
@@ -101,12 +315,24 @@ impl UserMap {
         self.len.load(Ordering::Relaxed)
     }
 
-    pub fn remove(&self, subject: Uid, target: Uid) {
+    /// Removes `target` from `subject`'s list. Returns whether the edge existed.
+    pub fn remove(&self, subject: Uid, target: Uid) -> bool {
         let (s, idx) = self.find(subject);
         let mut shard = self.shards[s].write();
 
-        if shard.delete(idx, target) {
+        let removed = shard.delete(idx, target);
+
+        if removed {
             self.len.fetch_sub(1, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+
+            if shard.get(idx).is_some_and(<[Uid]>::is_empty) {
+                self.subjects.fetch_sub(1, Ordering::Relaxed);
+            }
+
+            if let Some(inverse) = &self.inverse {
+                inverse.remove(target, subject);
+            }
 
             trace!(
                 subject,
@@ -115,45 +341,259 @@ impl UserMap {
                 "removed target from subject"
             );
         }
+
+        removed
+    }
+
+    /// Monotonic counter bumped on every effective change to the map.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn has_inverse(&self) -> bool {
+        self.inverse.is_some()
+    }
+
+    /// Returns the number of distinct targets with at least one subject, if
+    /// the inverse index is enabled.
+    pub fn distinct_targets(&self) -> Option<usize> {
+        self.inverse.as_ref().map(|inverse| inverse.subjects())
+    }
+
+    pub fn sharding(&self) -> Sharding {
+        self.sharding
+    }
+
+    /// Returns the number of subjects that have `target` in their list.
+    pub fn source_count(&self, target: Uid) -> usize {
+        match &self.inverse {
+            Some(inverse) => {
+                let (s, idx) = inverse.find(target);
+                inverse.shards[s].read().get(idx).map_or(0, <[Uid]>::len)
+            }
+            None => self.sources(target).len(),
+        }
+    }
+
+    /// Returns all subjects that have `target` in their list, sorted.
+    ///
+    /// Served from the inverse index if enabled, otherwise by
+    /// [`find_all_with_target`](Self::find_all_with_target).
+    pub fn sources(&self, target: Uid) -> Vec<Uid> {
+        match &self.inverse {
+            Some(inverse) => inverse.targets(target),
+            None => self.find_all_with_target(target),
+        }
+    }
+
+    /// Returns all subjects that have `target` in their list, sorted, by
+    /// scanning every shard regardless of the inverse index. O(total edges);
+    /// shards are scanned in parallel with the `rayon` feature.
+    pub fn find_all_with_target(&self, target: Uid) -> Vec<Uid> {
+        let mut sources = par::flat_map(&self.shards, |s, shard| self.scan_shard(s, shard, target));
+
+        sources.sort_unstable();
+
+        trace!(target, sources = sources.len(), "scanned sources of target");
+
+        sources
+    }
+
+    /// Subjects in shard `s` whose list contains `target`.
+    fn scan_shard(&self, s: usize, shard: &RwLock<Shard>, target: Uid) -> Vec<Uid> {
+        shard
+            .read()
+            .iter()
+            .filter(|(_, targets)| targets.binary_search(&target).is_ok())
+            .map(|(idx, _)| self.subject_at(s, idx))
+            .collect()
+    }
+
+    /// Suggests a sharding that spreads the current subjects evenly, or the
+    /// current sharding if it is already reasonably balanced.
+    ///
+    /// Each subject weighs one plus its number of targets. Balance is measured
+    /// as the effective shard count `total² / Σ load²`, the number of equally
+    /// loaded shards with the same contention. The current sharding is kept if
+    /// that reaches half its shard count (capped by the number of subjects);
+    /// otherwise the smallest sharding reaching it under the current routing is
+    /// returned, falling back to the best balanced one.
+    pub fn recommend_sharding(&self) -> Sharding {
+        let mut load: Vec<(Uid, usize)> = Vec::new();
+
+        for (s, shard) in self.shards.iter().enumerate() {
+            for (idx, targets) in shard.read().iter() {
+                if !targets.is_empty() {
+                    load.push((self.subject_at(s, idx), 1 + targets.len()));
+                }
+            }
+        }
+
+        let effective = |sharding: Sharding| -> f64 {
+            let mut per_shard = vec![0usize; sharding.count()];
+
+            for &(uid, weight) in &load {
+                let (s, _) = route(uid, sharding.bits(), sharding.mask() as Uid, self.routing);
+                per_shard[s] += weight;
+            }
+
+            let total = per_shard.iter().sum::<usize>() as f64;
+            let squares: f64 = per_shard.iter().map(|&l| (l as f64).powi(2)).sum();
+
+            if squares == 0.0 {
+                0.0
+            } else {
+                total * total / squares
+            }
+        };
+
+        let wanted = self.sharding.count().min(load.len()) as f64 / 2.0;
+        let current = effective(self.sharding);
+
+        if load.is_empty() || current >= wanted {
+            return self.sharding;
+        }
+
+        let mut best = (self.sharding, current);
+
+        for candidate in Sharding::ALL {
+            if candidate.validate().is_err() {
+                continue;
+            }
+
+            let balance = effective(candidate);
+
+            if balance >= wanted {
+                best = (candidate, balance);
+                break;
+            }
+
+            if balance > best.1 {
+                best = (candidate, balance);
+            }
+        }
+
+        debug!(
+            current = ?self.sharding,
+            recommended = ?best.0,
+            effective = best.1,
+            wanted,
+            "recommended sharding"
+        );
+
+        best.0
+    }
+
+    pub fn routing(&self) -> Routing {
+        self.routing
+    }
+
+    /// Returns the number of edges held by each shard, in shard order. Unlike
+    /// subject counts, this shows skew from a few subjects with many targets.
+    pub fn shard_loads(&self) -> Vec<usize> {
+        self.shards.iter().map(|s| s.read().total_edges()).collect()
+    }
+
+    /// Returns the number of subjects with a non-empty target list.
+    pub fn subjects(&self) -> usize {
+        self.subjects.load(Ordering::Relaxed)
+    }
+
+    /// Returns the sorted target list of `subject`.
+    pub fn targets(&self, subject: Uid) -> Vec<Uid> {
+        let (s, idx) = self.find(subject);
+        let shard = self.shards[s].read();
+
+        shard.get(idx).map(<[Uid]>::to_vec).unwrap_or_default()
     }
 }
 
-#[cfg_attr(coverage_nightly, coverage(off))]
-impl fmt::Display for UserMap {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut usage = Usage::new("locks", self.shards.len() * size_of::<RwLock<Shard>>());
+/// Maps `user` to `(shard, backbone index)` for the given shard bits and mask.
+#[inline]
+fn route(user: Uid, bits: u32, mask: Uid, routing: Routing) -> (usize, usize) {
+    let idx = user.checked_shr(bits).unwrap_or(0);
+
+    (((user ^ routing.salt(idx)) & mask) as usize, idx as usize)
+}
+
+impl UserMap {
+    /// Usage of this map's own locks and shards, excluding the inverse index.
+    fn own_usage(&self) -> Usage {
+        let mut usage = Usage::new("locks", 0);
+        usage.add_lock_overhead::<RwLock<Shard>>(self.shards.len());
 
         for s in self.shards.iter() {
             usage.observe(&*s.read());
         }
 
-        write!(f, "{usage}")
+        usage
+    }
+}
+
+impl ReportUsage for UserMap {
+    fn usage(&self) -> Usage {
+        let mut usage = self.own_usage();
+
+        if let Some(inverse) = &self.inverse {
+            usage.add_lock_overhead::<RwLock<Shard>>(inverse.shards.len());
+            usage.add_heap_usage(size_of::<UserMap>()); // the box itself
+
+            for s in inverse.shards.iter() {
+                usage.observe(&*s.read());
+            }
+        }
+
+        usage
+    }
+}
+
+impl UserMap {
+    /// Emits the map's usage, including any inverse index, as gauges under
+    /// `prefix`. See [`record_metrics`](crate::record_metrics).
+    #[cfg(feature = "metrics")]
+    pub fn record_metrics(&self, prefix: &str) {
+        crate::usage::record_metrics(prefix, &self.usage());
+    }
+}
+
+/// Splits into forward and inverse index when the latter is enabled.
+impl ReportUsageTree for UserMap {
+    fn usage_tree(&self) -> UsageTree {
+        let tree = UsageTree::new("map", self.usage());
+
+        match &self.inverse {
+            Some(inverse) => tree
+                .with_child(UsageTree::new("forward", self.own_usage()))
+                .with_child(UsageTree::new("inverse", inverse.own_usage())),
+            None => tree,
+        }
+    }
+}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl fmt::Display for UserMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.usage())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
     use proptest::prelude::*;
     use std::collections::{BTreeSet, HashMap};
 
-    const ALL_SHARDINGS: [Sharding; 12] = [
-        Sharding::S2,
-        Sharding::S4,
-        Sharding::S8,
-        Sharding::S16,
-        Sharding::S32,
-        Sharding::S64,
-        Sharding::S128,
-        Sharding::S256,
-        Sharding::S512,
-        Sharding::S1024,
-        Sharding::S2048,
-        Sharding::S4096,
-    ];
-
     fn sharding_strategy() -> impl Strategy<Value = Sharding> {
-        (0..ALL_SHARDINGS.len()).prop_map(|i| ALL_SHARDINGS[i])
+        let valid: Vec<_> = Sharding::ALL
+            .into_iter()
+            .filter(|s| s.validate().is_ok())
+            .collect();
+        proptest::sample::select(valid)
+    }
+
+    fn routing_strategy() -> impl Strategy<Value = Routing> {
+        prop_oneof![Just(Routing::LowBits), Just(Routing::Hash)]
     }
 
     #[derive(Clone, Debug)]
@@ -164,8 +604,8 @@ mod tests {
 
     fn op_strategy() -> impl Strategy<Value = Op> {
         prop_oneof![
-            (0..10_000u32, 0..10_000u32).prop_map(|(p, t)| Op::Add(p, t)),
-            (0..10_000u32, 0..10_000u32).prop_map(|(p, t)| Op::Remove(p, t)),
+            (0..10_000 as Uid, 0..10_000 as Uid).prop_map(|(p, t)| Op::Add(p, t)),
+            (0..10_000 as Uid, 0..10_000 as Uid).prop_map(|(p, t)| Op::Remove(p, t)),
         ]
     }
 
@@ -190,7 +630,7 @@ mod tests {
     ) {
         for &p in subjects {
             for &t in targets {
-                let expected = reference.get(&p).map_or(false, |s| s.contains(&t));
+                let expected = reference.get(&p).is_some_and(|s| s.contains(&t));
                 assert_eq!(
                     map.contains(p, t),
                     expected,
@@ -203,18 +643,344 @@ mod tests {
         }
         let expected_len: usize = reference.values().map(|s| s.len()).sum();
         assert_eq!(map.len(), expected_len, "len mismatch");
+
+        let expected_subjects = reference.values().filter(|s| !s.is_empty()).count();
+        assert_eq!(map.subjects(), expected_subjects, "subjects mismatch");
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn usage_matches_allocator() {
+        crate::usage::tests::assert_usage_within(0.0, || {
+            let map = UserMap::new_with_inverse(Sharding::S16);
+            for i in 0..500 {
+                map.add_bulk(i % 97, [i as Uid, (i * 7) as Uid]);
+            }
+            map
+        });
+    }
+
+    #[test]
+    fn display_uses_usage_format() {
+        let map = UserMap::new(Sharding::S2);
+
+        let mut expected = Usage::new("locks", 2 * size_of::<RwLock<Shard>>());
+        for s in map.shards.iter() {
+            expected.observe(&*s.read());
+        }
+        assert_eq!(map.to_string(), expected.to_string());
+        assert!(
+            map.to_string().ends_with("across 2 (0 active, 0 B..0 B)"),
+            "{map}"
+        );
+
+        map.add(0, 1);
+        assert!(
+            map.to_string().contains("across 2 (1 active, 0 B.."),
+            "{map}"
+        );
+    }
+
+    #[test]
+    fn intersect_across_and_within_shards() {
+        let map = UserMap::new(Sharding::S2);
+        map.add_bulk(0, [1, 2, 3, 5, 8]);
+        map.add_bulk(1, [2, 3, 4, 8]);
+        map.add_bulk(2, [3, 8, 9]);
+
+        assert_eq!(map.intersect(0, 1), vec![2, 3, 8], "different shards");
+        assert_eq!(map.intersect(1, 0), vec![2, 3, 8]);
+        assert_eq!(map.intersect(0, 2), vec![3, 8], "same shard");
+        assert_eq!(map.intersect(0, 0), vec![1, 2, 3, 5, 8]);
+        assert_eq!(map.intersect(0, 7), Vec::<Uid>::new());
+    }
+
+    #[test]
+    fn intersect_opposite_orders_do_not_deadlock() {
+        let map = std::sync::Arc::new(UserMap::new(Sharding::S2));
+        let (a, b) = (0, 1);
+        let (tx, rx) = std::sync::mpsc::channel();
+
+        // Writers interleave with readers so a queued writer would block an
+        // out-of-order second read lock. Threads are detached so a deadlock
+        // fails the test instead of hanging it.
+        for (x, y) in [(a, b), (b, a), (a, b), (b, a)] {
+            let (map, tx) = (map.clone(), tx.clone());
+            std::thread::spawn(move || {
+                for i in 0..2_000 as Uid {
+                    map.add(x, i);
+                    map.intersect(x, y);
+                }
+                tx.send(()).unwrap();
+            });
+        }
+
+        for _ in 0..4 {
+            rx.recv_timeout(std::time::Duration::from_secs(30))
+                .expect("intersect deadlocked");
+        }
+
+        assert_eq!(map.intersect(a, b).len(), 2_000);
+    }
+
+    #[test]
+    fn find_at_uid_width_boundary() {
+        // Principals index the backbone by their high bits; past the dense
+        // range (any wide uid) the slot is kept sparse.
+        let top = Uid::MAX;
+
+        for sharding in [Sharding::S4096, Sharding::S65536] {
+            let Ok(map) = UserMap::try_new(sharding) else {
+                continue;
+            };
+
+            assert_eq!(
+                map.find(Uid::MAX),
+                (
+                    sharding.mask() as usize & Uid::MAX as usize,
+                    Uid::MAX.checked_shr(sharding.bits()).unwrap_or(0) as usize,
+                ),
+            );
+
+            map.add(top, 0);
+            map.add(top - 1, Uid::MAX);
+            map.add(0, Uid::MAX);
+
+            assert!(map.contains(top, 0));
+            assert!(map.contains(top - 1, Uid::MAX));
+            assert!(map.contains(0, Uid::MAX));
+            assert!(!map.contains(top, Uid::MAX));
+            assert_eq!(map.sources(Uid::MAX), vec![0, top - 1]);
+        }
+    }
+
+    #[test]
+    fn try_new_rejects_sharding_at_uid_width() {
+        for sharding in Sharding::ALL {
+            let result = UserMap::try_new(sharding);
+
+            if sharding.bits() < Uid::BITS {
+                assert_eq!(result.map(|m| m.shards.len()).ok(), Some(sharding.count()));
+            } else {
+                let err = result.err().map(|e| e.to_string()).unwrap_or_default();
+                assert!(err.contains("uids are only"), "{sharding:?}: {err}");
+            }
+        }
+    }
+
+    #[test]
+    fn recommend_sharding_for_clustered_ids() {
+        let clustered = UserMap::new(Sharding::S64);
+        let spread = UserMap::new_with_routing(Sharding::S64, Routing::Hash);
+
+        // multiples of 64 all land in shard 0 under low-bit routing
+        for p in 0..1024 as Uid {
+            clustered.add(p * 64, 1);
+            spread.add(p * 64, 1);
+        }
+
+        let recommended = clustered.recommend_sharding();
+        assert_ne!(recommended, Sharding::S64);
+        assert_eq!(recommended, Sharding::S2048, "smallest with 32 busy shards");
+
+        assert_eq!(spread.recommend_sharding(), Sharding::S64);
+        assert_eq!(
+            UserMap::new(Sharding::S64).recommend_sharding(),
+            Sharding::S64
+        );
+    }
+
+    #[test]
+    fn contains_all_subsets() {
+        let map = UserMap::new(Sharding::S4);
+        map.add_bulk(1, [2, 4, 6, 8, 10]);
+
+        assert!(map.contains_all(1, &[2, 6, 10]), "sorted, all");
+        assert!(map.contains_all(1, &[10, 2, 8]), "unsorted, all");
+        assert!(map.contains_all(1, &[4, 4, 8]), "duplicates");
+        assert!(!map.contains_all(1, &[2, 5, 10]), "sorted, all but one");
+        assert!(!map.contains_all(1, &[10, 6, 12]), "unsorted, all but one");
+        assert!(!map.contains_all(1, &[1, 3, 5]), "none");
+        assert!(!map.contains_all(1, &[11]), "past the end");
+        assert!(!map.contains_all(99, &[2]), "unknown subject");
+        assert!(map.contains_all(1, &[]));
+        assert!(map.contains_all(99, &[]));
+    }
+
+    #[test]
+    fn jaccard_of_known_overlap() {
+        let map = UserMap::new(Sharding::S16);
+        map.add_bulk(1, [1, 2, 3, 4, 5, 6]);
+        map.add_bulk(2, [4, 5, 6, 7]);
+        // Same shard as 1 under low-bit routing.
+        map.add_bulk(17, [2, 3]);
+
+        assert_eq!(map.jaccard(1, 2), 3.0 / 7.0);
+        assert_eq!(map.jaccard(2, 1), map.jaccard(1, 2));
+        assert_eq!(map.jaccard(1, 17), 2.0 / 6.0);
+        assert_eq!(map.jaccard(2, 17), 0.0);
+        assert_eq!(map.jaccard(1, 1), 1.0);
+        assert_eq!(map.jaccard(1, 99), 0.0, "one empty");
+        assert_eq!(map.jaccard(98, 99), 0.0, "both empty");
+    }
+
+    #[test]
+    fn shard_loads_show_edge_skew() {
+        let map = UserMap::new(Sharding::S16);
+
+        map.add_bulk(3, 0..1000 as Uid);
+        for p in 16..64 as Uid {
+            map.add(p, 1);
+        }
+
+        let loads = map.shard_loads();
+        assert_eq!(loads.len(), 16);
+        assert_eq!(loads.iter().sum::<usize>(), map.len());
+
+        let (s, _) = map.find(3);
+        assert_eq!(loads[s], 1000 + 3, "uids 19, 35, 51 share the shard");
+        assert!(
+            loads
+                .iter()
+                .enumerate()
+                .all(|(i, &l)| i == s || l * 100 < loads[s])
+        );
+    }
+
+    #[test]
+    fn hash_routing_spreads_strided_uids() {
+        let occupied = |map: &UserMap| -> Vec<usize> {
+            map.shards
+                .iter()
+                .map(|s| s.read().iter().filter(|(_, t)| !t.is_empty()).count())
+                .collect()
+        };
+
+        for routing in [Routing::LowBits, Routing::Hash] {
+            let map = UserMap::new_with_routing(Sharding::S64, routing);
+            // 1024 strided uids fit every uid width
+            for p in 0..1024 as Uid {
+                map.add(p * 64, 1);
+            }
+
+            let per_shard = occupied(&map);
+            let max = per_shard.iter().copied().max().unwrap_or(0);
+
+            match routing {
+                Routing::LowBits => assert_eq!(max, 1024, "all strided uids hit shard 0"),
+                Routing::Hash => assert!(max <= 3 * 1024 / 64, "max {max} per shard"),
+            }
+
+            assert!(map.contains(64 * 100, 1));
+            assert_eq!(map.sources(1).len(), 1024);
+        }
+    }
+
+    #[test]
+    fn find_all_with_target_matches_brute_force() {
+        let map = UserMap::new_with_inverse(Sharding::S16);
+
+        for subject in 0..200 as Uid {
+            map.add_bulk(subject, (0..10 as Uid).filter(|t| subject % (t + 1) == 0));
+        }
+
+        for target in 0..12 as Uid {
+            let expected: Vec<Uid> = (0..200 as Uid)
+                .filter(|&s| map.contains(s, target))
+                .collect();
+
+            assert_eq!(
+                map.find_all_with_target(target),
+                expected,
+                "target {target}"
+            );
+            assert_eq!(map.sources(target), expected, "inverse agrees");
+        }
+    }
+
+    #[test]
+    fn bulk_merges_shrink_only_when_asked() {
+        let load = |map: &UserMap| {
+            map.add_bulk(1, [1, 5, 10]);
+            map.add_bulk(1, [3, 5, 7]);
+            assert_eq!(map.targets(1), [1, 3, 5, 7, 10]);
+        };
+
+        let map = UserMap::new(Sharding::S16);
+        load(&map);
+        assert!(map.usage().waste > 0, "duplicate 5 leaves slack");
+
+        map.shrink_to_fit();
+        let shrunk = map.usage().waste;
+
+        let compact = UserMap::new(Sharding::S16).with_compact_on_merge(true);
+        load(&compact);
+        assert_eq!(compact.usage().waste, shrunk);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn find_all_with_target_parallel_over_many_shards() {
+        let map = UserMap::new_with_routing(Sharding::S2048, Routing::Hash);
+
+        for subject in 0..10_000 as Uid {
+            map.add(subject, subject % 7);
+        }
+
+        let found = map.find_all_with_target(3);
+        assert_eq!(found.len(), (0..10_000).filter(|s| s % 7 == 3).count());
+        assert!(found.is_sorted() && found.iter().all(|s| s % 7 == 3));
+        assert!(map.find_all_with_target(7).is_empty());
     }
 
     proptest! {
+        #[test]
+        fn fuzz_sources(
+            sharding in sharding_strategy(),
+            routing in routing_strategy(),
+            ops in prop::collection::vec(op_strategy(), 0..80),
+            bulk in prop::collection::vec(0..10_000 as Uid, 0..20),
+        ) {
+            let indexed = UserMap::new_with_inverse(sharding);
+            let scanned = UserMap::new_with_routing(sharding, routing);
+            let mut reference: HashMap<Uid, BTreeSet<Uid>> = HashMap::new();
+
+            for op in &ops {
+                apply_and_track(&indexed, &mut reference, op);
+                apply_and_track(&scanned, &mut HashMap::new(), op);
+            }
+
+            indexed.add_bulk(7, bulk.iter().copied());
+            scanned.add_bulk(7, bulk.iter().copied());
+            reference.entry(7).or_default().extend(&bulk);
+
+            let targets: BTreeSet<Uid> = reference.values().flatten().copied().collect();
+
+            for &t in &targets {
+                let expected: Vec<Uid> = reference
+                    .iter()
+                    .filter(|(_, ts)| ts.contains(&t))
+                    .map(|(&p, _)| p)
+                    .sorted()
+                    .collect();
+
+                prop_assert_eq!(&indexed.sources(t), &expected, "indexed sources({})", t);
+                prop_assert_eq!(&scanned.sources(t), &expected, "scanned sources({})", t);
+                prop_assert_eq!(&indexed.find_all_with_target(t), &expected);
+                prop_assert_eq!(indexed.source_count(t), expected.len());
+            }
+        }
+
         #[test]
         fn fuzz_bulk_equivalence(
             sharding in sharding_strategy(),
-            subject in 0..10_000u32,
-            existing in prop::collection::vec(0..10_000u32, 0..50),
-            incoming in prop::collection::vec(0..10_000u32, 0..50),
+            routing in routing_strategy(),
+            subject in 0..10_000 as Uid,
+            existing in prop::collection::vec(0..10_000 as Uid, 0..50),
+            incoming in prop::collection::vec(0..10_000 as Uid, 0..50),
         ) {
-            let bulk_map = UserMap::new(sharding);
-            let individual_map = UserMap::new(sharding);
+            let bulk_map = UserMap::new_with_routing(sharding, routing);
+            let individual_map = UserMap::new_with_routing(sharding, routing);
 
             for &t in &existing {
                 bulk_map.add(subject, t);
@@ -240,9 +1006,10 @@ mod tests {
         #[test]
         fn fuzz_operations(
             sharding in sharding_strategy(),
+            routing in routing_strategy(),
             ops in prop::collection::vec(op_strategy(), 0..80),
         ) {
-            let map = UserMap::new(sharding);
+            let map = UserMap::new_with_routing(sharding, routing);
             let mut reference: HashMap<Uid, BTreeSet<Uid>> = HashMap::new();
 
             let mut subjects = BTreeSet::new();
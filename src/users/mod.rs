@@ -1,10 +1,32 @@
+mod cache;
 mod map;
 mod relationships;
+mod routing;
 mod shard;
 mod sharding;
 
+/// User id. `u32` by default; the `uid-u16` and `uid-u64` features select a
+/// different width (if both are enabled the wider one wins).
+#[cfg(not(any(feature = "uid-u16", feature = "uid-u64")))]
 pub type Uid = u32;
+#[cfg(all(feature = "uid-u16", not(feature = "uid-u64")))]
+pub type Uid = u16;
+#[cfg(feature = "uid-u64")]
+pub type Uid = u64;
 
+/// Arrow type of the uid column in arena exports.
+#[cfg(not(any(feature = "uid-u16", feature = "uid-u64")))]
+pub(crate) type UidType = arrow::datatypes::UInt32Type;
+#[cfg(all(feature = "uid-u16", not(feature = "uid-u64")))]
+pub(crate) type UidType = arrow::datatypes::UInt16Type;
+#[cfg(feature = "uid-u64")]
+pub(crate) type UidType = arrow::datatypes::UInt64Type;
+
+pub use cache::Visibility;
 pub use map::UserMap;
-pub use relationships::Relationships;
+pub use relationships::{
+    ApplyReport, EdgeOp, Kind, KindReport, RelationshipCounts, RelationshipEvent, Relationships,
+    ViewerSnapshot,
+};
+pub use routing::Routing;
 pub use sharding::Sharding;
@@ -1,15 +1,252 @@
+use std::fmt;
+
+use itertools::Itertools;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::usage::{BudgetViolation, ReportUsage, ReportUsageTree, Usage, UsageBudget, UsageTree};
+use crate::users::cache::{DecisionCache, Visibility};
 use crate::{Sharding, Uid, UserMap};
 
+/// Outcome of [`Relationships::apply`], per relationship kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct ApplyReport {
+    pub blocks: KindReport,
+    pub follows: KindReport,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum EdgeOp {
+    Add,
+    Remove,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Kind {
+    Block,
+    Follow,
+}
+
+/// Counts of effective changes and no-ops (adds of present edges, removes of
+/// absent edges) for a single relationship kind.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct KindReport {
+    pub added: usize,
+    pub noops: usize,
+    pub removed: usize,
+}
+
+/// Edge totals per relationship kind, read from counters without walking shards.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct RelationshipCounts {
+    pub blocks: usize,
+    /// Distinct uids with at least one follower, if the follower index is
+    /// enabled.
+    pub followed: Option<usize>,
+    pub followers: usize,
+    pub follows: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+pub struct RelationshipEvent {
+    pub kind: Kind,
+    pub op: EdgeOp,
+    pub principal: Uid,
+    pub target: Uid,
+}
+
 pub struct Relationships {
     pub blocks: UserMap,
+    cache: Option<DecisionCache>,
     pub follows: UserMap,
 }
 
+/// One viewer's follow and block lists, copied so that checking authors
+/// takes no locks. Later edges are not seen.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ViewerSnapshot {
+    blocks: Vec<Uid>,
+    follows: Vec<Uid>,
+}
+
 impl Relationships {
     pub fn new(sharding: Sharding) -> Self {
+        Self::new_with(sharding, sharding)
+    }
+
+    /// Creates relationships with independent sharding per kind.
+    ///
+    /// Follows are typically an order of magnitude denser than blocks (Bluesky
+    /// averages ~58 follows vs ~5 blocks per user), so a single sharding either
+    /// over-shards blocks or leaves follows contended:
+    ///
+    /// ```
+    /// use crimeline::{Relationships, Sharding};
+    ///
+    /// let rels = Relationships::new_with(Sharding::S4096, Sharding::S256);
+    ///
+    /// assert_eq!(rels.follows.sharding(), Sharding::S4096);
+    /// assert_eq!(rels.blocks.sharding(), Sharding::S256);
+    /// ```
+    pub fn new_with(follow_sharding: Sharding, block_sharding: Sharding) -> Self {
+        Relationships {
+            blocks: UserMap::new(block_sharding),
+            cache: None,
+            follows: UserMap::new(follow_sharding),
+        }
+    }
+
+    /// Creates relationships that maintain a follower index next to follows,
+    /// enabling [`followers_of`] without a full scan at the cost of doubling
+    /// follow edge memory.
+    ///
+    /// [`followers_of`]: Relationships::followers_of
+    pub fn new_with_followers(sharding: Sharding) -> Self {
         Relationships {
             blocks: UserMap::new(sharding),
-            follows: UserMap::new(sharding),
+            cache: None,
+            follows: UserMap::new_with_inverse(sharding),
+        }
+    }
+
+    /// Assembles relationships from maps built elsewhere, e.g. loaded in
+    /// parallel, keeping their sharding and any follower index.
+    pub fn from_maps(blocks: UserMap, follows: UserMap) -> Self {
+        Relationships {
+            blocks,
+            cache: None,
+            follows,
+        }
+    }
+
+    /// Applies a stream of relationship events.
+    ///
+    /// Events are bucketed by kind and principal. Within a bucket they are
+    /// applied in arrival order, with consecutive adds going through the bulk
+    /// path. Buckets touch disjoint adjacency lists, so the result equals
+    /// applying every event individually in order.
+    pub fn apply(&self, events: impl IntoIterator<Item = RelationshipEvent>) -> ApplyReport {
+        let mut events: Vec<RelationshipEvent> = events.into_iter().collect();
+
+        // stable: keeps arrival order within each bucket
+        events.sort_by_key(|e| (e.kind, e.principal));
+
+        let mut report = ApplyReport::default();
+
+        for ((kind, principal, op), run) in &events.iter().chunk_by(|e| (e.kind, e.principal, e.op))
+        {
+            let (map, kr) = match kind {
+                Kind::Block => (&self.blocks, &mut report.blocks),
+                Kind::Follow => (&self.follows, &mut report.follows),
+            };
+
+            match op {
+                EdgeOp::Add => {
+                    let targets: Vec<Uid> = run.map(|e| e.target).collect();
+                    let added = map.add_bulk(principal, targets.iter().copied());
+
+                    kr.added += added;
+                    kr.noops += targets.len() - added;
+                }
+                EdgeOp::Remove => {
+                    for e in run {
+                        if map.remove(principal, e.target) {
+                            kr.removed += 1;
+                        } else {
+                            kr.noops += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        debug!(
+            events = events.len(),
+            ?report,
+            "applied relationship events"
+        );
+
+        report
+    }
+
+    pub fn counts(&self) -> RelationshipCounts {
+        RelationshipCounts {
+            blocks: self.blocks.len(),
+            followed: self.follows.distinct_targets(),
+            followers: self.follows.subjects(),
+            follows: self.follows.len(),
+        }
+    }
+
+    pub fn follower_count(&self, uid: Uid) -> usize {
+        self.follows.source_count(uid)
+    }
+
+    pub fn followers_of(&self, uid: Uid) -> Vec<Uid> {
+        self.follows.sources(uid)
+    }
+
+    /// Enables a bounded cache of [`visibility`] decisions.
+    ///
+    /// Any write to follows or blocks invalidates the whole cache, so it pays
+    /// off for read-heavy phases such as rendering a feed that checks the same
+    /// few authors many times.
+    ///
+    /// [`visibility`]: Relationships::visibility
+    pub fn with_decision_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(DecisionCache::new(capacity));
+        self
+    }
+
+    /// Decides how `viewer` sees content by `author`.
+    pub fn visibility(&self, viewer: Uid, author: Uid) -> Visibility {
+        let Some(cache) = &self.cache else {
+            return self.decide(viewer, author);
+        };
+
+        // Read the generation before deciding: a write racing with `decide`
+        // bumps it, so the stored entry is never served afterwards.
+        let generation = self.generation();
+
+        if let Some(visibility) = cache.get(viewer, author, generation) {
+            return visibility;
+        }
+
+        let visibility = self.decide(viewer, author);
+        cache.insert(viewer, author, generation, visibility);
+        visibility
+    }
+
+    fn decide(&self, viewer: Uid, author: Uid) -> Visibility {
+        if self.blocks.contains(viewer, author) || self.blocks.contains(author, viewer) {
+            Visibility::Hidden
+        } else if self.follows.contains(viewer, author) {
+            Visibility::Followed
+        } else {
+            Visibility::Unrelated
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.follows
+            .generation()
+            .wrapping_add(self.blocks.generation())
+    }
+
+    /// Copies `viewer`'s follow and block lists, see [`ViewerSnapshot`].
+    pub fn viewer_snapshot(&self, viewer: Uid) -> ViewerSnapshot {
+        ViewerSnapshot {
+            blocks: self.blocks.targets(viewer),
+            follows: self.follows.targets(viewer),
         }
     }
 
@@ -24,11 +261,106 @@ impl Relationships {
     pub fn is_followed_by(&self, subject: Uid, target: Uid) -> bool {
         self.follows.contains(target, subject)
     }
+
+    #[cfg_attr(coverage_nightly, coverage(off))]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Total number of edges across all relationship kinds.
+    pub fn len(&self) -> usize {
+        self.blocks.len() + self.follows.len()
+    }
+}
+
+impl ViewerSnapshot {
+    pub fn blocks(&self, author: Uid) -> bool {
+        self.blocks.binary_search(&author).is_ok()
+    }
+
+    pub fn follows(&self, author: Uid) -> bool {
+        self.follows.binary_search(&author).is_ok()
+    }
+
+    /// Whether the viewer follows `author` and does not block them.
+    pub fn shows(&self, author: Uid) -> bool {
+        self.follows(author) && !self.blocks(author)
+    }
+}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl fmt::Display for RelationshipCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} follows by {} users, {} blocks",
+            self.follows, self.followers, self.blocks,
+        )?;
+
+        if let Some(followed) = self.followed {
+            write!(f, ", {followed} followed users")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Relationships {
+    /// Emits each kind's usage as gauges under `prefix`, labeled
+    /// `kind="follows"` or `kind="blocks"`.
+    #[cfg(feature = "metrics")]
+    pub fn record_metrics(&self, prefix: &str) {
+        for (kind, map) in [("follows", &self.follows), ("blocks", &self.blocks)] {
+            let label = metrics::Label::new("kind", kind);
+            crate::usage::record_metrics_labeled(prefix, &map.usage(), vec![label]);
+        }
+    }
+}
+
+impl Relationships {
+    /// Checks both kinds' combined usage against `budget`, see [`Usage::check`].
+    pub fn check_budget(&self, budget: &UsageBudget) -> Vec<BudgetViolation> {
+        self.usage().check(budget)
+    }
+}
+
+impl ReportUsage for Relationships {
+    fn usage(&self) -> Usage {
+        self.usage_tree().usage
+    }
+}
+
+impl ReportUsageTree for Relationships {
+    fn usage_tree(&self) -> UsageTree {
+        let mut root = Usage::new("locks", 0);
+        if let Some(cache) = &self.cache {
+            cache.add_overhead(&mut root);
+        }
+
+        let mut tree = UsageTree::new("relationships", root);
+        tree.observe_child("follows", &self.follows)
+            .observe_child("blocks", &self.blocks);
+        tree
+    }
+}
+
+#[cfg_attr(coverage_nightly, coverage(off))]
+impl fmt::Display for Relationships {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "follows ({:?}): {}",
+            self.follows.sharding(),
+            self.follows
+        )?;
+        write!(f, "blocks ({:?}): {}", self.blocks.sharding(), self.blocks)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::usage::BudgetCategory;
     use proptest::prelude::*;
     use std::collections::BTreeSet;
 
@@ -42,20 +374,366 @@ mod tests {
 
     fn op_strategy() -> impl Strategy<Value = Op> {
         prop_oneof![
-            (0..100u32, 0..100u32).prop_map(|(p, t)| Op::Block(p, t)),
-            (0..100u32, 0..100u32).prop_map(|(p, t)| Op::Unblock(p, t)),
-            (0..100u32, 0..100u32).prop_map(|(p, t)| Op::Follow(p, t)),
-            (0..100u32, 0..100u32).prop_map(|(p, t)| Op::Unfollow(p, t)),
+            (0..100 as Uid, 0..100 as Uid).prop_map(|(p, t)| Op::Block(p, t)),
+            (0..100 as Uid, 0..100 as Uid).prop_map(|(p, t)| Op::Unblock(p, t)),
+            (0..100 as Uid, 0..100 as Uid).prop_map(|(p, t)| Op::Follow(p, t)),
+            (0..100 as Uid, 0..100 as Uid).prop_map(|(p, t)| Op::Unfollow(p, t)),
         ]
     }
 
+    fn event(op: &Op) -> RelationshipEvent {
+        let (kind, op, principal, target) = match *op {
+            Op::Block(p, t) => (Kind::Block, EdgeOp::Add, p, t),
+            Op::Unblock(p, t) => (Kind::Block, EdgeOp::Remove, p, t),
+            Op::Follow(p, t) => (Kind::Follow, EdgeOp::Add, p, t),
+            Op::Unfollow(p, t) => (Kind::Follow, EdgeOp::Remove, p, t),
+        };
+
+        RelationshipEvent {
+            kind,
+            op,
+            principal,
+            target,
+        }
+    }
+
+    #[test]
+    fn apply_reports_noops() {
+        let rel = Relationships::new(Sharding::S4);
+
+        let report = rel.apply([
+            event(&Op::Unfollow(1, 2)),
+            event(&Op::Follow(1, 2)),
+            event(&Op::Follow(1, 2)),
+            event(&Op::Follow(1, 3)),
+            event(&Op::Block(1, 9)),
+            event(&Op::Unblock(1, 9)),
+        ]);
+
+        assert_eq!(
+            report.follows,
+            KindReport {
+                added: 2,
+                noops: 2,
+                removed: 0
+            },
+            "early unfollow and duplicate follow are no-ops",
+        );
+        assert_eq!(
+            report.blocks,
+            KindReport {
+                added: 1,
+                noops: 0,
+                removed: 1
+            },
+        );
+        assert!(rel.follows.contains(1, 2));
+        assert!(!rel.blocks.contains(1, 9));
+    }
+
+    #[test]
+    fn from_maps_uses_loaded_edges() {
+        let blocks = UserMap::new(Sharding::S4);
+        blocks.add(7, 1);
+        let follows = UserMap::new_with_inverse(Sharding::S16);
+        follows.add_bulk(2, [1, 3]);
+        follows.add(1, 2);
+
+        let rel = Relationships::from_maps(blocks, follows);
+
+        assert!(rel.is_blocked_by(1, 7));
+        assert!(!rel.is_blocked_by(7, 1));
+        assert!(rel.is_followed_by(1, 2));
+        assert!(rel.is_followed_by(3, 2));
+        assert!(!rel.is_followed_by(2, 3));
+        assert_eq!(rel.followers_of(1), [2]);
+        assert_eq!(
+            (rel.blocks.sharding(), rel.follows.sharding()),
+            (Sharding::S4, Sharding::S16)
+        );
+        assert_eq!(rel.len(), 4);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn event_deserializes() -> anyhow::Result<()> {
+        let e: RelationshipEvent = serde_json::from_str(
+            r#"{"kind": "follow", "op": "remove", "principal": 1, "target": 2}"#,
+        )?;
+        assert_eq!(e, event(&Op::Unfollow(1, 2)));
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn reports_serde_roundtrip() -> serde_json::Result<()> {
+        use crate::Routing;
+
+        let report = ApplyReport {
+            blocks: KindReport {
+                added: 1,
+                noops: 0,
+                removed: 2,
+            },
+            follows: KindReport::default(),
+        };
+        let json = serde_json::to_value(report)?;
+        assert_eq!(
+            json["blocks"],
+            serde_json::json!({"added": 1, "noops": 0, "removed": 2})
+        );
+        assert_eq!(serde_json::from_value::<ApplyReport>(json)?, report);
+
+        let counts = RelationshipCounts {
+            blocks: 1,
+            followed: None,
+            followers: 0,
+            follows: 3,
+        };
+        let json = serde_json::to_string(&counts)?;
+        assert_eq!(serde_json::from_str::<RelationshipCounts>(&json)?, counts);
+
+        for v in [
+            Visibility::Followed,
+            Visibility::Hidden,
+            Visibility::Unrelated,
+        ] {
+            let json = serde_json::to_string(&v)?;
+            assert_eq!(serde_json::from_str::<Visibility>(&json)?, v);
+        }
+        assert_eq!(serde_json::to_string(&Visibility::Hidden)?, r#""hidden""#);
+        assert_eq!(serde_json::to_string(&Routing::LowBits)?, r#""low_bits""#);
+        assert_eq!(serde_json::from_str::<Routing>(r#""hash""#)?, Routing::Hash);
+        Ok(())
+    }
+
+    #[test]
+    fn usage_tree_per_kind() {
+        let rels = Relationships::new_with_followers(Sharding::S4);
+        rels.follows.add_bulk(1, [2, 3, 4]);
+        rels.blocks.add(1, 5);
+
+        let tree = rels.usage_tree();
+        let labels = |t: &UsageTree| -> Vec<String> {
+            t.children.iter().map(|c| c.label.to_string()).collect()
+        };
+
+        assert_eq!(labels(&tree), ["follows", "blocks"]);
+        assert_eq!(labels(&tree.children[0]), ["forward", "inverse"]);
+        assert!(
+            tree.children[1].children.is_empty(),
+            "no inverse for blocks"
+        );
+
+        let follows = rels.follows.usage();
+        let blocks = rels.blocks.usage();
+        assert_eq!(tree.usage.heap, follows.heap + blocks.heap);
+        assert_eq!(
+            tree.usage.overhead(),
+            follows.overhead() + blocks.overhead()
+        );
+        assert_eq!(rels.usage().heap, tree.usage.heap);
+
+        let plain = Relationships::new(Sharding::S4);
+        let cached = Relationships::new(Sharding::S4).with_decision_cache(64);
+        assert!(cached.usage().overhead() > plain.usage().overhead());
+
+        let kinds: Vec<_> = rels
+            .usage()
+            .top_contributors()
+            .iter()
+            .map(|(name, _)| name.clone())
+            .sorted()
+            .collect();
+        assert_eq!(kinds, ["blocks", "follows"]);
+
+        let budget = UsageBudget {
+            heap: Some(0),
+            ..Default::default()
+        };
+        assert_eq!(rels.check_budget(&budget)[0].category, BudgetCategory::Heap);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn record_metrics_per_kind() {
+        let rels = Relationships::new(Sharding::S4);
+        rels.follows.add_bulk(1, [2, 3, 4]);
+
+        let gauges = crate::usage::tests::capture_gauges(|| rels.record_metrics("rels"));
+        let heap = |kind: &str| {
+            gauges[&(
+                "rels_heap_bytes".to_string(),
+                vec![("kind".to_string(), kind.to_string())],
+            )]
+        };
+
+        assert_eq!(heap("follows"), rels.follows.usage().heap as f64);
+        assert_eq!(heap("blocks"), 0.0);
+        assert_eq!(gauges.len(), 12, "6 gauges per kind");
+    }
+
+    #[test]
+    fn counts_per_kind() {
+        let rel = Relationships::new_with_followers(Sharding::S4);
+        rel.follows.add_bulk(1, [2, 3, 4]);
+        rel.follows.add(5, 2);
+        rel.blocks.add(1, 9);
+
+        let counts = rel.counts();
+        assert_eq!(
+            counts,
+            RelationshipCounts {
+                blocks: 1,
+                followed: Some(3),
+                followers: 2,
+                follows: 4,
+            }
+        );
+        assert_eq!(rel.len(), 5);
+        assert_eq!(
+            counts.to_string(),
+            "4 follows by 2 users, 1 blocks, 3 followed users"
+        );
+
+        rel.follows.remove(5, 2);
+        assert_eq!(rel.counts().followers, 1, "5 no longer follows anyone");
+        assert_eq!(rel.counts().followed, Some(3), "2 is still followed by 1");
+
+        let plain = Relationships::new(Sharding::S4);
+        plain.follows.add(1, 2);
+        assert_eq!(plain.counts().followed, None, "needs the follower index");
+    }
+
+    #[test]
+    fn visibility_cache_invalidated_by_writes() {
+        let rel = Relationships::new(Sharding::S4).with_decision_cache(64);
+
+        assert_eq!(rel.visibility(1, 2), Visibility::Unrelated);
+
+        rel.follows.add(1, 2);
+        assert_eq!(rel.visibility(1, 2), Visibility::Followed);
+        assert_eq!(rel.visibility(1, 2), Visibility::Followed, "cache hit");
+
+        rel.blocks.add(2, 1);
+        assert_eq!(
+            rel.visibility(1, 2),
+            Visibility::Hidden,
+            "author blocks viewer"
+        );
+
+        rel.blocks.remove(2, 1);
+        rel.follows.remove(1, 2);
+        assert_eq!(rel.visibility(1, 2), Visibility::Unrelated);
+    }
+
+    #[test]
+    fn new_with_independent_sharding() {
+        let rels = Relationships::new_with(Sharding::S1024, Sharding::S4);
+        assert_eq!(rels.follows.sharding(), Sharding::S1024);
+        assert_eq!(rels.blocks.sharding(), Sharding::S4);
+
+        let uniform = Relationships::new(Sharding::S64);
+        assert_eq!(uniform.follows.sharding(), Sharding::S64);
+        assert_eq!(uniform.blocks.sharding(), Sharding::S64);
+    }
+
     proptest! {
+        #[test]
+        fn fuzz_apply_equivalence(
+            ops in prop::collection::vec(op_strategy(), 0..120),
+        ) {
+            let batched = Relationships::new(Sharding::S8);
+            let individual = Relationships::new(Sharding::S8);
+
+            let report = batched.apply(ops.iter().map(event));
+
+            let mut expected = ApplyReport::default();
+            for op in &ops {
+                let (changed, kr) = match *op {
+                    Op::Block(p, t) => (individual.blocks.add(p, t), &mut expected.blocks),
+                    Op::Unblock(p, t) => (individual.blocks.remove(p, t), &mut expected.blocks),
+                    Op::Follow(p, t) => (individual.follows.add(p, t), &mut expected.follows),
+                    Op::Unfollow(p, t) => (individual.follows.remove(p, t), &mut expected.follows),
+                };
+                match (changed, op) {
+                    (false, _) => kr.noops += 1,
+                    (true, Op::Block(..) | Op::Follow(..)) => kr.added += 1,
+                    (true, Op::Unblock(..) | Op::Unfollow(..)) => kr.removed += 1,
+                }
+            }
+
+            prop_assert_eq!(report, expected);
+            prop_assert_eq!(batched.blocks.len(), individual.blocks.len());
+            prop_assert_eq!(batched.follows.len(), individual.follows.len());
+
+            for p in 0..100 as Uid {
+                for t in 0..100 as Uid {
+                    prop_assert_eq!(batched.blocks.contains(p, t), individual.blocks.contains(p, t));
+                    prop_assert_eq!(batched.follows.contains(p, t), individual.follows.contains(p, t));
+                }
+            }
+        }
+
+        #[test]
+        fn fuzz_followers_concurrent(
+            ops in prop::collection::vec(op_strategy(), 0..200),
+        ) {
+            let rel = Relationships::new_with_followers(Sharding::S4);
+
+            std::thread::scope(|scope| {
+                for chunk in ops.chunks(ops.len().div_ceil(4).max(1)) {
+                    let rel = &rel;
+                    scope.spawn(move || {
+                        for op in chunk {
+                            rel.apply([event(op)]);
+                        }
+                    });
+                }
+            });
+
+            for target in 0..100 as Uid {
+                let followers = rel.followers_of(target);
+                prop_assert_eq!(followers.len(), rel.follower_count(target));
+
+                for principal in 0..100 as Uid {
+                    prop_assert_eq!(
+                        rel.follows.contains(principal, target),
+                        followers.binary_search(&principal).is_ok(),
+                        "follows({}, {}) disagrees with followers_of", principal, target,
+                    );
+                }
+            }
+        }
+
+        #[test]
+        fn fuzz_visibility_cache(
+            ops in prop::collection::vec(op_strategy(), 0..80),
+            queries in prop::collection::vec((0..100 as Uid, 0..100 as Uid), 1..30),
+        ) {
+            let cached = Relationships::new(Sharding::S8).with_decision_cache(16);
+            let uncached = Relationships::new(Sharding::S8);
+
+            for op in &ops {
+                cached.apply([event(op)]);
+                uncached.apply([event(op)]);
+
+                for &(viewer, author) in &queries {
+                    prop_assert_eq!(
+                        cached.visibility(viewer, author),
+                        uncached.visibility(viewer, author),
+                        "visibility({}, {})", viewer, author,
+                    );
+                }
+            }
+        }
+
         #[test]
         fn fuzz_relationships(
             ops in prop::collection::vec(op_strategy(), 0..80),
-            queries in prop::collection::vec((0..100u32, 0..100u32), 1..30),
+            queries in prop::collection::vec((0..100 as Uid, 0..100 as Uid), 1..30),
         ) {
-            let rel = Relationships::new(Sharding::S64);
+            let rel = Relationships::new_with(Sharding::S64, Sharding::S4);
 
             let mut ref_blocks: BTreeSet<(Uid, Uid)> = BTreeSet::new();
             let mut ref_follows: BTreeSet<(Uid, Uid)> = BTreeSet::new();
@@ -0,0 +1,38 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::users::Uid;
+
+/// How a uid is split into a shard and a backbone index.
+///
+/// The backbone index is always `uid >> shard_bits`, so both modes keep
+/// backbones equally dense; they only differ in which shard a uid lands in.
+///
+/// With the `serde` feature, serializes as `"hash"` or `"low_bits"`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Deserialize, Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Routing {
+    /// XORs the low bits with a Fibonacci hash of the backbone index before
+    /// masking. Spreads strided ids (e.g. all multiples of 64) evenly across
+    /// shards at the cost of one multiply per lookup.
+    Hash,
+    /// Shards by the uid's low bits. Ideal for dense, sequential ids but
+    /// degenerate when ids share low bits.
+    #[default]
+    LowBits,
+}
+
+impl Routing {
+    /// Returns the value XORed onto a uid's low bits for backbone index `idx`.
+    /// XOR keeps the split bijective, so the uid can be reconstructed from
+    /// `(shard, idx)`.
+    #[inline]
+    #[allow(clippy::unnecessary_cast)] // `Uid` may already be `u64`
+    pub(crate) fn salt(self, idx: Uid) -> Uid {
+        match self {
+            Routing::Hash => ((idx as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 32) as Uid,
+            Routing::LowBits => 0,
+        }
+    }
+}
@@ -1,22 +1,41 @@
 use std::cmp::Ordering;
+use std::collections::BTreeMap;
 
 use crate::usage::{ReportUsage, Usage};
 use crate::users::Uid;
 
+/// Backbone slots at or past this index are kept in a sparse map, so one
+/// huge uid (e.g. `Uid::MAX` under `uid-u64`) cannot force a dense
+/// allocation of every slot below it.
+pub(crate) const DENSE_SLOTS: usize = 1 << 24;
+
 /// Inner storage for one shard's adjacency lists.
 ///
 /// Uses `Vec<Uid>` over `Box<[Uid]>` which trades a simpler algorithm,
 /// (and associated cycles) for an 8-bytes (capacity word) of memory
 /// overhead.
-pub(crate) struct Shard(Vec<Vec<Uid>>);
+pub(crate) struct Shard {
+    /// Slots below [`DENSE_SLOTS`], indexed directly.
+    dense: Vec<Vec<Uid>>,
+    /// Slots at or past [`DENSE_SLOTS`].
+    sparse: BTreeMap<usize, Vec<Uid>>,
+}
 
 impl Shard {
     pub fn new() -> Self {
-        Shard(Vec::new())
+        Shard {
+            dense: Vec::new(),
+            sparse: BTreeMap::new(),
+        }
     }
 
     pub fn delete(&mut self, index: usize, target: Uid) -> bool {
-        let Some(list) = self.0.get_mut(index) else {
+        let list = if index < DENSE_SLOTS {
+            self.dense.get_mut(index)
+        } else {
+            self.sparse.get_mut(&index)
+        };
+        let Some(list) = list else {
             return false;
         };
 
@@ -30,21 +49,55 @@ impl Shard {
     }
 
     pub fn entry(&mut self, index: usize) -> &mut Vec<Uid> {
-        let len = self.0.len();
+        if index >= DENSE_SLOTS {
+            return self.sparse.entry(index).or_default();
+        }
+
+        let len = self.dense.len();
 
         if len <= index {
-            self.0.reserve_exact(index + 1 - len);
+            self.dense.reserve_exact(index + 1 - len);
 
             for _ in len..=index {
-                self.0.push(Vec::new());
+                self.dense.push(Vec::new());
             }
         }
 
-        &mut self.0[index]
+        &mut self.dense[index]
     }
 
     pub fn get(&self, index: usize) -> Option<&[Uid]> {
-        self.0.get(index).map(|v| v.as_slice())
+        let list = if index < DENSE_SLOTS {
+            self.dense.get(index)
+        } else {
+            self.sparse.get(&index)
+        };
+        list.map(|v| v.as_slice())
+    }
+
+    /// Iterates all backbone slots as `(index, targets)` in index order,
+    /// including empty ones.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &[Uid])> {
+        let dense = self.dense.iter().map(Vec::as_slice).enumerate();
+        let sparse = self.sparse.iter().map(|(&i, v)| (i, v.as_slice()));
+        dense.chain(sparse)
+    }
+
+    fn lists_mut(&mut self) -> impl Iterator<Item = &mut Vec<Uid>> {
+        self.dense.iter_mut().chain(self.sparse.values_mut())
+    }
+
+    /// Drops spare capacity from the backbone and every list.
+    pub fn shrink_to_fit(&mut self) {
+        self.dense.shrink_to_fit();
+        for list in self.lists_mut() {
+            list.shrink_to_fit();
+        }
+    }
+
+    /// Total targets across all lists.
+    pub fn total_edges(&self) -> usize {
+        self.iter().map(|(_, list)| list.len()).sum()
     }
 
     pub fn insert(&mut self, index: usize, target: Uid) -> bool {
@@ -65,8 +118,19 @@ impl Shard {
     /// The slow path uses an in-place forward merge: old data is shifted to the
     /// end of the buffer and then merged forward with `incoming`, reusing the
     /// existing allocation instead of allocating a second `Vec`.
-    pub fn merge(&mut self, index: usize, incoming: &[Uid]) -> usize {
+    ///
+    /// Both paths reserve room for `incoming` up front and settle capacity
+    /// the same way afterwards, so the resulting capacity (and reported
+    /// waste) does not depend on which path ran. Growth is amortized and a
+    /// list is shrunk once at least half of it is slack; with `compact` it
+    /// is grown exactly and always shrunk to fit.
+    pub fn merge(&mut self, index: usize, incoming: &[Uid], compact: bool) -> usize {
         let list = self.entry(index);
+        if compact {
+            list.reserve_exact(incoming.len());
+        } else {
+            list.reserve(incoming.len());
+        }
 
         // Fast path: empty list or all incoming > all existing — just append.
         if list.is_empty()
@@ -77,9 +141,7 @@ impl Shard {
         {
             let before = list.len();
             list.extend_from_slice(incoming);
-            if list.capacity() >= list.len() * 2 {
-                list.shrink_to_fit();
-            }
+            settle(list, compact);
             return list.len() - before;
         }
 
@@ -139,28 +201,40 @@ impl Shard {
         }
 
         list.truncate(write);
-        if list.capacity() >= list.len() * 2 {
-            list.shrink_to_fit();
-        }
+        settle(list, compact);
 
         write - old_len
     }
 }
 
+/// Shrinks a merged list if `compact` is set or half of it is slack.
+fn settle(list: &mut Vec<Uid>, compact: bool) {
+    if compact || list.capacity() >= list.len() * 2 {
+        list.shrink_to_fit();
+    }
+}
+
 impl ReportUsage for Shard {
     fn usage(&self) -> Usage {
         let mut u = Usage::default();
 
-        // Outer backbone: Vec<Vec<Uid>>.
-        u.add_heap_usage(self.0.capacity() * size_of::<Vec<Uid>>());
+        // Outer backbone: Vec<Vec<Uid>>, empty slots count as waste.
+        u.add_vec(&self.dense);
 
-        let empty = self.0.iter().filter(|v| v.is_empty()).count();
-        let excess = self.0.capacity() - self.0.len();
-        u.add_heap_waste((empty + excess) * size_of::<Vec<Uid>>());
+        let empty = self.dense.iter().filter(|v| v.is_empty()).count();
+        u.add_heap_waste(empty * size_of::<Vec<Uid>>());
+
+        // Sparse slots: one (index, list) per entry, tree nodes not counted.
+        u.add_collection(
+            self.sparse.len(),
+            self.sparse.len(),
+            size_of::<(usize, Vec<Uid>)>(),
+        );
 
         // Inner adjacency lists.
-        for v in &self.0 {
+        for v in self.dense.iter().chain(self.sparse.values()) {
             u.add_vec(v);
+            u.add_items(v.len());
         }
 
         u
@@ -183,9 +257,9 @@ mod tests {
     #[test]
     fn merge_fast_path_all_incoming_greater() {
         let mut s = Shard::new();
-        s.merge(0, &[1, 2, 3]);
+        s.merge(0, &[1, 2, 3], true);
 
-        let added = s.merge(0, &[10, 20, 30]);
+        let added = s.merge(0, &[10, 20, 30], true);
         assert_eq!(added, 3, "all incoming are new");
         assert_eq!(s.get(0).unwrap(), &[1, 2, 3, 10, 20, 30]);
     }
@@ -196,7 +270,7 @@ mod tests {
         s.entry(0); // create empty list
 
         // empty list has no last(), so is_some_and returns false => slow path
-        let added = s.merge(0, &[5, 10]);
+        let added = s.merge(0, &[5, 10], true);
         assert_eq!(added, 2);
         assert_eq!(s.get(0).unwrap(), &[5, 10]);
     }
@@ -206,7 +280,7 @@ mod tests {
         let mut s = Shard::new();
         s.insert(0, 1);
 
-        let added = s.merge(0, &[2]);
+        let added = s.merge(0, &[2], true);
         assert_eq!(added, 1);
         assert_eq!(s.get(0).unwrap(), &[1, 2]);
     }
@@ -214,13 +288,70 @@ mod tests {
     #[test]
     fn merge_slow_path_interleaved() {
         let mut s = Shard::new();
-        s.merge(0, &[1, 5, 10]);
+        s.merge(0, &[1, 5, 10], true);
 
-        let added = s.merge(0, &[3, 5, 7, 12]);
+        let added = s.merge(0, &[3, 5, 7, 12], true);
         assert_eq!(added, 3, "5 is duplicate");
         assert_eq!(s.get(0).unwrap(), &[1, 3, 5, 7, 10, 12]);
     }
 
+    #[test]
+    fn merge_waste_independent_of_path() {
+        for compact in [true, false] {
+            let mut fast = Shard::new();
+            fast.merge(0, &[1, 2, 3], compact);
+            fast.merge(0, &[4, 5, 6], compact);
+
+            let mut slow = Shard::new();
+            slow.merge(0, &[4, 5, 6], compact);
+            slow.merge(0, &[1, 2, 3], compact);
+
+            assert_eq!(fast.get(0), slow.get(0));
+
+            let (f, s) = (fast.usage(), slow.usage());
+            assert_eq!(f.heap, s.heap, "compact={compact}: heap");
+            assert_eq!(f.waste, s.waste, "compact={compact}: waste");
+        }
+    }
+
+    #[test]
+    fn far_slots_stay_sparse() {
+        let mut s = Shard::new();
+        s.insert(3, 1);
+        s.merge(usize::MAX, &[4, 5], false);
+        s.insert(DENSE_SLOTS, 2);
+
+        assert_eq!(s.get(usize::MAX), Some(&[4, 5][..]));
+        assert_eq!(s.get(DENSE_SLOTS), Some(&[2][..]));
+        assert_eq!(s.get(DENSE_SLOTS + 1), None);
+        assert!(s.delete(usize::MAX, 4));
+        assert_eq!(s.total_edges(), 3);
+
+        let indexes: Vec<usize> = s.iter().map(|(i, _)| i).collect();
+        assert_eq!(indexes, [0, 1, 2, 3, DENSE_SLOTS, usize::MAX]);
+        assert!(
+            s.usage().heap < 1024,
+            "no dense backbone up to the far slots"
+        );
+    }
+
+    #[test]
+    fn small_merges_grow_amortized() {
+        let mut s = Shard::new();
+        let mut reallocs = 0;
+        let mut capacity = 0;
+
+        for target in 0..1000 {
+            s.merge(0, &[target], false);
+            let now = s.entry(0).capacity();
+            reallocs += usize::from(now != capacity);
+            capacity = now;
+        }
+
+        assert_eq!(s.get(0).unwrap().len(), 1000);
+        assert!(reallocs < 20, "{reallocs} reallocations");
+    }
+
     #[test]
     fn stats_delete_leaves_empty_slot() {
         let mut s = Shard::new();
@@ -243,6 +374,20 @@ mod tests {
         let useful = 2 * VEC_SIZE + 2 * UID_SIZE;
         assert!(u.heap >= useful, "heap: {}", u.heap);
         assert_eq!(u.waste, u.heap - useful, "waste = excess inner capacity");
+        assert_eq!(u.items, 2, "one item per edge");
+    }
+
+    #[cfg(feature = "alloc-stats")]
+    #[test]
+    fn usage_matches_allocator() {
+        crate::usage::tests::assert_usage_within(0.0, || {
+            let mut s = Shard::new();
+            for i in 0..200 {
+                s.insert(i % 37, i as Uid);
+                s.merge(i % 11, &[i as Uid, 500 + i as Uid], i % 2 == 0);
+            }
+            s
+        });
     }
 
     #[test]
@@ -260,7 +405,7 @@ mod tests {
     #[test]
     fn stats_merge_tolerates_slack() {
         let mut s = Shard::new();
-        s.merge(0, &[1, 2, 3]);
+        s.merge(0, &[1, 2, 3], true);
         let u = s.usage();
         let useful = VEC_SIZE + 3 * UID_SIZE;
         assert!(u.heap >= useful, "heap: {}", u.heap);
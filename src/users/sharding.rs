@@ -1,3 +1,19 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, Error, bail, ensure};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+use crate::users::Uid;
+
+/// Principals per shard the heuristic aims to stay under.
+const USERS_PER_SHARD: u64 = 1 << 16;
+
+/// Shards per concurrent writer the heuristic aims for, keeping the chance of
+/// two writers contending on the same lock low.
+const SHARDS_PER_WRITER: usize = 4;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u32)]
 pub enum Sharding {
@@ -13,9 +29,81 @@ pub enum Sharding {
     S1024 = 10,
     S2048 = 11,
     S4096 = 12,
+    S8192 = 13,
+    S16384 = 14,
+    S32768 = 15,
+    S65536 = 16,
 }
 
 impl Sharding {
+    pub(crate) const ALL: [Sharding; 16] = [
+        Sharding::S2,
+        Sharding::S4,
+        Sharding::S8,
+        Sharding::S16,
+        Sharding::S32,
+        Sharding::S64,
+        Sharding::S128,
+        Sharding::S256,
+        Sharding::S512,
+        Sharding::S1024,
+        Sharding::S2048,
+        Sharding::S4096,
+        Sharding::S8192,
+        Sharding::S16384,
+        Sharding::S32768,
+        Sharding::S65536,
+    ];
+
+    /// Picks a sharding for roughly `users` principals written by
+    /// `writer_threads` concurrent writers.
+    ///
+    /// Takes the larger of two power-of-two shard counts: enough shards that
+    /// each holds at most ~64Ki principals, and four shards per writer thread
+    /// to keep lock contention low. The result is clamped to `S2..=S4096`.
+    pub fn for_expected_users(users: u64, writer_threads: usize) -> Sharding {
+        let by_users = users.div_ceil(USERS_PER_SHARD).max(1);
+        let by_writers = writer_threads.saturating_mul(SHARDS_PER_WRITER).max(1);
+
+        let wanted = usize::try_from(by_users)
+            .unwrap_or(usize::MAX)
+            .max(by_writers)
+            .min(Sharding::S4096.count())
+            .next_power_of_two();
+
+        Self::ALL
+            .into_iter()
+            .find(|s| s.count() >= wanted)
+            .unwrap_or(Sharding::S4096)
+    }
+
+    /// Returns the sharding with exactly `n` shards, if `n` is a supported
+    /// power of two and valid for the configured uid width.
+    pub fn from_count(n: usize) -> Option<Sharding> {
+        Self::ALL
+            .into_iter()
+            .find(|s| s.count() == n)
+            .filter(|s| s.validate().is_ok())
+    }
+
+    /// Checks that the shard bits leave room for a backbone index in the
+    /// configured uid width and that the shard count fits in `usize`.
+    pub fn validate(self) -> anyhow::Result<()> {
+        let bits = self.bits();
+
+        ensure!(
+            bits < Uid::BITS,
+            "sharding {self} needs {bits} shard bits, but uids are only {} bits wide",
+            Uid::BITS
+        );
+        ensure!(
+            1usize.checked_shl(bits).is_some(),
+            "sharding {self} has more shards than fit in usize"
+        );
+
+        Ok(())
+    }
+
     #[inline]
     pub fn bits(self) -> u32 {
         self as u32
@@ -27,7 +115,120 @@ impl Sharding {
 
     #[inline]
     pub fn mask(self) -> u32 {
-        (1u32 << self.bits()) - 1
+        // Shifting right cannot overflow for bits in 1..=32, unlike
+        // `(1 << bits) - 1`.
+        u32::MAX >> (u32::BITS - self.bits())
+    }
+}
+
+impl TryFrom<usize> for Sharding {
+    type Error = Error;
+
+    fn try_from(n: usize) -> Result<Self, Self::Error> {
+        ensure!(n.is_power_of_two(), "shard count {n} is not a power of two");
+
+        let sharding = Self::ALL
+            .into_iter()
+            .find(|s| s.count() == n)
+            .with_context(|| {
+                format!(
+                    "shard count {n} is out of range ({}..={})",
+                    Sharding::S2.count(),
+                    Sharding::S65536.count()
+                )
+            })?;
+
+        sharding.validate()?;
+        Ok(sharding)
+    }
+}
+
+impl TryFrom<u32> for Sharding {
+    type Error = Error;
+
+    fn try_from(n: u32) -> Result<Self, Self::Error> {
+        Sharding::try_from(n as usize)
+    }
+}
+
+impl From<Sharding> for u32 {
+    fn from(s: Sharding) -> u32 {
+        s.count() as u32
+    }
+}
+
+/// Formats as the variant name, e.g. `S1024`.
+impl fmt::Display for Sharding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "S{}", self.count())
+    }
+}
+
+/// Parses either the variant name (`S1024`) or the plain shard count (`1024`).
+impl FromStr for Sharding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s.strip_prefix('S').unwrap_or(s);
+
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("invalid sharding {s:?}, expected e.g. \"S1024\" or \"1024\"");
+        }
+
+        let n: usize = digits
+            .parse()
+            .with_context(|| format!("shard count {digits} is out of range"))?;
+
+        Sharding::try_from(n)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Sharding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Accepts the same forms as [`FromStr`], plus a bare integer count in
+/// human-readable formats. Binary formats, which cannot say what comes next,
+/// read the string [`Serialize`] writes.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Sharding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = Sharding;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a shard count like \"S1024\", \"1024\" or 1024")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Sharding, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Sharding, E> {
+                usize::try_from(v)
+                    .map_err(Error::from)
+                    .and_then(Sharding::try_from)
+                    .map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Sharding, E> {
+                match u64::try_from(v) {
+                    Ok(v) => self.visit_u64(v),
+                    Err(_) => Err(E::custom(format!("shard count {v} is negative"))),
+                }
+            }
+        }
+
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(Visitor)
+        } else {
+            deserializer.deserialize_str(Visitor)
+        }
     }
 }
 
@@ -37,20 +238,151 @@ mod tests {
 
     use super::*;
 
-    const ALL: [Sharding; 12] = [
-        Sharding::S2,
-        Sharding::S4,
-        Sharding::S8,
-        Sharding::S16,
-        Sharding::S32,
-        Sharding::S64,
-        Sharding::S128,
-        Sharding::S256,
-        Sharding::S512,
-        Sharding::S1024,
-        Sharding::S2048,
-        Sharding::S4096,
-    ];
+    const ALL: [Sharding; 16] = Sharding::ALL;
+
+    #[test]
+    fn for_expected_users_bluesky_scale() {
+        // scenarios from examples/footprint.rs
+        assert_eq!(
+            Sharding::for_expected_users(42_500_000, 16),
+            Sharding::S1024
+        );
+        assert_eq!(Sharding::for_expected_users(4_000_000, 16), Sharding::S64);
+        assert_eq!(
+            Sharding::for_expected_users(100_000_000, 16),
+            Sharding::S2048
+        );
+        assert_eq!(
+            Sharding::for_expected_users(500_000_000, 16),
+            Sharding::S4096
+        );
+    }
+
+    #[test]
+    fn for_expected_users_bounds() {
+        assert_eq!(Sharding::for_expected_users(0, 0), Sharding::S2);
+        assert_eq!(Sharding::for_expected_users(10, 1), Sharding::S4);
+        assert_eq!(Sharding::for_expected_users(10, 64), Sharding::S256);
+        assert_eq!(
+            Sharding::for_expected_users(u64::MAX, usize::MAX),
+            Sharding::S4096
+        );
+    }
+
+    #[test]
+    fn from_count_accepts_exact_powers() -> Result<()> {
+        for s in ALL.iter().filter(|s| s.validate().is_ok()) {
+            assert_eq!(Sharding::from_count(s.count()), Some(*s));
+            assert_eq!(Sharding::try_from(s.count())?, *s);
+        }
+
+        assert_eq!(Sharding::from_count(0), None);
+        assert_eq!(Sharding::from_count(1), None);
+        assert_eq!(Sharding::from_count(48), None);
+        assert!(Sharding::try_from(1usize << 17).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn display_from_str_round_trip() -> Result<()> {
+        for s in ALL.iter().filter(|s| s.validate().is_ok()) {
+            assert_eq!(s.to_string(), format!("{s:?}"));
+            assert_eq!(s.to_string().parse::<Sharding>()?, *s);
+            assert_eq!(s.count().to_string().parse::<Sharding>()?, *s);
+            assert_eq!(Sharding::try_from(u32::from(*s))?, *s);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn from_str_errors_are_descriptive() {
+        let err = |s: &str| s.parse::<Sharding>().unwrap_err().to_string();
+
+        assert!(err("48").contains("not a power of two"), "{}", err("48"));
+        assert!(err("S1").contains("out of range"), "{}", err("S1"));
+        assert!(err("131072").contains("out of range"), "{}", err("131072"));
+        assert!(err("S").contains("invalid sharding"), "{}", err("S"));
+        assert!(err("s64").contains("invalid sharding"), "{}", err("s64"));
+        assert!(err("-2").contains("invalid sharding"), "{}", err("-2"));
+        assert!(
+            err("99999999999999999999999").contains("out of range"),
+            "{}",
+            err("99999999999999999999999")
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() -> Result<()> {
+        assert_eq!(serde_json::to_string(&Sharding::S1024)?, r#""S1024""#);
+
+        for s in ALL.iter().filter(|s| s.validate().is_ok()) {
+            let json = serde_json::to_string(s)?;
+            assert_eq!(serde_json::from_str::<Sharding>(&json)?, *s);
+        }
+
+        assert_eq!(serde_json::from_str::<Sharding>("1024")?, Sharding::S1024);
+        assert_eq!(serde_json::from_str::<Sharding>(r#""64""#)?, Sharding::S64);
+        assert!(serde_json::from_str::<Sharding>("48").is_err());
+        assert!(serde_json::from_str::<Sharding>("-2").is_err());
+        Ok(())
+    }
+
+    /// Answers only typed requests, like a format that is not self-describing.
+    #[cfg(feature = "serde")]
+    struct Binary<'a>(&'a str);
+
+    #[cfg(feature = "serde")]
+    impl<'de> Deserializer<'de> for Binary<'_> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("deserialize_any is not supported"))
+        }
+
+        fn deserialize_str<V: de::Visitor<'de>>(self, v: V) -> Result<V::Value, Self::Error> {
+            v.visit_str(self.0)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            false
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct
+            map struct enum identifier ignored_any
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_without_self_description() {
+        assert_eq!(Sharding::deserialize(Binary("S1024")), Ok(Sharding::S1024));
+        assert_eq!(Sharding::deserialize(Binary("64")), Ok(Sharding::S64));
+        assert!(Sharding::deserialize(Binary("S48")).is_err());
+    }
+
+    #[test]
+    fn validate_against_uid_width() {
+        for s in &ALL {
+            assert_eq!(
+                s.validate().is_ok(),
+                s.bits() < Uid::BITS,
+                "{s:?} with {}-bit uids",
+                Uid::BITS
+            );
+            assert_eq!(
+                Sharding::from_count(s.count()).is_some(),
+                s.bits() < Uid::BITS
+            );
+        }
+
+        // every enum variant must stay usable with the default 32-bit uids
+        assert!(ALL.iter().all(|s| s.bits() < u32::BITS));
+        // the heuristic never picks a sharding too wide for the narrowest uid
+        assert!(Sharding::S4096.bits() < u16::BITS);
+    }
 
     #[test]
     fn mask_equals_count_minus_one() {
@@ -64,6 +396,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn bits_count_mask() {
+        for (i, s) in ALL.iter().enumerate() {
+            let bits = i as u32 + 1;
+            assert_eq!(s.bits(), bits, "{s:?}: bits");
+            assert_eq!(s.count(), 1 << bits, "{s:?}: count");
+            assert_eq!(s.mask(), (1 << bits) - 1, "{s:?}: mask");
+        }
+
+        assert_eq!(Sharding::S65536.mask(), 0xFFFF);
+    }
+
     #[test]
     fn variant_name_matches_count() -> Result<()> {
         for s in &ALL {
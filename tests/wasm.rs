@@ -0,0 +1,87 @@
+//! The read path without a filesystem. Runs natively as a plain integration
+//! test and in the browser or node under `wasm32-unknown-unknown` through
+//! wasm-bindgen-test:
+//!
+//! ```sh
+//! wasm-pack test --node -- --test wasm
+//! ```
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use crimeline::arena::{Cold, Hot};
+use crimeline::{
+    Order, Relationships, ReportUsage, Sharding, Timeline, Uid, UserMap, Visibility, Window,
+};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen_test::wasm_bindgen_test as test;
+
+/// A parquet export, as a viewer would fetch it.
+fn exported(epoch: u64, n: u64) -> Bytes {
+    let mut hot = Hot::new(Window::new(epoch, 100)).unwrap();
+    for i in 0..n {
+        hot.add(
+            (i % 4) as Uid,
+            epoch + i,
+            epoch + n - 1 - i,
+            format!("post {i}").as_bytes(),
+        )
+        .unwrap();
+    }
+
+    let cold: Arc<Cold> = hot.try_into().unwrap();
+    let mut buf = Vec::new();
+    cold.export(&mut buf).unwrap();
+    Bytes::from(buf)
+}
+
+#[test]
+fn import_and_iterate() {
+    let cold = Cold::import(exported(1000, 20)).unwrap();
+    assert_eq!(cold.len(), 20);
+
+    let timestamps: Vec<u64> = cold.iter(Order::Asc, 1015).map(|e| e.timestamp()).collect();
+    assert_eq!(timestamps, [1015, 1016, 1017, 1018, 1019]);
+
+    let entry = cold.get(1003).unwrap();
+    assert_eq!(entry.resolve(), (1003, b"post 3".as_slice()));
+
+    if cfg!(target_arch = "wasm32") {
+        let usage = cold.usage();
+        assert_eq!((usage.disk, usage.mapped), (0, 0), "blobs live on the heap");
+    }
+}
+
+#[test]
+fn timeline_slices_across_arenas() {
+    let timeline = Timeline::new(vec![
+        Cold::import(exported(1000, 10)).unwrap(),
+        Cold::import(exported(1100, 10)).unwrap(),
+    ]);
+
+    let mut slice = timeline.iter(1105, Order::Desc);
+    let mut seen = Vec::new();
+    while let Some(e) = slice.next() {
+        seen.push(e.timestamp());
+    }
+    assert_eq!(seen, [1109, 1108, 1107, 1106, 1105]);
+
+    let mut all = timeline.iter_all(Order::Asc);
+    assert_eq!(all.next().map(|e| e.timestamp()), Some(1000));
+    assert_eq!(timeline.resolve(1104).map(|(ts, _)| ts), Some(1105));
+}
+
+#[test]
+fn relationships_in_memory() {
+    let map = UserMap::new(Sharding::S16);
+    map.add_bulk(1, [2, 3, 4]);
+    assert_eq!(map.targets(1), [2, 3, 4]);
+    assert_eq!(map.sources(3), [1]);
+
+    let rels = Relationships::new(Sharding::S16);
+    rels.follows.add(1, 2);
+    rels.blocks.add(3, 1);
+    assert_eq!(rels.visibility(1, 2), Visibility::Followed);
+    assert_eq!(rels.visibility(1, 3), Visibility::Hidden);
+}